@@ -0,0 +1,123 @@
+use std::path::Path;
+
+use error_stack::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::core::storage::{self, JsonFileError};
+use crate::core::title::normalize_title;
+
+pub const DEFAULT_ALIASES_PATH: &str = "aliases.json";
+
+// Well known series whose English, romaji and Japanese titles share nothing a fuzzy match
+// could find. The aliases file adds to these
+const BUILTIN_ALIASES: [&[&str]; 10] = [
+    &["Shingeki no Kyojin", "Attack on Titan", "進撃の巨人"],
+    &["Kimetsu no Yaiba", "Demon Slayer", "鬼滅の刃"],
+    &[
+        "Boku no Hero Academia",
+        "My Hero Academia",
+        "僕のヒーローアカデミア",
+    ],
+    &[
+        "Re:Zero kara Hajimeru Isekai Seikatsu",
+        "Re:Zero",
+        "Re:ゼロから始める異世界生活",
+    ],
+    &[
+        "Yakusoku no Neverland",
+        "The Promised Neverland",
+        "約束のネバーランド",
+    ],
+    &["Jujutsu Kaisen", "呪術廻戦"],
+    &[
+        "Sousou no Frieren",
+        "Frieren: Beyond Journey's End",
+        "葬送のフリーレン",
+    ],
+    &[
+        "Kaguya-sama wa Kokurasetai",
+        "Kaguya-sama: Love is War",
+        "かぐや様は告らせたい",
+    ],
+    &["Bocchi the Rock!", "ぼっち・ざ・ろっく！"],
+    &[
+        "Kusuriya no Hitorigoto",
+        "The Apothecary Diaries",
+        "薬屋のひとりごと",
+    ],
+];
+
+// Groups of titles naming the same series, e.g. [["Shingeki no Kyojin", "Attack on Titan"]]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TitleAliases(pub Vec<Vec<String>>);
+
+impl TitleAliases {
+    pub fn builtin() -> Self {
+        TitleAliases(
+            BUILTIN_ALIASES
+                .iter()
+                .map(|titles| titles.iter().map(|title| title.to_string()).collect())
+                .collect(),
+        )
+    }
+
+    // The aliases of the file, followed by the builtin ones
+    pub fn load(path: &Path) -> Result<Self, JsonFileError> {
+        let mut aliases: TitleAliases = storage::load_json(path)?;
+        aliases.0.extend(TitleAliases::builtin().0);
+        Ok(aliases)
+    }
+
+    // Titles found for a series at runtime (e.g. from its metadata) take precedence
+    pub fn add(&mut self, titles: Vec<String>) {
+        self.0.insert(0, titles);
+    }
+
+    // The title followed by the other titles of its series, if it has aliases
+    pub fn expand(&self, title: &str) -> Vec<String> {
+        let normalized = normalize_title(title);
+        let mut titles = vec![title.to_owned()];
+
+        let groups = self.0.iter().filter(|group| {
+            group
+                .iter()
+                .any(|alias| normalize_title(alias) == normalized)
+        });
+        for alias in groups.flatten() {
+            if !titles.contains(alias) {
+                titles.push(alias.clone());
+            }
+        }
+
+        titles
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expand() {
+        let aliases = TitleAliases::builtin();
+
+        assert_eq!(
+            aliases.expand("attack on titan"),
+            vec![
+                "attack on titan",
+                "Shingeki no Kyojin",
+                "Attack on Titan",
+                "進撃の巨人"
+            ]
+        );
+        assert_eq!(aliases.expand("進撃の巨人")[1], "Shingeki no Kyojin");
+        assert_eq!(aliases.expand("Bocchi"), vec!["Bocchi"]);
+    }
+
+    #[test]
+    fn test_load_adds_builtin() {
+        let aliases = TitleAliases::load(Path::new("does-not-exist.json")).unwrap();
+
+        assert_eq!(aliases.0.len(), BUILTIN_ALIASES.len());
+    }
+}
@@ -20,10 +20,13 @@ pub trait StringDownloader {
     fn download_uri(&self, uri: &Uri) -> Result<String, StringDownloaderError>;
     fn download_indexer(&self, indexer: &Indexer) -> Result<String, StringDownloaderError>;
 
+    #[allow(dead_code)]
     #[deprecated(note = "Use download_indexer in a loop instead")]
     fn download_indexers(&self, indexers: &[Indexer]) -> Result<Vec<String>, StringDownloaderError>;
 }
 
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
 pub enum Destination {
     GivenFolderGivenFileBasename(String /* folder */, String /* file basename */),
     GivenFolderGuessFileBasename(String /* folder */),
@@ -54,6 +57,7 @@ pub trait FileDownloader {
         dest: &Destination,
     ) -> Result<(), FileDownloaderError>;
 
+    #[allow(dead_code)]
     #[deprecated(note = "Use download_indexer_to_file in a loop instead")]
     fn download_indexers_to_file(
         &self,
@@ -30,7 +30,7 @@ impl StringDownloader for ReqwestDownloader {
         log::debug!("Download complete from uri: {}", uri);
         log::trace!("Downloaded text: {}", response_text);
 
-        return Ok(response_text);
+        Ok(response_text)
     }
 
     fn download_indexer(&self, indexer: &Indexer) -> Result<String, StringDownloaderError> {
@@ -54,6 +54,6 @@ impl StringDownloader for ReqwestDownloader {
             let result = self.download_indexer(indexer)?;
             results.push(result);
         }
-        return Ok(results);
+        Ok(results)
     }
 }
@@ -0,0 +1,191 @@
+use std::ops::RangeInclusive;
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+lazy_static! {
+    // Ordered from the most to the least explicit way of writing an episode number
+    static ref EPISODE_REGEXES: Vec<Regex> = [
+        r"第\s*(\d{1,4})\s*[話话回]",
+        r"(?i)\bS\d{1,2}\s*E(\d{1,4})\b",
+        r"(?i)\b(?:Episode|Ep|E)\s*\.?\s*(\d{1,4})(?:v\d+)?\b",
+        r"\s-\s(\d{1,4})(?:v\d+)?(?:[\s.\[(]|$)",
+        r"#(\d{1,4})\b",
+        // Four digits only outside of 19xx and 20xx, which are the year of the release
+        r"\((\d{1,3}|[03-9]\d{3}|1[0-8]\d{2}|2[1-9]\d{2})\)",
+    ]
+    .iter()
+    .map(|regex| match Regex::new(regex) {
+        Ok(regex) => regex,
+        Err(error) => panic!("Failed to create regex for episode number, error: {}", error),
+    })
+    .collect();
+
+    static ref SEASON_EPISODE_REGEX: Regex = match Regex::new(r"(?i)\bS(\d{1,2})\s*E\d{1,4}\b") {
+        Ok(regex) => regex,
+        Err(error) => panic!("Failed to create regex for season number, error: {}", error),
+    };
+
+    // Release group, resolution and other tags, e.g. "[Ohys-Raws]" or "(BS11)"
+    static ref TAG_REGEX: Regex = match Regex::new(r"\[[^\]]*\]|\([^)]*\)") {
+        Ok(regex) => regex,
+        Err(error) => panic!("Failed to create regex for release tags, error: {}", error),
+    };
+}
+
+// Finds the episode number in a raw or subtitle file name, e.g. "[Ohys-Raws] Bocchi - 06 (AT-X 1280x720).mp4"
+pub fn parse_episode_number(file_name: &str) -> Option<u32> {
+    // Folders inside batch torrents may contain numbers too (e.g. "Season 2")
+    let file_name = file_name.rsplit(['/', '\\']).next().unwrap_or(file_name);
+
+    EPISODE_REGEXES
+        .iter()
+        .find_map(|regex| regex.captures(file_name))
+        .and_then(|capture| capture[1].parse().ok())
+}
+
+// The season of a file numbered like "Bocchi.the.Rock.S02E04.mkv"
+pub fn parse_season_number(file_name: &str) -> Option<u32> {
+    let file_name = file_name.rsplit(['/', '\\']).next().unwrap_or(file_name);
+
+    SEASON_EPISODE_REGEX
+        .captures(file_name)
+        .and_then(|capture| capture[1].parse().ok())
+}
+
+// The part of a file name before its episode number, without release tags or extension,
+// e.g. "Bocchi the Rock" for "[SubsPlease] Bocchi the Rock - 12 [1080p].mkv"
+pub fn parse_series_name(file_name: &str) -> Option<String> {
+    let file_name = file_name.rsplit(['/', '\\']).next().unwrap_or(file_name);
+
+    let episode_start = EPISODE_REGEXES
+        .iter()
+        .find_map(|regex| regex.find(file_name))?
+        .start();
+
+    let series_name = TAG_REGEX
+        .replace_all(&file_name[..episode_start], " ")
+        .replace(['.', '_'], " ");
+    let series_name = series_name
+        .trim_matches(|c: char| c.is_whitespace() || "-([#".contains(c))
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    match series_name.is_empty() {
+        true => None,
+        false => Some(series_name),
+    }
+}
+
+// Parses "5" or "5-7" into an inclusive range of episodes, used as a clap value parser
+pub fn parse_episode_range(value: &str) -> Result<RangeInclusive<u32>, String> {
+    let parse = |number: &str| {
+        number
+            .trim()
+            .parse::<u32>()
+            .map_err(|e| format!("Invalid episode number '{}': {}", number, e))
+    };
+
+    let range = match value.split_once('-') {
+        Some((start, end)) => parse(start)?..=parse(end)?,
+        None => parse(value)?..=parse(value)?,
+    };
+
+    if range.is_empty() {
+        return Err(format!("Episode range '{}' is empty", value));
+    }
+
+    Ok(range)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_episode_number_dash() {
+        assert_eq!(
+            parse_episode_number(
+                "[Ohys-Raws] Bougyoryoku 2 - 06 (AT-X 1280x720 x264 AAC).mp4"
+            ),
+            Some(6)
+        );
+        assert_eq!(
+            parse_episode_number("[SubsPlease] Bocchi the Rock! - 12v2 [1080p].mkv"),
+            Some(12)
+        );
+    }
+
+    #[test]
+    fn test_parse_episode_number_markers() {
+        assert_eq!(parse_episode_number("ぼっち・ざ・ろっく！ 第3話.ass"), Some(3));
+        assert_eq!(parse_episode_number("Bocchi.the.Rock.S01E04.1080p.mkv"), Some(4));
+        assert_eq!(parse_episode_number("Bocchi the Rock EP05.srt"), Some(5));
+        assert_eq!(parse_episode_number("ぼっち・ざ・ろっく！ #07.ass"), Some(7));
+    }
+
+    #[test]
+    fn test_parse_episode_number_parenthesis() {
+        assert_eq!(
+            parse_episode_number("[Fumi-Raws] (One Piece (1051) - (フジテレビ 1920x1080).mkv"),
+            Some(1051)
+        );
+    }
+
+    #[test]
+    fn test_parse_episode_number_ignores_years() {
+        assert_eq!(parse_episode_number("Show (2023) - 05.mkv"), Some(5));
+        assert_eq!(parse_episode_number("Show (1998) (12).mkv"), Some(12));
+        assert_eq!(parse_episode_number("Show (2023).mkv"), None);
+        assert_eq!(
+            parse_series_name("Show (2023) - 05.mkv"),
+            Some("Show".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_episode_number_ignores_folders_and_resolution() {
+        assert_eq!(
+            parse_episode_number("Season 2 - 1080p/Bocchi the Rock! - 03.mkv"),
+            Some(3)
+        );
+        assert_eq!(parse_episode_number("Bocchi the Rock! 1920x1080.mkv"), None);
+        assert_eq!(parse_episode_number("readme.txt"), None);
+    }
+
+    #[test]
+    fn test_parse_season_number() {
+        assert_eq!(parse_season_number("Bocchi.the.Rock.S02E04.1080p.mkv"), Some(2));
+        assert_eq!(parse_season_number("Season 3/Bocchi the Rock! - 04.mkv"), None);
+    }
+
+    #[test]
+    fn test_parse_series_name() {
+        assert_eq!(
+            parse_series_name("[SubsPlease] Bocchi the Rock! - 12v2 [1080p].mkv"),
+            Some("Bocchi the Rock!".to_string())
+        );
+        assert_eq!(
+            parse_series_name("Season 2/Bocchi.the.Rock.S01E04.1080p.mkv"),
+            Some("Bocchi the Rock".to_string())
+        );
+        assert_eq!(
+            parse_series_name("[Fumi-Raws] (One Piece (1051) - (フジテレビ 1920x1080).mkv"),
+            Some("One Piece".to_string())
+        );
+        assert_eq!(
+            parse_series_name("ぼっち・ざ・ろっく！ 第3話.ass"),
+            Some("ぼっち・ざ・ろっく！".to_string())
+        );
+        assert_eq!(parse_series_name("Episode 03.mkv"), None);
+    }
+
+    #[test]
+    fn test_parse_episode_range() {
+        assert_eq!(parse_episode_range("5"), Ok(5..=5));
+        assert_eq!(parse_episode_range("5-7"), Ok(5..=7));
+        assert!(parse_episode_range("7-5").is_err());
+        assert!(parse_episode_range("five").is_err());
+    }
+}
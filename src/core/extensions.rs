@@ -0,0 +1,4 @@
+// Formats players load as subtitles. The subs downloader saves them as they are
+pub const SUBTITLE_EXTENSIONS: [&str; 4] = ["srt", "ass", "ssa", "vtt"];
+// Kitsunekko also has whole seasons in archives, which the subs downloader can't extract
+pub const ARCHIVE_EXTENSIONS: [&str; 3] = ["zip", "rar", "7z"];
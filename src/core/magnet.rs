@@ -0,0 +1,294 @@
+use std::error::Error;
+use std::fmt::Write;
+
+use derive_getters::Getters;
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+
+use error_stack::{IntoReport, Report, Result, ResultExt};
+
+const MAGNET_PREFIX: &str = "magnet:?";
+const BTIH_PREFIX: &str = "urn:btih:";
+const BTMH_PREFIX: &str = "urn:btmh:";
+// Multihash header for a 32 bytes sha2-256 digest, the only one BitTorrent v2 uses
+const BTMH_SHA256_HEADER: &str = "1220";
+
+// Same escaping nyaa uses, so scraped magnets serialize back to the same string
+const MAGNET_VALUE: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+#[derive(Debug)]
+pub struct MagnetLinkError;
+
+impl std::fmt::Display for MagnetLinkError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Invalid magnet link")
+    }
+}
+
+impl Error for MagnetLinkError {}
+
+#[derive(Debug, Hash, Eq, PartialEq, Clone, Getters)]
+pub struct MagnetLink {
+    btih: Option<String>, /* v1 info-hash, lowercase hex */
+    btmh: Option<String>, /* v2 info-hash, lowercase hex multihash */
+    display_name: Option<String>,
+    trackers: Vec<String>,
+    exact_length: Option<u64>,
+}
+
+impl MagnetLink {
+    pub fn parse(uri: &str) -> Result<Self, MagnetLinkError> {
+        let uri = html_unescape(uri.trim());
+
+        let query = uri.strip_prefix(MAGNET_PREFIX).ok_or_else(|| {
+            Report::new(MagnetLinkError).attach_printable(format!(
+                "Uri doesn't start with '{}': {}",
+                MAGNET_PREFIX, uri
+            ))
+        })?;
+
+        let mut magnet = MagnetLink {
+            btih: None,
+            btmh: None,
+            display_name: None,
+            trackers: Vec::new(),
+            exact_length: None,
+        };
+
+        for param in query.split('&').filter(|param| !param.is_empty()) {
+            let (key, value) = param.split_once('=').unwrap_or((param, ""));
+            // Form encoded values (e.g. nyaa's "Show+-+01") use '+' for spaces, a plus is %2B
+            let value = value.replace('+', " ");
+            let value = percent_decode_str(&value)
+                .decode_utf8()
+                .into_report()
+                .attach_printable_lazy(|| format!("Parameter is not valid utf-8: {}", param))
+                .change_context(MagnetLinkError)?;
+
+            // Multiple exact topics are numbered as xt.1, xt.2...
+            match key.split('.').next().unwrap_or(key) {
+                "xt" => magnet.parse_exact_topic(&value)?,
+                "dn" => magnet.display_name = Some(value.into_owned()),
+                "tr" => magnet.trackers.push(value.into_owned()),
+                "xl" => {
+                    let exact_length = value
+                        .parse()
+                        .into_report()
+                        .attach_printable_lazy(|| format!("Invalid exact length: {}", value))
+                        .change_context(MagnetLinkError)?;
+                    magnet.exact_length = Some(exact_length);
+                }
+                _ => log::trace!("Ignoring magnet parameter: {}", param),
+            }
+        }
+
+        if magnet.btih.is_none() && magnet.btmh.is_none() {
+            return Err(Report::new(MagnetLinkError)
+                .attach_printable(format!("Magnet has no info-hash: {}", uri)));
+        }
+
+        Ok(magnet)
+    }
+
+    // The id qBittorrent reports for this torrent: the v1 hash, or the truncated v2 hash
+    pub fn info_hash(&self) -> &str {
+        match (&self.btih, &self.btmh) {
+            (Some(btih), _) => btih,
+            (None, Some(btmh)) => &btmh[BTMH_SHA256_HEADER.len()..BTMH_SHA256_HEADER.len() + 40],
+            (None, None) => unreachable!("MagnetLink is always parsed with an info-hash"),
+        }
+    }
+
+    fn parse_exact_topic(&mut self, topic: &str) -> Result<(), MagnetLinkError> {
+        if let Some(hash) = strip_prefix_ignore_case(topic, BTIH_PREFIX) {
+            self.btih = Some(parse_btih(hash)?);
+        } else if let Some(hash) = strip_prefix_ignore_case(topic, BTMH_PREFIX) {
+            self.btmh = Some(parse_btmh(hash)?);
+        } else {
+            log::trace!("Ignoring unsupported exact topic: {}", topic);
+        }
+
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for MagnetLink {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let mut params = Vec::new();
+
+        if let Some(btih) = &self.btih {
+            params.push(format!("xt={}{}", BTIH_PREFIX, btih));
+        }
+        if let Some(btmh) = &self.btmh {
+            params.push(format!("xt={}{}", BTMH_PREFIX, btmh));
+        }
+        if let Some(display_name) = &self.display_name {
+            params.push(format!(
+                "dn={}",
+                utf8_percent_encode(display_name, MAGNET_VALUE)
+            ));
+        }
+        if let Some(exact_length) = &self.exact_length {
+            params.push(format!("xl={}", exact_length));
+        }
+        for tracker in &self.trackers {
+            params.push(format!("tr={}", utf8_percent_encode(tracker, MAGNET_VALUE)));
+        }
+
+        write!(f, "{}{}", MAGNET_PREFIX, params.join("&"))
+    }
+}
+
+fn strip_prefix_ignore_case<'a>(value: &'a str, prefix: &str) -> Option<&'a str> {
+    match value.get(..prefix.len()) {
+        Some(start) if start.eq_ignore_ascii_case(prefix) => Some(&value[prefix.len()..]),
+        _ => None,
+    }
+}
+
+fn is_hex(value: &str) -> bool {
+    value.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+// v1 hashes come either as 40 hex chars or as 32 base32 chars
+fn parse_btih(hash: &str) -> Result<String, MagnetLinkError> {
+    match hash.len() {
+        40 if is_hex(hash) => Ok(hash.to_lowercase()),
+        32 => base32_to_hex(hash).ok_or_else(|| {
+            Report::new(MagnetLinkError)
+                .attach_printable(format!("Invalid base32 btih info-hash: {}", hash))
+        }),
+        _ => Err(Report::new(MagnetLinkError)
+            .attach_printable(format!("Invalid btih info-hash: {}", hash))),
+    }
+}
+
+fn parse_btmh(hash: &str) -> Result<String, MagnetLinkError> {
+    let is_sha256_multihash = hash.len() == BTMH_SHA256_HEADER.len() + 64
+        && hash.starts_with(BTMH_SHA256_HEADER)
+        && is_hex(hash);
+
+    if !is_sha256_multihash {
+        return Err(Report::new(MagnetLinkError)
+            .attach_printable(format!("Invalid btmh info-hash: {}", hash)));
+    }
+
+    Ok(hash.to_lowercase())
+}
+
+fn base32_to_hex(value: &str) -> Option<String> {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+    let mut bits: u64 = 0;
+    let mut bit_count = 0;
+    let mut hex = String::new();
+
+    for c in value.to_ascii_uppercase().bytes() {
+        let index = ALPHABET.iter().position(|&a| a == c)?;
+        bits = (bits << 5) | index as u64;
+        bit_count += 5;
+
+        if bit_count >= 8 {
+            bit_count -= 8;
+            write!(hex, "{:02x}", (bits >> bit_count) & 0xff).ok()?;
+        }
+    }
+
+    Some(hex)
+}
+
+// Scraped pages escape the '&' between parameters, among other things
+// "&amp;" goes last, so an escaped entity like "&amp;lt;" stays "&lt;"
+fn html_unescape(value: &str) -> String {
+    value
+        .replace("&quot;", "\"")
+        .replace("&#34;", "\"")
+        .replace("&#39;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&#38;", "&")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NYAA_MAGNET: &str = "magnet:?xt=urn:btih:568807a73ecd33fff3ac19f47805f0940cbdb9ac&amp;dn=%5BFumi-Raws%5D%20%28One%20Piece%20%281051%29%20-%20%28%E3%83%95%E3%82%B8%E3%83%86%E3%83%AC%E3%83%93%201920x1080%29.mkv&amp;tr=http%3A%2F%2Fnyaa.tracker.wf%3A7777%2Fannounce&amp;tr=udp%3A%2F%2Fopen.stealth.si%3A80%2Fannounce";
+
+    #[test]
+    fn test_parse_html_escaped() {
+        let magnet = MagnetLink::parse(NYAA_MAGNET).unwrap();
+        assert_eq!(
+            magnet.info_hash(),
+            "568807a73ecd33fff3ac19f47805f0940cbdb9ac"
+        );
+        assert_eq!(
+            magnet.display_name().as_deref(),
+            Some("[Fumi-Raws] (One Piece (1051) - (フジテレビ 1920x1080).mkv")
+        );
+        assert_eq!(
+            magnet.trackers(),
+            &vec![
+                "http://nyaa.tracker.wf:7777/announce".to_string(),
+                "udp://open.stealth.si:80/announce".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_serialize_round_trip() {
+        let magnet = MagnetLink::parse(NYAA_MAGNET).unwrap();
+        assert_eq!(magnet.to_string(), NYAA_MAGNET.replace("&amp;", "&"));
+        assert_eq!(MagnetLink::parse(&magnet.to_string()).unwrap(), magnet);
+    }
+
+    #[test]
+    fn test_parse_form_encoded_display_name() {
+        let magnet = MagnetLink::parse(
+            "magnet:?xt=urn:btih:568807a73ecd33fff3ac19f47805f0940cbdb9ac&dn=Show+-+01+%2B+Extra",
+        )
+        .unwrap();
+        assert_eq!(magnet.display_name().as_deref(), Some("Show - 01 + Extra"));
+    }
+
+    #[test]
+    fn test_html_unescape() {
+        assert_eq!(html_unescape("a&amp;b&lt;c"), "a&b<c");
+        assert_eq!(html_unescape("&amp;lt;"), "&lt;");
+    }
+
+    #[test]
+    fn test_parse_base32_btih() {
+        let magnet =
+            MagnetLink::parse("magnet:?xt=urn:btih:k2eapjz6zuz7745mdh2hqbpqsqgl3onm").unwrap();
+        assert_eq!(
+            magnet.info_hash(),
+            "568807a73ecd33fff3ac19f47805f0940cbdb9ac"
+        );
+    }
+
+    #[test]
+    fn test_parse_btmh() {
+        let btmh = "1220caf1e1c30e81cb361b9ee167c4aa64228a7fa4fa9f6105232b28ad099f3a302e";
+        let magnet = MagnetLink::parse(&format!("magnet:?xt=urn:btmh:{}&xl=1024", btmh)).unwrap();
+        assert_eq!(magnet.btmh().as_deref(), Some(btmh));
+        assert_eq!(magnet.info_hash(), &btmh[4..44]);
+        assert_eq!(magnet.exact_length(), &Some(1024));
+    }
+
+    #[test]
+    fn test_parse_rejects_invalid() {
+        assert!(MagnetLink::parse("__BROKEN__MAGNET__LINK__").is_err());
+        assert!(MagnetLink::parse("magnet:?dn=no%20hash").is_err());
+        assert!(MagnetLink::parse("magnet:?xt=urn:btih:1234").is_err());
+        assert!(
+            MagnetLink::parse("magnet:?xt=urn:btih:zz8807a73ecd33fff3ac19f47805f0940cbdb9ac")
+                .is_err()
+        );
+        assert!(MagnetLink::parse("magnet:?xt=urn:btmh:1220abc").is_err());
+    }
+}
@@ -1,3 +1,10 @@
+pub mod aliases;
 pub mod download;
+pub mod episode;
+pub mod extensions;
 pub mod scrapper;
-pub mod indexer;
\ No newline at end of file
+pub mod indexer;
+pub mod magnet;
+pub mod season;
+pub mod storage;
+pub mod title;
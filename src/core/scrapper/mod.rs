@@ -23,7 +23,7 @@ impl<T> HttpScrapper<T> where T: ScrapperData {
     }
 
     pub fn scrap_raw_data(&self, data: &str) -> Result<Vec<T>, Box<dyn std::error::Error>> {
-        let capture_matches = self.inner_regex.captures_iter(&data);
+        let capture_matches = self.inner_regex.captures_iter(data);
         let data = T::from_captures(capture_matches);
         Ok(data)
     }
@@ -0,0 +1,298 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+
+lazy_static! {
+    // Matched against normalized titles (see title::normalize_title), so kana markers are in
+    // romaji and long vowels are folded
+    static ref SEASON_REGEXES: Vec<Regex> = create_season_regexes(&[
+        r"\b(\d+)(?:st|nd|rd|th) season\b",
+        r"\bseason (\d+)\b",
+        r"\bs(\d+)\b",
+        r"第(\d+)期",
+    ]);
+    // e.g. "Bocchi the Rock 2", a number too small to be a year. Only a season when the title
+    // without it names the series too, "Kaiju No. 8" and "Steins;Gate 0" are whole titles
+    static ref BARE_SEASON_REGEX: Regex = create_season_regex(r" ([1-9]\d?)$");
+    static ref PART_REGEXES: Vec<Regex> = create_season_regexes(&[
+        r"\bpart (\d+)\b",
+        r"\bcour (\d+)\b",
+        r"第(\d+)kuru",
+    ]);
+    // Sequels without a number, e.g. "ReLife Kanketsu Hen"
+    static ref SEQUEL_REGEX: Regex = create_season_regex(r"\bkanketsu ?hen\b|\bzoku\b|完結編");
+    // e.g. "Shingeki no Kyojin The Final Season", a sequel whose number isn't known
+    static ref FINAL_SEASON_REGEX: Regex = create_season_regex(r"\b(?:the )?final season\b");
+    static ref OVA_REGEX: Regex = create_season_regex(r"\b(?:ova|oad|ona)s?\b");
+    static ref MOVIE_REGEX: Regex =
+        create_season_regex(r"\b(?:the movie|movie|film|gekijoban)\b|劇場版");
+}
+
+fn create_season_regex(regex: &str) -> Regex {
+    match Regex::new(regex) {
+        Ok(regex) => regex,
+        Err(error) => panic!("Failed to create regex for seasons, error: {}", error),
+    }
+}
+
+fn create_season_regexes(regexes: &[&str]) -> Vec<Regex> {
+    regexes
+        .iter()
+        .map(|regex| create_season_regex(regex))
+        .collect()
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum ReleaseKind {
+    #[default]
+    Series,
+    Ova,
+    Movie,
+}
+
+// What part of a franchise a title names, as far as its name tells
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct SeasonInfo {
+    pub season: Option<u32>,
+    pub part: Option<u32>,
+    // Set for numbered seasons after the first and for unnumbered sequels
+    pub sequel: bool,
+    pub final_season: bool,
+    pub kind: ReleaseKind,
+}
+
+impl SeasonInfo {
+    // A series without any marker is its first season
+    fn season_number(&self) -> Option<u32> {
+        match (self.season, self.sequel) {
+            (Some(season), _) => Some(season),
+            (None, false) => Some(1),
+            (None, true) => None,
+        }
+    }
+
+    // How well a candidate fits what was asked, 0 being a full match. None when it clearly
+    // names another season, part or kind of release than the one asked for
+    pub fn agreement(&self, candidate: &SeasonInfo) -> Option<i64> {
+        let mut score = 0;
+
+        if self.kind != candidate.kind {
+            match self.kind {
+                ReleaseKind::Series => score -= 2,
+                ReleaseKind::Ova | ReleaseKind::Movie => return None,
+            }
+        }
+
+        match (self.season, candidate.season_number()) {
+            (Some(asked), Some(season)) if asked != season => return None,
+            (Some(_), None) => score -= 1,
+            // An unnumbered sequel may be any numbered one but the first
+            (None, Some(1)) if self.sequel => return None,
+            (None, Some(_)) if self.sequel => score -= 1,
+            (None, _) if self.sequel != candidate.sequel => score -= 1,
+            // Another unnumbered sequel, e.g. "Kanketsu Hen" when the final season is asked
+            (None, None) if self.final_season != candidate.final_season => score -= 1,
+            _ => (),
+        }
+
+        match (self.part, candidate.part) {
+            (Some(asked), Some(part)) if asked != part => return None,
+            (Some(_), None) => score -= 1,
+            (None, Some(part)) if part > 1 => score -= 1,
+            _ => (),
+        }
+
+        Some(score)
+    }
+}
+
+// Splits a normalized title into the title of the franchise and its season markers,
+// e.g. "bocchi the rock" and season 2 for "bocchi the rock 2nd season"
+pub fn parse_season(title: &str) -> (String, SeasonInfo) {
+    let mut title = title.to_owned();
+    // The part goes first, so "Season 3 Part 2" isn't read as season 2
+    let part = take_number(&PART_REGEXES, &mut title);
+    let season = take_number(&SEASON_REGEXES, &mut title);
+    let mut info = SeasonInfo {
+        season,
+        part,
+        ..Default::default()
+    };
+
+    if FINAL_SEASON_REGEX.is_match(&title) {
+        info.sequel = true;
+        info.final_season = true;
+        title = FINAL_SEASON_REGEX.replace_all(&title, " ").into_owned();
+    }
+    if SEQUEL_REGEX.is_match(&title) {
+        info.sequel = true;
+        title = SEQUEL_REGEX.replace_all(&title, " ").into_owned();
+    }
+    info.sequel |= info.season.is_some_and(|season| season > 1);
+
+    if MOVIE_REGEX.is_match(&title) {
+        info.kind = ReleaseKind::Movie;
+        title = MOVIE_REGEX.replace_all(&title, " ").into_owned();
+    } else if OVA_REGEX.is_match(&title) {
+        info.kind = ReleaseKind::Ova;
+        title = OVA_REGEX.replace_all(&title, " ").into_owned();
+    }
+
+    (title.split_whitespace().collect::<Vec<_>>().join(" "), info)
+}
+
+// Like parse_season, also reading a bare trailing number as the season when the title without
+// it is known to name the series (e.g. it's another kitsunekko folder)
+pub fn parse_season_among(title: &str, is_known: impl Fn(&str) -> bool) -> (String, SeasonInfo) {
+    let (title, mut info) = parse_season(title);
+    if info.season.is_some() {
+        return (title, info);
+    }
+
+    let bare_season = BARE_SEASON_REGEX.captures(&title).and_then(|capture| {
+        let bare_title = title[..capture.get(0)?.start()].to_owned();
+        let season = capture[1].parse().ok()?;
+        is_known(&bare_title).then_some((bare_title, season))
+    });
+
+    match bare_season {
+        Some((bare_title, season)) => {
+            info.season = Some(season);
+            info.sequel |= season > 1;
+            (bare_title, info)
+        }
+        None => (title, info),
+    }
+}
+
+// Removes the first marker found from the title and returns its number
+fn take_number(regexes: &[Regex], title: &mut String) -> Option<u32> {
+    let (range, number) = regexes.iter().find_map(|regex| {
+        let capture = regex.captures(title)?;
+        Some((capture.get(0)?.range(), capture[1].parse().ok()?))
+    })?;
+
+    title.replace_range(range, " ");
+    Some(number)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::title::normalize_title;
+
+    fn parse(title: &str) -> (String, SeasonInfo) {
+        parse_season(&normalize_title(title))
+    }
+
+    fn season(season: Option<u32>, part: Option<u32>, sequel: bool) -> SeasonInfo {
+        SeasonInfo {
+            season,
+            part,
+            sequel,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_parse_season() {
+        assert_eq!(
+            parse("Kaguya-sama wa Kokurasetai 2nd Season"),
+            (
+                "kaguya sama wa kokurasetai".to_owned(),
+                season(Some(2), None, true)
+            )
+        );
+        assert_eq!(
+            parse("Shingeki no Kyojin Season 3 Part 2"),
+            (
+                "shingeki no kyojin".to_owned(),
+                season(Some(3), Some(2), true)
+            )
+        );
+        assert_eq!(
+            parse("進撃の巨人 第2期"),
+            ("進撃no巨人".to_owned(), season(Some(2), None, true))
+        );
+        assert_eq!(
+            parse("ReLife Kanketsu Hen"),
+            ("relife".to_owned(), season(None, None, true))
+        );
+        assert_eq!(
+            parse("Shingeki no Kyojin The Final Season"),
+            (
+                "shingeki no kyojin".to_owned(),
+                SeasonInfo {
+                    final_season: true,
+                    ..season(None, None, true)
+                }
+            )
+        );
+        assert_eq!(
+            parse("Lupin III: The Final Haircut"),
+            (
+                "lupin iii the final haircut".to_owned(),
+                SeasonInfo::default()
+            )
+        );
+        assert_eq!(
+            parse("ReLIFE"),
+            ("relife".to_owned(), SeasonInfo::default())
+        );
+    }
+
+    #[test]
+    fn test_parse_season_among() {
+        let parse_among =
+            |title| parse_season_among(&normalize_title(title), |bare| bare == "bocchi the rock");
+
+        assert_eq!(
+            parse_among("Bocchi the Rock! 2"),
+            ("bocchi the rock".to_owned(), season(Some(2), None, true))
+        );
+        assert_eq!(
+            parse_among("Kaiju No. 8"),
+            ("kaiju no 8".to_owned(), SeasonInfo::default())
+        );
+        assert_eq!(
+            parse_among("Steins;Gate 0"),
+            ("steins gate 0".to_owned(), SeasonInfo::default())
+        );
+    }
+
+    #[test]
+    fn test_parse_release_kind() {
+        assert_eq!(parse("Bocchi the Rock! Movie").1.kind, ReleaseKind::Movie);
+        assert_eq!(
+            parse("劇場版 ぼっち・ざ・ろっく！").1.kind,
+            ReleaseKind::Movie
+        );
+        assert_eq!(parse("Kaguya-sama OVA").1.kind, ReleaseKind::Ova);
+    }
+
+    #[test]
+    fn test_agreement() {
+        let first = parse("Bocchi the Rock!").1;
+        let second = parse("Bocchi the Rock! S2").1;
+        let movie = parse("Bocchi the Rock! Movie").1;
+
+        assert_eq!(first.agreement(&first), Some(0));
+        assert_eq!(first.agreement(&second), Some(-1));
+        assert_eq!(first.agreement(&movie), Some(-2));
+        assert_eq!(second.agreement(&first), None);
+        assert_eq!(movie.agreement(&first), None);
+    }
+
+    #[test]
+    fn test_agreement_final_season() {
+        let first = parse("Shingeki no Kyojin").1;
+        let third = parse("Shingeki no Kyojin Season 3").1;
+        let final_season = parse("Shingeki no Kyojin The Final Season").1;
+        let kanketsu_hen = parse("Shingeki no Kyojin Kanketsu Hen").1;
+
+        assert_eq!(final_season.agreement(&final_season), Some(0));
+        assert_eq!(final_season.agreement(&third), Some(-1));
+        assert_eq!(final_season.agreement(&kanketsu_hen), Some(-1));
+        assert_eq!(final_season.agreement(&first), None);
+        assert_eq!(first.agreement(&final_season), Some(-1));
+    }
+}
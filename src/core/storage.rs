@@ -0,0 +1,84 @@
+use std::error::Error;
+use std::path::Path;
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use error_stack::{IntoReport, Result, ResultExt};
+
+#[derive(Debug)]
+pub struct JsonFileError;
+
+impl std::fmt::Display for JsonFileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Failed to access json file")
+    }
+}
+
+impl Error for JsonFileError {}
+
+// Loads a json file, falling back to the default value if the file doesn't exist yet
+pub fn load_json<T>(path: &Path) -> Result<T, JsonFileError>
+where
+    T: DeserializeOwned + Default,
+{
+    if !path.exists() {
+        log::debug!(
+            "File doesn't exist, using default: '{}'",
+            path.to_str().unwrap_or("invalid path")
+        );
+        return Ok(T::default());
+    }
+
+    let content = std::fs::read_to_string(path)
+        .into_report()
+        .attach_printable_lazy(|| {
+            format!(
+                "Failed to read file: '{}'",
+                path.to_str().unwrap_or("invalid path")
+            )
+        })
+        .change_context(JsonFileError)?;
+
+    serde_json::from_str(&content)
+        .into_report()
+        .attach_printable_lazy(|| {
+            format!(
+                "Failed to parse json from file: '{}'",
+                path.to_str().unwrap_or("invalid path")
+            )
+        })
+        .change_context(JsonFileError)
+}
+
+pub fn save_json<T>(value: &T, path: &Path) -> Result<(), JsonFileError>
+where
+    T: Serialize,
+{
+    if let Some(parent) = path.parent() {
+        // Create the folder if it doesn't exist
+        std::fs::create_dir_all(parent)
+            .into_report()
+            .attach_printable_lazy(|| {
+                format!(
+                    "Failed to create folders for path: '{}'",
+                    path.to_str().unwrap_or("invalid path")
+                )
+            })
+            .change_context(JsonFileError)?;
+    }
+
+    let content = serde_json::to_string_pretty(value)
+        .into_report()
+        .attach_printable("Failed to serialize value to json")
+        .change_context(JsonFileError)?;
+
+    std::fs::write(path, content)
+        .into_report()
+        .attach_printable_lazy(|| {
+            format!(
+                "Failed to save file to path: '{}'",
+                path.to_str().unwrap_or("invalid path")
+            )
+        })
+        .change_context(JsonFileError)
+}
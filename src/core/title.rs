@@ -0,0 +1,229 @@
+// Folds full width ASCII to ASCII and half width katakana to full width, joining their
+// separate voicing marks (ｶﾞ is ガ)
+pub fn fold_width(text: &str) -> String {
+    let mut folded: Vec<char> = Vec::with_capacity(text.len());
+
+    for c in text.chars() {
+        match c as u32 {
+            0xFF01..=0xFF5E => folded.push(char::from_u32(c as u32 - 0xFEE0).unwrap_or(c)),
+            0x3000 => folded.push(' '),
+            0xFF61..=0xFF9D => folded.push(half_width_katakana(c)),
+            0xFF9E | 0xFF9F => {
+                if let Some(voiced) = folded.last().and_then(|&last| voice(last, c == '\u{FF9F}')) {
+                    folded.pop();
+                    folded.push(voiced);
+                }
+            }
+            _ => folded.push(c),
+        }
+    }
+
+    folded.into_iter().collect()
+}
+
+fn half_width_katakana(c: char) -> char {
+    const FULL_WIDTH: &str = "。「」、・ヲァィゥェォャュョッーアイウエオカキクケコサシスセソタチツテトナニヌネノハヒフヘホマミムメモヤユヨラリルレロワン";
+    FULL_WIDTH.chars().nth(c as usize - 0xFF61).unwrap_or(c)
+}
+
+// The katakana with a voicing mark (ガ for カ, or パ for ハ when semi-voiced)
+fn voice(kana: char, semi_voiced: bool) -> Option<char> {
+    let code = kana as u32;
+    let offset = match (code, semi_voiced) {
+        (0x30A6, false) => return Some('ヴ'),
+        (0x30AB..=0x30C2, false) if !code.is_multiple_of(2) => 1,
+        (0x30C4..=0x30C8, false) if code.is_multiple_of(2) => 1,
+        (0x30CF..=0x30DD, _) if (code - 0x30CF).is_multiple_of(3) => 1 + semi_voiced as u32,
+        _ => return None,
+    };
+    char::from_u32(code + offset)
+}
+
+// Katakana as hiragana, except ヷ-ヺ which have none
+pub fn to_hiragana(c: char) -> char {
+    match c as u32 {
+        0x30A1..=0x30F6 => char::from_u32(c as u32 - 0x60).unwrap_or(c),
+        _ => c,
+    }
+}
+
+// Hepburn romanization of a hiragana, None for the small ones that modify the previous kana
+fn romaji(kana: char) -> Option<&'static str> {
+    const ROWS: [(&str, [&str; 5]); 16] = [
+        ("あいうえお", ["a", "i", "u", "e", "o"]),
+        ("かきくけこ", ["ka", "ki", "ku", "ke", "ko"]),
+        ("さしすせそ", ["sa", "shi", "su", "se", "so"]),
+        ("たちつてと", ["ta", "chi", "tsu", "te", "to"]),
+        ("なにぬねの", ["na", "ni", "nu", "ne", "no"]),
+        ("はひふへほ", ["ha", "hi", "fu", "he", "ho"]),
+        ("まみむめも", ["ma", "mi", "mu", "me", "mo"]),
+        ("や_ゆ_よ", ["ya", "", "yu", "", "yo"]),
+        ("らりるれろ", ["ra", "ri", "ru", "re", "ro"]),
+        ("わ___を", ["wa", "", "", "", "o"]),
+        ("がぎぐげご", ["ga", "gi", "gu", "ge", "go"]),
+        ("ざじずぜぞ", ["za", "ji", "zu", "ze", "zo"]),
+        ("だぢづでど", ["da", "ji", "zu", "de", "do"]),
+        ("ばびぶべぼ", ["ba", "bi", "bu", "be", "bo"]),
+        ("ぱぴぷぺぽ", ["pa", "pi", "pu", "pe", "po"]),
+        ("ん___ゔ", ["n", "", "", "", "vu"]),
+    ];
+
+    ROWS.iter()
+        .find_map(|(kana_row, romaji_row)| {
+            let column = kana_row.chars().position(|row_kana| row_kana == kana)?;
+            Some(romaji_row[column])
+        })
+        .filter(|syllable| !syllable.is_empty())
+}
+
+// Kana written in romaji, e.g. "shingekinokyojin" for "しんげきのきょじん". Small kana
+// change the previous syllable (きょ is kyo, ファ is fa) and っ doubles the next consonant
+pub fn romanize(text: &str) -> String {
+    let mut romanized = String::with_capacity(text.len());
+    let mut double_next = false;
+
+    for c in text.chars().map(to_hiragana) {
+        if let Some(syllable) = romaji(c) {
+            if double_next && syllable.starts_with(|first: char| !"aiueon".contains(first)) {
+                romanized.push_str(&syllable[..1]);
+            }
+            romanized.push_str(syllable);
+            double_next = false;
+            continue;
+        }
+
+        match c {
+            'っ' => double_next = true,
+            'ゃ' | 'ゅ' | 'ょ' => {
+                let vowel = match c {
+                    'ゃ' => "a",
+                    'ゅ' => "u",
+                    _ => "o",
+                };
+                if romanized.ends_with('i') {
+                    romanized.pop();
+                    // しゃ is sha but きゃ is kya
+                    if !(romanized.ends_with("sh")
+                        || romanized.ends_with("ch")
+                        || romanized.ends_with('j'))
+                    {
+                        romanized.push('y');
+                    }
+                } else {
+                    romanized.push('y');
+                }
+                romanized.push_str(vowel);
+            }
+            'ぁ' | 'ぃ' | 'ぅ' | 'ぇ' | 'ぉ' => {
+                let vowel = match c {
+                    'ぁ' => 'a',
+                    'ぃ' => 'i',
+                    'ぅ' => 'u',
+                    'ぇ' => 'e',
+                    _ => 'o',
+                };
+                if romanized.ends_with(['a', 'i', 'u', 'e', 'o']) {
+                    romanized.pop();
+                }
+                romanized.push(vowel);
+            }
+            // The long vowel mark repeats the previous vowel
+            'ー' => {
+                if let Some(vowel) = romanized
+                    .chars()
+                    .last()
+                    .filter(|last| "aiueo".contains(*last))
+                {
+                    romanized.push(vowel);
+                }
+            }
+            c => romanized.push(c),
+        }
+    }
+
+    romanized
+}
+
+// A title reduced to what identifies it, so the ways of writing it compare equal: widths,
+// kana, case, punctuation and long vowels ("Kyōjin" and "きょうじん" are "kyojin"). Long
+// vowels are only folded in romaji, "Good" and "God" are different titles
+pub fn normalize_title(title: &str) -> String {
+    let mut romanized = String::with_capacity(title.len());
+    let mut kana = String::new();
+    for c in fold_width(title).chars() {
+        if is_kana(c) {
+            kana.push(c);
+            continue;
+        }
+        romanized.push_str(&fold_long_vowels(&romanize(&kana)));
+        kana.clear();
+        romanized.push(c);
+    }
+    romanized.push_str(&fold_long_vowels(&romanize(&kana)));
+
+    let title: String = romanized
+        .to_lowercase()
+        .chars()
+        .map(|c| match c {
+            'ā' | 'â' => 'a',
+            'ī' | 'î' => 'i',
+            'ū' | 'û' => 'u',
+            'ē' | 'ê' => 'e',
+            'ō' | 'ô' => 'o',
+            c if c.is_alphanumeric() => c,
+            _ => ' ',
+        })
+        .collect();
+
+    title.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+// Hiragana, katakana and the long vowel mark, which romanize writes in romaji
+fn is_kana(c: char) -> bool {
+    matches!(to_hiragana(c) as u32, 0x3041..=0x3096 | 0x30FC)
+}
+
+fn fold_long_vowels(romaji: &str) -> String {
+    let mut romaji = romaji.to_owned();
+    for (long, short) in [
+        ("ou", "o"),
+        ("oo", "o"),
+        ("uu", "u"),
+        ("aa", "a"),
+        ("ii", "i"),
+    ] {
+        while romaji.contains(long) {
+            romaji = romaji.replace(long, short);
+        }
+    }
+
+    romaji
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fold_width() {
+        assert_eq!(fold_width("Ｒｅ：ＺＥＲＯ　ｶﾞﾝﾀﾞﾑ"), "Re:ZERO ガンダム");
+    }
+
+    #[test]
+    fn test_romanize() {
+        assert_eq!(romanize("しんげきのきょじん"), "shingekinokyojin");
+        assert_eq!(romanize("ぼっち・ざ・ろっく！"), "bocchi・za・rokku！");
+        assert_eq!(romanize("チェンソーマン"), "chensooman");
+        assert_eq!(romanize("ファミリー"), "famirii");
+    }
+
+    #[test]
+    fn test_normalize_title() {
+        assert_eq!(normalize_title("Re:Zero kara"), "re zero kara");
+        assert_eq!(normalize_title("Shingeki no Kyōjin"), "shingeki no kyojin");
+        assert_eq!(normalize_title("しんげきのきょうじん"), "shingekinokyojin");
+        assert_eq!(normalize_title("Good Bloom, you"), "good bloom you");
+        assert_eq!(normalize_title("ぼっち・ざ・ろっく！"), "bocchi za rokku");
+        assert_eq!(normalize_title("ＳＰＹ×ＦＡＭＩＬＹ"), "spy family");
+    }
+}
@@ -0,0 +1,134 @@
+use lazy_static::lazy_static;
+use regex::{Captures, Regex};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+lazy_static! {
+    static ref PLACEHOLDER_REGEX: Regex = match Regex::new(r"\{(\w+)\}") {
+        Ok(regex) => regex,
+        Err(error) => panic!(
+            "Failed to create regex for template placeholders, error: {}",
+            error
+        ),
+    };
+}
+
+#[derive(Deserialize, Serialize, Debug, Copy, Clone, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum HookEventKind {
+    TorrentAdded,
+    TorrentCompleted,
+    SubtitleSaved,
+    // Fired after a search, after each poll of the watch daemon and after a library scan.
+    // The other subcommands don't download anything, so they never fire it
+    RunFinished,
+}
+
+// Serialized as the body of webhooks, its fields are also the variables of templates
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum HookEvent {
+    TorrentAdded {
+        name: String,
+        hash: String,
+    },
+    TorrentCompleted {
+        name: String,
+        hash: String,
+        save_path: String,
+    },
+    SubtitleSaved {
+        name: String,
+        path: String,
+    },
+    RunFinished {
+        name: String,
+    },
+}
+
+impl HookEvent {
+    pub fn kind(&self) -> HookEventKind {
+        match self {
+            HookEvent::TorrentAdded { .. } => HookEventKind::TorrentAdded,
+            HookEvent::TorrentCompleted { .. } => HookEventKind::TorrentCompleted,
+            HookEvent::SubtitleSaved { .. } => HookEventKind::SubtitleSaved,
+            HookEvent::RunFinished { .. } => HookEventKind::RunFinished,
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        serde_json::to_value(self).expect("Hook events always serialize")
+    }
+
+    // Replaces every {field} of the event, e.g. "Downloaded {name}". Unknown ones are kept as is.
+    // A single pass, so a value that contains "{path}" isn't expanded again
+    pub fn render(&self, template: &str) -> String {
+        let Value::Object(fields) = self.to_json() else {
+            return template.to_owned();
+        };
+
+        PLACEHOLDER_REGEX
+            .replace_all(template, |captures: &Captures| {
+                match fields.get(&captures[1]) {
+                    Some(Value::String(value)) => value.clone(),
+                    Some(value) => value.to_string(),
+                    None => captures[0].to_owned(),
+                }
+            })
+            .into_owned()
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            HookEvent::TorrentAdded { name, .. } => format!("Enqueued {}", name),
+            HookEvent::TorrentCompleted { name, .. } => format!("Downloaded {}", name),
+            HookEvent::SubtitleSaved { name, .. } => format!("Saved subtitles {}", name),
+            HookEvent::RunFinished { name } => format!("Finished run for {}", name),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let event = HookEvent::TorrentCompleted {
+            name: "[Ohys-Raws] Bocchi - 01 (BS11).mp4".to_string(),
+            hash: "568807a73ecd33fff3ac19f47805f0940cbdb9ac".to_string(),
+            save_path: "/downloads/raws".to_string(),
+        };
+
+        assert_eq!(
+            event.render("{event}: {save_path}/{name} {unknown}"),
+            "torrent_completed: /downloads/raws/[Ohys-Raws] Bocchi - 01 (BS11).mp4 {unknown}"
+        );
+    }
+
+    #[test]
+    fn test_render_does_not_expand_values() {
+        let event = HookEvent::SubtitleSaved {
+            name: "{path}".to_string(),
+            path: "/subs/{name}.srt".to_string(),
+        };
+
+        assert_eq!(
+            event.render("{name} -> {path}"),
+            "{path} -> /subs/{name}.srt"
+        );
+    }
+
+    #[test]
+    fn test_to_json() {
+        let event = HookEvent::RunFinished {
+            name: "Bocchi".to_string(),
+        };
+
+        assert_eq!(
+            event.to_json(),
+            serde_json::json!({"event": "run_finished", "name": "Bocchi"})
+        );
+        assert_eq!(event.kind(), HookEventKind::RunFinished);
+    }
+}
@@ -0,0 +1,323 @@
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::error::Error;
+use std::path::Path;
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+use error_stack::{IntoReport, Report, Result, ResultExt};
+use reqwest::blocking::Client;
+use reqwest::header::CONTENT_TYPE;
+use serde::{Deserialize, Serialize};
+
+use crate::core::storage::{self, JsonFileError};
+use crate::qbittorrent::api::torrents::TorrentList;
+
+pub mod event;
+
+pub use event::{HookEvent, HookEventKind};
+
+pub const DEFAULT_HOOKS_PATH: &str = "hooks.json";
+
+// Commands get the whole event as json through this variable
+const EVENT_ENV_VAR: &str = "ANIME_JP_EVENT";
+const NOTIFICATION_TITLE: &str = "anime-jp";
+// Hooks run in the middle of downloads, so a hanging one is given up on
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(60);
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+const COMMAND_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Debug)]
+pub struct HookError;
+
+impl std::fmt::Display for HookError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Failed to run hook")
+    }
+}
+
+impl Error for HookError {}
+
+// Templates may use the fields of the event, e.g. {name} or {save_path}
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum HookAction {
+    Command {
+        program: String,
+        #[serde(default)]
+        args: Vec<String>,
+    },
+    Webhook {
+        url: String,
+    },
+    Notification {
+        title: Option<String>,
+        body: Option<String>,
+    },
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Hook {
+    on: Vec<HookEventKind>,
+    #[serde(flatten)]
+    action: HookAction,
+}
+
+impl Hook {
+    fn run(&self, event: &HookEvent) -> Result<(), HookError> {
+        match &self.action {
+            HookAction::Command { program, args } => run_command(event, program, args),
+            HookAction::Webhook { url } => post_webhook(event, url),
+            HookAction::Notification { title, body } => {
+                let title = title.as_deref().map_or_else(
+                    || NOTIFICATION_TITLE.to_owned(),
+                    |title| event.render(title),
+                );
+                let body = body
+                    .as_deref()
+                    .map_or_else(|| event.describe(), |body| event.render(body));
+                notify(&title, &body)
+            }
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Default, Clone)]
+pub struct Hooks(pub Vec<Hook>);
+
+impl Hooks {
+    pub fn load(path: &Path) -> Result<Self, JsonFileError> {
+        storage::load_json(path)
+    }
+
+    // A failing hook is only logged, hooks never interrupt the downloads
+    pub fn fire(&self, event: HookEvent) {
+        for hook in self.0.iter().filter(|hook| hook.on.contains(&event.kind())) {
+            log::debug!("Running hook {:?} for {:?}", hook.action, event);
+
+            if let Err(e) = hook.run(&event) {
+                log::warn!("\n{e:?}");
+            }
+        }
+    }
+
+    // Fires TorrentCompleted once for each torrent seen finished in the updates
+    pub fn on_torrents_update(&self) -> impl Fn(&TorrentList) + '_ {
+        let completed = RefCell::new(HashSet::new());
+
+        move |torrents: &TorrentList| {
+            for torrent in torrents.0.iter().filter(|torrent| torrent.finished()) {
+                if completed.borrow_mut().insert(torrent.hash().clone()) {
+                    self.fire(HookEvent::TorrentCompleted {
+                        name: torrent.name().clone(),
+                        hash: torrent.hash().clone(),
+                        save_path: torrent.save_path().clone(),
+                    });
+                }
+            }
+        }
+    }
+}
+
+fn run_command(event: &HookEvent, program: &str, args: &[String]) -> Result<(), HookError> {
+    let mut command = Command::new(event.render(program));
+    command
+        .args(args.iter().map(|arg| event.render(arg)))
+        .env(EVENT_ENV_VAR, event.to_json().to_string());
+
+    run_with_timeout(&mut command, COMMAND_TIMEOUT)
+        .attach_printable_lazy(|| format!("Command failed: {}", program))
+}
+
+// Kills the command when it runs past the timeout
+fn run_with_timeout(command: &mut Command, timeout: Duration) -> Result<(), HookError> {
+    let mut child = command
+        .spawn()
+        .into_report()
+        .attach_printable("Failed to start command")
+        .change_context(HookError)?;
+
+    let start = Instant::now();
+    let status = loop {
+        let status = child
+            .try_wait()
+            .into_report()
+            .attach_printable("Failed to wait for command")
+            .change_context(HookError)?;
+
+        if let Some(status) = status {
+            break status;
+        }
+
+        if start.elapsed() >= timeout {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(Report::new(HookError).attach_printable(format!(
+                "Command killed after running for {} seconds",
+                timeout.as_secs()
+            )));
+        }
+
+        std::thread::sleep(COMMAND_POLL_INTERVAL);
+    };
+
+    if !status.success() {
+        return Err(
+            Report::new(HookError).attach_printable(format!("Command exited with: {}", status))
+        );
+    }
+
+    Ok(())
+}
+
+fn post_webhook(event: &HookEvent, url: &str) -> Result<(), HookError> {
+    let res = Client::builder()
+        .timeout(WEBHOOK_TIMEOUT)
+        .build()
+        .into_report()
+        .attach_printable("Failed to create webhook client")
+        .change_context(HookError)?
+        .post(event.render(url))
+        .header(CONTENT_TYPE, "application/json")
+        .body(event.to_json().to_string())
+        .send()
+        .into_report()
+        .attach_printable_lazy(|| format!("Failed to post webhook: {}", url))
+        .change_context(HookError)?;
+
+    if !res.status().is_success() {
+        return Err(Report::new(HookError)
+            .attach_printable(format!("Webhook failed with status: {}", res.status())));
+    }
+
+    Ok(())
+}
+
+// Goes through the notification tool of the desktop, so no extra service has to run
+fn notify(title: &str, body: &str) -> Result<(), HookError> {
+    let mut command = match std::env::consts::OS {
+        "macos" => {
+            let escape = |text: &str| text.replace('\\', "\\\\").replace('"', "\\\"");
+            let mut command = Command::new("osascript");
+            command.arg("-e").arg(format!(
+                "display notification \"{}\" with title \"{}\"",
+                escape(body),
+                escape(title)
+            ));
+            command
+        }
+        _ => {
+            let mut command = Command::new("notify-send");
+            command.arg(title).arg(body);
+            command
+        }
+    };
+
+    run_with_timeout(&mut command, COMMAND_TIMEOUT)
+        .attach_printable("Failed to send desktop notification")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::qbittorrent::api::torrents::{Torrent, TorrentState};
+    use crate::test_util::unique_temp_dir;
+
+    #[test]
+    fn test_parse_hooks() {
+        let hooks: Hooks = serde_json::from_str(
+            r#"[
+                {"on": ["torrent_completed"], "type": "command", "program": "jellyfin-scan", "args": ["{save_path}"]},
+                {"on": ["subtitle_saved", "run_finished"], "type": "webhook", "url": "http://localhost:8080/hook"},
+                {"on": ["torrent_added"], "type": "notification"}
+            ]"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            hooks.0,
+            vec![
+                Hook {
+                    on: vec![HookEventKind::TorrentCompleted],
+                    action: HookAction::Command {
+                        program: "jellyfin-scan".to_string(),
+                        args: vec!["{save_path}".to_string()],
+                    },
+                },
+                Hook {
+                    on: vec![HookEventKind::SubtitleSaved, HookEventKind::RunFinished],
+                    action: HookAction::Webhook {
+                        url: "http://localhost:8080/hook".to_string(),
+                    },
+                },
+                Hook {
+                    on: vec![HookEventKind::TorrentAdded],
+                    action: HookAction::Notification {
+                        title: None,
+                        body: None,
+                    },
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_run_with_timeout() {
+        let mut command = Command::new("sh");
+        command.arg("-c").arg("exit 0");
+        assert!(run_with_timeout(&mut command, Duration::from_secs(5)).is_ok());
+
+        let mut command = Command::new("sh");
+        command.arg("-c").arg("exit 1");
+        assert!(run_with_timeout(&mut command, Duration::from_secs(5)).is_err());
+
+        let start = Instant::now();
+        let mut command = Command::new("sleep");
+        command.arg("10");
+        assert!(run_with_timeout(&mut command, Duration::from_millis(200)).is_err());
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_fire_command_once_per_completed_torrent() {
+        let folder = unique_temp_dir("hook-command");
+        let output = folder.join("output");
+
+        let hooks = Hooks(vec![Hook {
+            on: vec![HookEventKind::TorrentCompleted],
+            action: HookAction::Command {
+                program: "sh".to_string(),
+                args: vec![
+                    "-c".to_string(),
+                    format!("echo {{name}} >> {}", output.display()),
+                ],
+            },
+        }]);
+
+        let torrents = TorrentList(vec![
+            Torrent::new(
+                "568807a73ecd33fff3ac19f47805f0940cbdb9ac",
+                "Bocchi",
+                10,
+                TorrentState::Uploading,
+                10,
+                0,
+            ),
+            Torrent::new(
+                "4936206e05d1bb04084f50032d6b3704f271eff7",
+                "Bougyoryoku",
+                10,
+                TorrentState::Downloading,
+                2,
+                60,
+            ),
+        ]);
+
+        let on_update = hooks.on_torrents_update();
+        on_update(&torrents);
+        on_update(&torrents);
+
+        assert_eq!(std::fs::read_to_string(&output).unwrap(), "Bocchi\n");
+        std::fs::remove_dir_all(&folder).unwrap();
+    }
+}
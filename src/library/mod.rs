@@ -0,0 +1,2 @@
+pub mod muxing;
+pub mod scan;
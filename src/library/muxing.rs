@@ -0,0 +1,186 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use error_stack::Result;
+
+use crate::core::episode::parse_episode_number;
+use crate::core::extensions::SUBTITLE_EXTENSIONS;
+use crate::mux::{self, MuxOptions};
+use crate::qbittorrent::api::torrents::Torrent;
+use crate::subs::language::SubLanguage;
+
+use super::scan::{self, ScanError, VideoFile, VIDEO_EXTENSIONS};
+
+// The first subtitle of each episode below the folder, in path order. Subtitles tagged with
+// another language are left out, they are muxed as the Japanese track
+pub fn find_japanese_subtitles(folder: &Path) -> Result<BTreeMap<u32, PathBuf>, ScanError> {
+    let mut paths = Vec::new();
+    // Nothing was downloaded for the series yet
+    if folder.is_dir() {
+        scan::for_each_file(folder, &mut |path| {
+            if scan::has_extension(path, &SUBTITLE_EXTENSIONS) && is_japanese(path) {
+                paths.push(path.to_path_buf());
+            }
+        })?;
+    }
+    paths.sort();
+
+    let mut subtitles = BTreeMap::new();
+    for path in paths {
+        let episode = path
+            .file_name()
+            .and_then(|file_name| file_name.to_str())
+            .and_then(parse_episode_number);
+
+        if let Some(episode) = episode {
+            subtitles.entry(episode).or_insert(path);
+        }
+    }
+
+    Ok(subtitles)
+}
+
+// "ep01.ja.srt" and "ep01.srt" are Japanese, "ep01.en.srt" isn't
+fn is_japanese(subtitle: &Path) -> bool {
+    SubLanguage::of_subtitle(subtitle) == SubLanguage::Japanese
+}
+
+// The videos of a finished torrent, whose files are in "<save_path>/<name>", a folder for
+// batches. A client on another machine may save them where they can't be read from here
+pub fn torrent_videos(torrent: &Torrent) -> Result<Vec<VideoFile>, ScanError> {
+    let content = Path::new(torrent.save_path()).join(torrent.name());
+
+    if content.is_dir() {
+        return scan::find_videos(&content);
+    }
+
+    if !content.is_file() {
+        log::warn!("Downloaded raw not found: {}", content.display());
+        return Ok(Vec::new());
+    }
+
+    Ok(VideoFile::parse(&content)
+        .filter(|_| scan::has_extension(&content, &VIDEO_EXTENSIONS))
+        .into_iter()
+        .collect())
+}
+
+// Muxes into each MKV the subtitle of its episode, unless it already has a Japanese track.
+// Returns how many were muxed, and whether every MKV has a Japanese track now
+pub fn mux_videos(
+    videos: &[VideoFile],
+    subtitles: &BTreeMap<u32, PathBuf>,
+    options: &MuxOptions,
+) -> (usize, bool) {
+    let mut muxed = 0;
+    let mut complete = true;
+
+    for video in videos
+        .iter()
+        .filter(|video| scan::has_extension(video.path(), &["mkv"]))
+    {
+        if mux::has_japanese_track(video.path()) {
+            continue;
+        }
+
+        let Some(subtitle) = subtitles.get(video.episode()) else {
+            log::info!("No subtitles to mux yet into: {}", video.path().display());
+            complete = false;
+            continue;
+        };
+
+        match mux::mux_subtitle(video.path(), subtitle, options) {
+            Ok(_) => {
+                log::info!("Muxed subtitles into: {}", video.path().display());
+                muxed += 1;
+            }
+            Err(e) => {
+                log::error!("\n{e:?}");
+                complete = false;
+            }
+        }
+    }
+
+    (muxed, complete)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::qbittorrent::api::torrents::TorrentState;
+    use crate::test_util::unique_temp_dir;
+    use std::fs;
+
+    #[test]
+    fn test_find_japanese_subtitles() {
+        let folder = unique_temp_dir("library-muxing-subs");
+        fs::create_dir_all(folder.join("Season 2")).unwrap();
+        for file_name in [
+            "ぼっち・ざ・ろっく！ 第1話.ja.ass",
+            "ぼっち・ざ・ろっく！ 第1話.srt",
+            "Bocchi - 02.en.srt",
+            "Season 2/Bocchi - 03.srt",
+            "notes.txt",
+        ] {
+            fs::write(folder.join(file_name), "").unwrap();
+        }
+
+        let subtitles = find_japanese_subtitles(&folder).unwrap();
+        assert_eq!(
+            subtitles,
+            BTreeMap::from([
+                (1, folder.join("ぼっち・ざ・ろっく！ 第1話.ja.ass")),
+                (3, folder.join("Season 2/Bocchi - 03.srt")),
+            ])
+        );
+        assert!(find_japanese_subtitles(&folder.join("missing"))
+            .unwrap()
+            .is_empty());
+
+        fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[test]
+    fn test_torrent_videos() {
+        let folder = unique_temp_dir("library-muxing-videos");
+        let batch = folder.join("[Ohys-Raws] Bocchi the Rock! (BD)");
+        fs::create_dir_all(&batch).unwrap();
+        fs::write(batch.join("Bocchi the Rock! - 01.mkv"), "").unwrap();
+        fs::write(batch.join("Bocchi the Rock! - 02.mkv"), "").unwrap();
+        fs::write(folder.join("Bocchi the Rock! - 03.mkv"), "").unwrap();
+        let save_path = folder.display().to_string();
+
+        let torrent = |name: &str| {
+            Torrent::new(
+                "568807a73ecd33fff3ac19f47805f0940cbdb9ac",
+                name,
+                10,
+                TorrentState::Uploading,
+                10,
+                0,
+            )
+            .with_save_path(&save_path)
+        };
+
+        let episodes = |videos: Vec<VideoFile>| {
+            videos
+                .iter()
+                .map(|video| *video.episode())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            episodes(torrent_videos(&torrent("[Ohys-Raws] Bocchi the Rock! (BD)")).unwrap()),
+            vec![1, 2]
+        );
+        assert_eq!(
+            episodes(torrent_videos(&torrent("Bocchi the Rock! - 03.mkv")).unwrap()),
+            vec![3]
+        );
+        assert!(torrent_videos(&torrent("Bocchi the Rock! - 04.mkv"))
+            .unwrap()
+            .is_empty());
+
+        fs::remove_dir_all(&folder).unwrap();
+    }
+}
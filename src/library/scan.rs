@@ -0,0 +1,495 @@
+use std::collections::{BTreeMap, HashSet};
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use derive_getters::Getters;
+use error_stack::{IntoReport, Report, Result, ResultExt};
+
+use crate::core::aliases::TitleAliases;
+use crate::core::download::downloader::{Destination, FileDownloader};
+use crate::core::episode::{parse_episode_number, parse_season_number, parse_series_name};
+use crate::core::extensions::{ARCHIVE_EXTENSIONS, SUBTITLE_EXTENSIONS};
+use crate::core::indexer::Indexer;
+use crate::core::season::parse_season;
+use crate::core::title::normalize_title;
+use crate::hooks::{HookEvent, Hooks};
+use crate::mux::{self, MuxOptions};
+use crate::subs;
+use crate::subs::download::AnimeSubsDownloader;
+use crate::subs::language::SubLanguage;
+use crate::sync::{self, SyncOptions};
+
+pub const VIDEO_EXTENSIONS: [&str; 6] = ["mkv", "mp4", "avi", "webm", "m4v", "ts"];
+
+#[derive(Debug)]
+pub struct ScanError;
+
+impl std::fmt::Display for ScanError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Problem while scanning the library")
+    }
+}
+
+impl Error for ScanError {}
+
+#[derive(Debug, Clone, PartialEq, Eq, Getters)]
+pub struct VideoFile {
+    path: PathBuf,
+    series: String,
+    episode: u32,
+    season: Option<u32>,
+}
+
+impl VideoFile {
+    // Videos named only by their episode (e.g. "Bocchi/Season 1/03.mkv") take the series
+    // from the closest folder that isn't a season, and the season from the closest that is
+    pub fn parse(path: &Path) -> Option<Self> {
+        let file_name = path.file_name()?.to_str()?;
+        let episode = parse_episode_number(file_name)
+            .or_else(|| path.file_stem()?.to_str()?.trim().parse().ok())?;
+
+        let series = parse_series_name(file_name).or_else(|| {
+            path.ancestors()
+                .skip(1)
+                .filter_map(|folder| folder.file_name()?.to_str())
+                .find(|folder| !folder.to_lowercase().starts_with("season"))
+                .map(str::to_owned)
+        })?;
+
+        let season = parse_season_number(file_name).or_else(|| {
+            path.ancestors()
+                .skip(1)
+                .filter_map(|folder| folder.file_name()?.to_str())
+                .find(|folder| folder.to_lowercase().starts_with("season"))
+                .and_then(|folder| parse_season(&normalize_title(folder)).1.season)
+        });
+
+        Some(VideoFile {
+            path: path.to_path_buf(),
+            series,
+            episode,
+            season,
+        })
+    }
+
+    // Players pick up "<video>.srt" as well as "<video>.ja.srt", untagged ones are Japanese
+    pub fn has_sidecar(&self, language: SubLanguage) -> bool {
+        let (Some(folder), Some(stem)) = (
+            self.path.parent(),
+            self.path.file_stem().and_then(|stem| stem.to_str()),
+        ) else {
+            return false;
+        };
+
+        let Ok(entries) = fs::read_dir(folder) else {
+            return false;
+        };
+
+        entries.filter_map(|entry| entry.ok()).any(|entry| {
+            let path = entry.path();
+            let is_subtitle = has_extension(&path, &SUBTITLE_EXTENSIONS);
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+
+            is_subtitle
+                && name.starts_with(stem)
+                && name[stem.len()..].starts_with('.')
+                && SubLanguage::of_subtitle(&path) == language
+        })
+    }
+}
+
+pub fn has_extension(path: &Path, extensions: &[&str]) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| extensions.contains(&extension.to_lowercase().as_str()))
+}
+
+// Every video below the folder whose episode number could be parsed
+pub fn find_videos(folder: &Path) -> Result<Vec<VideoFile>, ScanError> {
+    let mut videos = Vec::new();
+    for_each_file(folder, &mut |path| {
+        if has_extension(path, &VIDEO_EXTENSIONS) {
+            match VideoFile::parse(path) {
+                Some(video) => videos.push(video),
+                None => log::debug!("No episode number in: {}", path.display()),
+            }
+        }
+    })?;
+
+    videos.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(videos)
+}
+
+// Calls `on_file` with every file below the folder
+pub fn for_each_file(folder: &Path, on_file: &mut dyn FnMut(&Path)) -> Result<(), ScanError> {
+    walk_folder(folder, &mut HashSet::new(), on_file)
+}
+
+// Folders are only read once, a symlink to one of their parents would otherwise loop forever
+fn walk_folder(
+    folder: &Path,
+    visited: &mut HashSet<PathBuf>,
+    on_file: &mut dyn FnMut(&Path),
+) -> Result<(), ScanError> {
+    let canonical_folder = fs::canonicalize(folder)
+        .into_report()
+        .attach_printable_lazy(|| format!("Failed to resolve folder: {}", folder.display()))
+        .change_context(ScanError)?;
+    if !visited.insert(canonical_folder) {
+        log::debug!("Folder already scanned: {}", folder.display());
+        return Ok(());
+    }
+
+    let entries = fs::read_dir(folder)
+        .into_report()
+        .attach_printable_lazy(|| format!("Failed to read folder: {}", folder.display()))
+        .change_context(ScanError)?;
+
+    for entry in entries {
+        let path = entry
+            .into_report()
+            .attach_printable_lazy(|| format!("Failed to read folder: {}", folder.display()))
+            .change_context(ScanError)?
+            .path();
+
+        match path.is_dir() {
+            true => walk_folder(&path, visited, on_file)?,
+            false => on_file(&path),
+        }
+    }
+
+    Ok(())
+}
+
+// Series written slightly differently (e.g. "Bocchi the Rock" and "Bocchi the Rock!") are
+// grouped together, under the name of their first video. Seasons are kept apart, kitsunekko
+// has a folder for each
+pub fn group_by_series(videos: Vec<VideoFile>) -> BTreeMap<(String, Option<u32>), Vec<VideoFile>> {
+    let mut groups = BTreeMap::new();
+    for video in videos {
+        groups
+            .entry((normalize_title(&video.series), video.season))
+            .or_insert_with(|| ((video.series.clone(), video.season), Vec::new()))
+            .1
+            .push(video);
+    }
+
+    groups.into_values().collect()
+}
+
+// The first subtitle file of the episode, in the order kitsunekko lists them. Files of
+// subfolders are named by their path, only their file name tells the episode
+pub fn match_sub_file(sub_files: &[Indexer], episode: u32) -> Option<&Indexer> {
+    sub_files.iter().find(|sub_file| {
+        let path = Path::new(sub_file.name());
+        let file_name = path
+            .file_name()
+            .and_then(|file_name| file_name.to_str())
+            .unwrap_or(sub_file.name());
+
+        !has_extension(path, &ARCHIVE_EXTENSIONS)
+            && parse_episode_number(file_name) == Some(episode)
+    })
+}
+
+pub struct LibraryScanner {
+    aliases: TitleAliases,
+    hooks: Hooks,
+    sync_options: Option<SyncOptions>,
+    mux_options: Option<MuxOptions>,
+    min_score: i64,
+    languages: Vec<SubLanguage>,
+}
+
+impl LibraryScanner {
+    // With sync options, downloaded subtitles are realigned to the audio of their video.
+    // With mux options, they are then added to the video as a track
+    pub fn new(
+        aliases: TitleAliases,
+        hooks: Hooks,
+        sync_options: Option<SyncOptions>,
+        mux_options: Option<MuxOptions>,
+    ) -> Self {
+        LibraryScanner {
+            aliases,
+            hooks,
+            sync_options,
+            mux_options,
+            min_score: subs::search::DEFAULT_MIN_SCORE,
+            languages: vec![SubLanguage::Japanese],
+        }
+    }
+
+    // Series whose best kitsunekko folder scores below this are skipped
+    pub fn with_min_score(mut self, min_score: i64) -> Self {
+        self.min_score = min_score;
+        self
+    }
+
+    // The kitsunekko languages to download, each saved as "<video>.<code>.<extension>"
+    pub fn with_languages(mut self, languages: Vec<SubLanguage>) -> Self {
+        self.languages = languages;
+        self
+    }
+
+    // Downloads subtitles next to every video lacking them, returns how many were saved.
+    // A series missing from kitsunekko is only logged, the others are still scanned
+    pub fn scan(&self, folder: &Path, dry_run: bool) -> Result<usize, ScanError> {
+        let videos = find_videos(folder)?;
+
+        let mut count = 0;
+        for language in &self.languages {
+            count += self.scan_language(&videos, *language, dry_run);
+        }
+
+        if !dry_run {
+            self.hooks.fire(HookEvent::RunFinished {
+                name: folder.display().to_string(),
+            });
+        }
+
+        Ok(count)
+    }
+
+    fn scan_language(&self, videos: &[VideoFile], language: SubLanguage, dry_run: bool) -> usize {
+        // Muxed subtitles may have had their sidecar deleted
+        let missing = group_by_series(
+            videos
+                .iter()
+                .filter(|video| {
+                    !(video.has_sidecar(language)
+                        || (language == SubLanguage::Japanese
+                            && self.mux_options.is_some()
+                            && mux::has_japanese_track(&video.path)))
+                })
+                .cloned()
+                .collect(),
+        );
+
+        log::info!(
+            "Found {} series with episodes missing {} subtitles",
+            missing.len(),
+            language.code()
+        );
+
+        let mut count = 0;
+        for ((series, season), videos) in &missing {
+            match season {
+                Some(season) => log::info!(
+                    "{} season {}: {} episodes missing subtitles",
+                    series,
+                    season,
+                    videos.len()
+                ),
+                None => log::info!("{}: {} episodes missing subtitles", series, videos.len()),
+            }
+
+            if dry_run {
+                for video in videos {
+                    log::info!("Missing subtitles: {}", video.path.display());
+                }
+                continue;
+            }
+
+            match self.download_series_subs(series, *season, language, videos) {
+                Ok(saved) => count += saved,
+                Err(e) => log::error!("\n{e:?}"),
+            }
+        }
+
+        count
+    }
+
+    fn download_series_subs(
+        &self,
+        series: &str,
+        season: Option<u32>,
+        language: SubLanguage,
+        videos: &[VideoFile],
+    ) -> Result<usize, ScanError> {
+        let anime_indexer =
+            subs::search::fetch_best_indexers_for(series, season, language, &self.aliases)
+                .map_err(|e| {
+                    Report::new(ScanError)
+                        .attach_printable(format!("Failed to fetch indexers for: {}", series))
+                        .attach_printable(e.to_string())
+                })?
+                .into_iter()
+                .next()
+                .ok_or_else(|| {
+                    Report::new(ScanError)
+                        .attach_printable(format!("Subs not found for: {}", series))
+                })?;
+
+        if anime_indexer.score() < self.min_score {
+            return Err(Report::new(ScanError)
+                .attach_printable(format!("No confident subtitles match for: {}", series))
+                .attach_printable(format!("Best match: {}", anime_indexer)));
+        }
+        let anime_indexer = anime_indexer.into_indexer();
+
+        log::debug!("Matched {} with {}", series, anime_indexer.name());
+
+        let sub_files = subs::search::fetch_sub_files(&anime_indexer).map_err(|e| {
+            Report::new(ScanError)
+                .attach_printable(format!("Failed to fetch sub files for: {}", series))
+                .attach_printable(e.to_string())
+        })?;
+
+        let subs_downloader = AnimeSubsDownloader::new().with_language(language);
+        let mut count = 0;
+        for video in videos {
+            let Some(sub_file) = match_sub_file(&sub_files, video.episode) else {
+                log::warn!("No subtitles for episode {} of {}", video.episode, series);
+                continue;
+            };
+
+            // Same name as the video, so players load the subtitles with it
+            let (Some(folder), Some(stem)) = (
+                video.path.parent().and_then(|folder| folder.to_str()),
+                video.path.file_stem().and_then(|stem| stem.to_str()),
+            ) else {
+                continue;
+            };
+            let dest =
+                Destination::GivenFolderGivenFileBasename(folder.to_owned(), stem.to_owned());
+
+            match subs_downloader.download_indexer_to_file(sub_file, &dest) {
+                Ok(_) => {
+                    let path = subs_downloader.indexer_file_path(sub_file, &dest);
+                    log::info!("Saved subtitles: {}", path.display());
+
+                    // Unsynced subtitles are still better than none, so a failure is only logged
+                    if let Some(sync_options) = &self.sync_options {
+                        if let Err(e) =
+                            sync::sync_subtitle(&video.path, &path, Some(&path), sync_options)
+                        {
+                            log::error!("\n{e:?}");
+                        }
+                    }
+
+                    // Subtitles are muxed as the Japanese track
+                    let mux_options = self
+                        .mux_options
+                        .as_ref()
+                        .filter(|_| language == SubLanguage::Japanese);
+                    if let Some(mux_options) = mux_options {
+                        match mux::mux_subtitle(&video.path, &path, mux_options) {
+                            Ok(_) => log::info!("Muxed subtitles into: {}", video.path.display()),
+                            Err(e) => log::error!("\n{e:?}"),
+                        }
+                    }
+
+                    self.hooks.fire(HookEvent::SubtitleSaved {
+                        name: sub_file.name().to_owned(),
+                        path: path.display().to_string(),
+                    });
+                    count += 1;
+                }
+                Err(e) => log::error!("\n{e:?}"),
+            }
+        }
+
+        Ok(count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::unique_temp_dir;
+
+    #[test]
+    fn test_find_videos_missing_subtitles() {
+        let folder = unique_temp_dir("library-scan");
+        let season = folder.join("Bocchi the Rock").join("Season 1");
+        fs::create_dir_all(&season).unwrap();
+        for file_name in [
+            "[SubsPlease] Bocchi the Rock! - 01 [1080p].mkv",
+            "[SubsPlease] Bocchi the Rock! - 01 [1080p].ja.srt",
+            "[SubsPlease] Bocchi the Rock! - 02 [1080p].mkv",
+            "03.mkv",
+            "notes.txt",
+        ] {
+            fs::write(season.join(file_name), "").unwrap();
+        }
+
+        let videos = find_videos(&folder).unwrap();
+        let missing: Vec<_> = videos
+            .iter()
+            .filter(|video| !video.has_sidecar(SubLanguage::Japanese))
+            .collect();
+
+        assert_eq!(videos.len(), 3);
+        assert_eq!(
+            missing
+                .iter()
+                .map(|video| (video.series().as_str(), *video.episode()))
+                .collect::<Vec<_>>(),
+            vec![("Bocchi the Rock", 3), ("Bocchi the Rock!", 2)]
+        );
+
+        let groups = group_by_series(missing.into_iter().cloned().collect());
+        assert_eq!(
+            groups
+                .iter()
+                .map(|((series, season), videos)| (series.as_str(), *season, videos.len()))
+                .collect::<Vec<_>>(),
+            vec![("Bocchi the Rock", Some(1), 2)]
+        );
+
+        fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_find_videos_symlink_loop() {
+        let folder = unique_temp_dir("library-scan-loop");
+        let season = folder.join("Bocchi the Rock");
+        fs::create_dir_all(&season).unwrap();
+        fs::write(season.join("Bocchi the Rock - 01.mkv"), "").unwrap();
+        std::os::unix::fs::symlink(&folder, season.join("loop")).unwrap();
+
+        let videos = find_videos(&folder).unwrap();
+        assert_eq!(videos.len(), 1);
+
+        fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[test]
+    fn test_parse_video_season() {
+        let season = |path: &str| *VideoFile::parse(Path::new(path)).unwrap().season();
+
+        assert_eq!(season("Bocchi/Season 2/Bocchi the Rock! - 03.mkv"), Some(2));
+        assert_eq!(season("Bocchi/Bocchi.the.Rock.S02E03.mkv"), Some(2));
+        assert_eq!(season("Bocchi/Bocchi the Rock! - 03.mkv"), None);
+    }
+
+    #[test]
+    fn test_match_sub_file() {
+        let sub_files = vec![
+            Indexer::new(
+                "ぼっち・ざ・ろっく！ 第1-12話.zip",
+                "https://kitsunekko.net/1",
+            ),
+            Indexer::new("ぼっち・ざ・ろっく！ 第2話.ass", "https://kitsunekko.net/2"),
+            Indexer::new(
+                "ぼっち・ざ・ろっく！ 第12話.ass",
+                "https://kitsunekko.net/3",
+            ),
+            Indexer::new("Season 2/Bocchi - 04.ass", "https://kitsunekko.net/4"),
+        ];
+
+        assert_eq!(
+            match_sub_file(&sub_files, 2).map(|sub_file| sub_file.uri()),
+            Some("https://kitsunekko.net/2")
+        );
+        assert_eq!(match_sub_file(&sub_files, 1), None);
+        assert_eq!(
+            match_sub_file(&sub_files, 4).map(|sub_file| sub_file.uri()),
+            Some("https://kitsunekko.net/4")
+        );
+    }
+}
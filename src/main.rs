@@ -1,22 +1,56 @@
-use clap::{arg, command};
-use clap::{Parser, ValueEnum};
-use error_stack::{Result, ResultExt, IntoReport, Report};
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use clap::{Parser, Subcommand, ValueEnum};
+use error_stack::{Report, Result, ResultExt};
 use fern::colors::{Color, ColoredLevelConfig};
 use indicatif::ProgressBar;
 use log::LevelFilter;
 use raws::search::AnimeRawData;
+use tokio::runtime::Runtime;
 
+use crate::core::aliases::{TitleAliases, DEFAULT_ALIASES_PATH};
 use crate::core::download::downloader::{Destination, FileDownloader};
+use crate::core::episode::parse_episode_range;
 use crate::core::indexer::Indexer;
-use crate::qbittorrent::api::torrents::TorrentList;
+use crate::hooks::{HookEvent, Hooks, DEFAULT_HOOKS_PATH};
+use crate::library::scan::LibraryScanner;
+use crate::metadata::anilist::{self, AniListClient};
+use crate::metadata::AnimeMetadata;
+use crate::mux::MuxOptions;
+use crate::qbittorrent::api::torrents::{
+    FilePriority, QueuePriority, TorrentAddOptions, TorrentInfoFilter, TorrentList,
+};
+use crate::qbittorrent::client::QBitTorrentClient;
 use crate::raws::download::AnimeRawDownloader;
+use crate::raws::progress::TorrentProgress;
+use crate::study::export::format_timestamp;
+use crate::study::search::SubtitleIndex;
+use crate::study::ExportOptions;
 use crate::subs::download::AnimeSubsDownloader;
+use crate::subs::language::SubLanguage;
+use crate::sync::SyncOptions;
+use crate::torrent::{TorrentClientBackend, TorrentClientConfig, WaitOptions};
+use crate::watch::daemon::WatchDaemon;
+use crate::watch::state::{WatchState, DEFAULT_STATE_PATH};
+use crate::watch::watchlist::{Watchlist, WatchlistEntry, DEFAULT_WATCHLIST_PATH};
 
 mod core;
+mod hooks;
+mod library;
+mod metadata;
+mod mux;
 mod prelude;
 mod qbittorrent;
 mod raws;
+mod study;
 mod subs;
+mod sync;
+#[cfg(test)]
+mod test_util;
+mod torrent;
+mod watch;
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
 enum SearchType {
@@ -26,10 +60,15 @@ enum SearchType {
 }
 
 #[derive(Parser, Debug, Clone)]
-#[command(author, version, about, long_about = None)]
+#[command(author, version, about, long_about = None, subcommand_negates_reqs = true)]
 struct Args {
-    #[arg(short, long, help = "The name of the anime you want to search for")]
-    anime_name: String,
+    #[arg(
+        short,
+        long,
+        required = true,
+        help = "The name of the anime you want to search for"
+    )]
+    anime_name: Option<String>,
 
     #[arg(
         short,
@@ -48,11 +87,458 @@ struct Args {
     )]
     search_type: SearchType,
 
-    #[arg(short, long, default_value = "false", help = "Show more logs")]
+    #[arg(long, help = "The qBittorrent category to add raws to")]
+    category: Option<String>,
+
+    #[arg(
+        long,
+        default_value = "false",
+        help = "Add raws paused instead of starting them"
+    )]
+    paused: bool,
+
+    #[arg(
+        long,
+        default_value = "false",
+        help = "Download the pieces of each raw in order"
+    )]
+    sequential_download: bool,
+
+    #[arg(
+        short,
+        long,
+        value_delimiter = ',',
+        value_parser = parse_episode_range,
+        help = "Only download these episodes from batch raws, e.g. 1,3,5-7"
+    )]
+    episodes: Vec<RangeInclusive<u32>>,
+
+    #[arg(
+        long,
+        help = "Only match kitsunekko folders of this season, instead of the one in the anime name"
+    )]
+    season: Option<u32>,
+
+    #[arg(
+        long,
+        num_args = 0..=1,
+        default_missing_value = "5",
+        value_name = "N",
+        help = "Print the N best kitsunekko folders with how their names scored"
+    )]
+    explain: Option<usize>,
+
+    #[arg(
+        long,
+        default_value_t = subs::search::DEFAULT_MIN_SCORE,
+        allow_negative_numbers = true,
+        help = "The score below which the best kitsunekko folder isn't picked"
+    )]
+    min_score: i64,
+
+    #[arg(
+        long,
+        value_enum,
+        value_delimiter = ',',
+        default_value = "ja",
+        help = "The kitsunekko languages to download subtitles in, e.g. ja,en"
+    )]
+    sub_lang: Vec<SubLanguage>,
+
+    #[command(flatten)]
+    wait_options: WaitOptions,
+
+    #[arg(
+        long,
+        default_value = "false",
+        help = "Add the subtitles to the raws as a Japanese track once they finished downloading"
+    )]
+    mux: bool,
+
+    #[command(flatten)]
+    mux_options: MuxOptions,
+
+    #[arg(
+        long,
+        default_value = "false",
+        help = "Look the anime up on AniList, to search with its canonical titles"
+    )]
+    metadata: bool,
+
+    #[arg(
+        short,
+        long,
+        global = true,
+        default_value = "false",
+        help = "Show more logs"
+    )]
     verbose: bool,
 
-    #[arg(short, long, default_value = "false", help = "Show even more logs")]
+    #[arg(
+        short,
+        long,
+        global = true,
+        default_value = "false",
+        help = "Show even more logs"
+    )]
     trace: bool,
+
+    #[arg(
+        long,
+        global = true,
+        help = "The torrent client's download folder, relative raw folders are resolved against it"
+    )]
+    download_root: Option<PathBuf>,
+
+    #[arg(
+        long,
+        global = true,
+        default_value = DEFAULT_HOOKS_PATH,
+        help = "Commands, webhooks and notifications to run on download events"
+    )]
+    hooks: PathBuf,
+
+    #[arg(
+        long,
+        global = true,
+        default_value = DEFAULT_ALIASES_PATH,
+        help = "Other titles of series (English, romaji or Japanese) used when searching subtitles"
+    )]
+    aliases: PathBuf,
+
+    #[command(flatten)]
+    torrent_client: TorrentClientConfig,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+impl Args {
+    fn anime_name(&self) -> &str {
+        self.anime_name
+            .as_deref()
+            .expect("anime_name is required when no subcommand is given")
+    }
+}
+
+#[derive(Subcommand, Debug, Clone)]
+enum Command {
+    #[command(about = "Manage the watchlist and poll it for newly aired episodes")]
+    Watch {
+        #[arg(long, default_value = DEFAULT_WATCHLIST_PATH, help = "Where the watchlist is stored")]
+        watchlist: PathBuf,
+
+        #[arg(long, default_value = DEFAULT_STATE_PATH, help = "Where already fetched episodes are recorded")]
+        state: PathBuf,
+
+        #[command(subcommand)]
+        action: WatchAction,
+    },
+
+    #[command(about = "Download subtitles for the videos of a media folder that lack them")]
+    Scan {
+        #[arg(help = "The media folder, scanned recursively")]
+        folder: PathBuf,
+
+        #[arg(
+            long,
+            default_value = "false",
+            help = "Only list the videos missing subtitles"
+        )]
+        dry_run: bool,
+
+        #[arg(
+            long,
+            default_value = "false",
+            help = "Realign the downloaded subtitles to the audio of their video, needs ffmpeg"
+        )]
+        sync: bool,
+
+        #[command(flatten)]
+        sync_options: SyncOptions,
+
+        #[arg(
+            long,
+            default_value = "false",
+            help = "Add the downloaded subtitles to their MKV as a Japanese track"
+        )]
+        mux: bool,
+
+        #[command(flatten)]
+        mux_options: MuxOptions,
+
+        #[arg(
+            long,
+            default_value_t = subs::search::DEFAULT_MIN_SCORE,
+            allow_negative_numbers = true,
+            help = "The score below which the best kitsunekko folder isn't picked"
+        )]
+        min_score: i64,
+
+        #[arg(
+            long,
+            value_enum,
+            value_delimiter = ',',
+            default_value = "ja",
+            help = "The kitsunekko languages to download subtitles in, e.g. ja,en"
+        )]
+        sub_lang: Vec<SubLanguage>,
+    },
+
+    #[command(about = "Add subtitles to an MKV as its default Japanese track")]
+    Mux {
+        #[arg(help = "The MKV video, replaced by the muxed one")]
+        video: PathBuf,
+
+        #[arg(help = "The subtitles to add")]
+        subtitle: PathBuf,
+
+        #[command(flatten)]
+        options: MuxOptions,
+    },
+
+    #[command(about = "Realign subtitles to the speech in a video, needs ffmpeg")]
+    Sync {
+        #[arg(help = "The video the subtitles belong to")]
+        video: PathBuf,
+
+        #[arg(help = "The subtitles to realign (srt, ass or vtt)")]
+        subtitle: PathBuf,
+
+        #[arg(
+            short,
+            long,
+            help = "Where to write the realigned subtitles, next to the video by default"
+        )]
+        output: Option<PathBuf>,
+
+        #[command(flatten)]
+        options: SyncOptions,
+    },
+
+    #[command(about = "Export the sentences of subtitles with their timestamps for study")]
+    Export {
+        #[arg(
+            required = true,
+            help = "Subtitle files, or folders searched recursively for them"
+        )]
+        paths: Vec<PathBuf>,
+
+        #[command(flatten)]
+        options: ExportOptions,
+    },
+
+    #[command(about = "Search the downloaded subtitles for a phrase, by its text or reading")]
+    GrepSubs {
+        #[arg(help = "The phrase, kana and kanji, width and case are matched loosely")]
+        query: String,
+
+        #[arg(
+            required = false,
+            default_value = subs::download::DEFAULT_FOLDER,
+            help = "Subtitle files, or folders searched recursively for them"
+        )]
+        paths: Vec<PathBuf>,
+    },
+
+    #[command(about = "Show the AniList entry of an anime: its titles, season and episodes")]
+    Info {
+        #[arg(help = "The name of the anime, in English, romaji or Japanese")]
+        anime_name: String,
+    },
+
+    #[command(about = "Manage the torrents in qBittorrent")]
+    Queue {
+        #[command(subcommand)]
+        action: QueueAction,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+enum QueueAction {
+    #[command(about = "List the torrents, optionally filtered by category or tag")]
+    List {
+        #[arg(long, help = "Only list torrents in this category")]
+        category: Option<String>,
+
+        #[arg(long, help = "Only list torrents with this tag (e.g. the series name)")]
+        tag: Option<String>,
+    },
+
+    #[command(about = "Pause torrents")]
+    Pause {
+        #[arg(required = true, help = "Info-hashes of the torrents, or 'all'")]
+        hashes: Vec<String>,
+    },
+
+    #[command(about = "Resume torrents")]
+    Resume {
+        #[arg(required = true, help = "Info-hashes of the torrents, or 'all'")]
+        hashes: Vec<String>,
+    },
+
+    #[command(about = "Remove torrents from qBittorrent")]
+    Delete {
+        #[arg(required = true, help = "Info-hashes of the torrents, or 'all'")]
+        hashes: Vec<String>,
+
+        #[arg(
+            long,
+            default_value = "false",
+            help = "Also delete the downloaded files"
+        )]
+        delete_files: bool,
+    },
+
+    #[command(about = "Recheck the downloaded data of torrents")]
+    Recheck {
+        #[arg(required = true, help = "Info-hashes of the torrents, or 'all'")]
+        hashes: Vec<String>,
+    },
+
+    #[command(about = "Reannounce torrents to their trackers")]
+    Reannounce {
+        #[arg(required = true, help = "Info-hashes of the torrents, or 'all'")]
+        hashes: Vec<String>,
+    },
+
+    #[command(about = "Move torrents in the download queue")]
+    Priority {
+        #[arg(value_enum, help = "Where to move the torrents")]
+        priority: QueuePriority,
+
+        #[arg(required = true, help = "Info-hashes of the torrents, or 'all'")]
+        hashes: Vec<String>,
+    },
+
+    #[command(about = "Move the files of torrents to another folder")]
+    Move {
+        #[arg(
+            short,
+            long,
+            help = "The new folder, relative ones are resolved against --download-root"
+        )]
+        location: String,
+
+        #[arg(required = true, help = "Info-hashes of the torrents, or 'all'")]
+        hashes: Vec<String>,
+    },
+
+    #[command(about = "Set the speed limits of torrents")]
+    Limit {
+        #[arg(long, help = "Download limit in bytes per second, 0 for unlimited")]
+        download: Option<u64>,
+
+        #[arg(long, help = "Upload limit in bytes per second, 0 for unlimited")]
+        upload: Option<u64>,
+
+        #[arg(required = true, help = "Info-hashes of the torrents, or 'all'")]
+        hashes: Vec<String>,
+    },
+
+    #[command(about = "Set the priority of files inside a torrent")]
+    FilePriority {
+        #[arg(help = "Info-hash of the torrent")]
+        hash: String,
+
+        #[arg(value_enum, help = "The new priority of the files")]
+        priority: FilePriority,
+
+        #[arg(
+            required = true,
+            value_delimiter = ',',
+            help = "Indexes of the files inside the torrent"
+        )]
+        files: Vec<usize>,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+enum WatchAction {
+    #[command(about = "Add a series to the watchlist, replacing any entry with the same name")]
+    Add {
+        #[arg(short, long, help = "The name of the anime, as searched on nyaa")]
+        anime_name: String,
+
+        #[arg(
+            short = 'g',
+            long,
+            help = "Only accept raws from this group (e.g. Ohys-Raws)"
+        )]
+        raw_group: Option<String>,
+
+        #[arg(
+            short,
+            long,
+            help = "Only accept raws with this resolution (e.g. 1920x1080)"
+        )]
+        resolution: Option<String>,
+
+        #[arg(
+            short = 'i',
+            long,
+            help = "The kitsunekko series name, if the fuzzy match picks the wrong one"
+        )]
+        subs_id: Option<String>,
+
+        #[arg(
+            long,
+            help = "Only match kitsunekko folders of this season, instead of the one in the anime name"
+        )]
+        season: Option<u32>,
+    },
+
+    #[command(about = "Remove a series from the watchlist")]
+    Remove {
+        #[arg(short, long, help = "The name of the anime to remove")]
+        anime_name: String,
+    },
+
+    #[command(about = "List the watched series")]
+    List,
+
+    #[command(about = "Poll every watched series for new raws and subtitles")]
+    Run {
+        #[arg(short, long, default_value = "30", help = "Minutes between polls")]
+        interval: u64,
+
+        #[arg(long, default_value = "false", help = "Poll a single time and exit")]
+        once: bool,
+
+        #[arg(
+            long,
+            default_value = "false",
+            help = "Add the subtitles to the raws as a Japanese track once they finished downloading"
+        )]
+        mux: bool,
+
+        #[command(flatten)]
+        mux_options: MuxOptions,
+
+        #[arg(
+            long,
+            default_value = "false",
+            help = "Look the series up on AniList, to search with their canonical titles and stop searching raws of finished ones"
+        )]
+        metadata: bool,
+
+        #[arg(
+            long,
+            default_value_t = subs::search::DEFAULT_MIN_SCORE,
+            allow_negative_numbers = true,
+            help = "The score below which the best kitsunekko folder isn't picked"
+        )]
+        min_score: i64,
+
+        #[arg(
+            long,
+            value_enum,
+            value_delimiter = ',',
+            default_value = "ja",
+            help = "The kitsunekko languages to download subtitles in, e.g. ja,en"
+        )]
+        sub_lang: Vec<SubLanguage>,
+    },
 }
 
 fn setup_logger(level: LevelFilter) -> std::result::Result<(), fern::InitError> {
@@ -80,9 +566,18 @@ fn setup_logger(level: LevelFilter) -> std::result::Result<(), fern::InitError>
 }
 
 #[derive(Debug)]
+#[allow(clippy::enum_variant_names)]
 enum OperationError {
     SearchError,
     DownloadError,
+    WatchlistError,
+    QueueError,
+    ScanError,
+    SyncError,
+    MuxError,
+    ExportError,
+    GrepError,
+    MetadataError,
 }
 
 impl std::fmt::Display for OperationError {
@@ -90,21 +585,36 @@ impl std::fmt::Display for OperationError {
         match self {
             OperationError::SearchError => write!(f, "Problem in search while doing operation"),
             OperationError::DownloadError => write!(f, "Problem in download while doing operation"),
+            OperationError::WatchlistError => {
+                write!(f, "Problem in watchlist while doing operation")
+            }
+            OperationError::QueueError => write!(f, "Problem in queue while doing operation"),
+            OperationError::ScanError => write!(f, "Problem in scan while doing operation"),
+            OperationError::SyncError => write!(f, "Problem in sync while doing operation"),
+            OperationError::MuxError => write!(f, "Problem in mux while doing operation"),
+            OperationError::ExportError => write!(f, "Problem in export while doing operation"),
+            OperationError::GrepError => write!(f, "Problem in grep while doing operation"),
+            OperationError::MetadataError => {
+                write!(f, "Problem in metadata lookup while doing operation")
+            }
         }
     }
 }
 
 impl std::error::Error for OperationError {}
 
-
+#[allow(dead_code)]
 enum OperationSuccess<Ind> {
     DryRun(Vec<Ind>),
     Downloaded(Vec<Ind>),
 }
 
-fn search_raws(args: &Args) -> Result<OperationSuccess<AnimeRawData>, OperationError> {
-    log::info!("Searching for anime raws for: {}", args.anime_name);
-    let result = raws::search::search_anime_raws(args.anime_name.as_str());
+fn search_raws(
+    args: &Args,
+    hooks: &Hooks,
+) -> Result<OperationSuccess<AnimeRawData>, OperationError> {
+    log::info!("Searching for anime raws for: {}", args.anime_name());
+    let result = raws::search::search_anime_raws(args.anime_name());
 
     let indexers = match result {
         Ok(result) => result,
@@ -117,7 +627,7 @@ fn search_raws(args: &Args) -> Result<OperationSuccess<AnimeRawData>, OperationE
     log::info!(
         "Found {} raws for anime {}",
         indexers.len(),
-        args.anime_name
+        args.anime_name()
     );
     log::trace!("Found raws: {:#?}", indexers);
 
@@ -127,19 +637,39 @@ fn search_raws(args: &Args) -> Result<OperationSuccess<AnimeRawData>, OperationE
     }
 
     log::trace!("Creating downloader...");
-    let downloader = AnimeRawDownloader::new();
+    let torrent_client =
+        torrent::create_torrent_client(&args.torrent_client, args.download_root.clone())
+            .change_context(OperationError::DownloadError)?;
+    let downloader = AnimeRawDownloader::new(
+        torrent_client,
+        TorrentAddOptions {
+            category: args.category.clone(),
+            paused: args.paused.then_some(true),
+            sequential_download: args.sequential_download.then_some(true),
+            ..TorrentAddOptions::for_series(args.anime_name())
+        },
+        args.episodes.clone(),
+    );
 
     log::info!("Queueing raws...");
+    // One folder per series, the same layout the watch daemon saves to
+    let dest = Destination::GivenFolderGuessFileBasename(format!(
+        "{}/{}",
+        raws::download::DEFAULT_FOLDER,
+        args.anime_name()
+    ));
     let pb = ProgressBar::new(indexers.len() as u64);
     for raw_data in &indexers {
-        let dest = Destination::Default;
-
-        //TODO: melhorar essa conversão (ou nem ter conversão)
-        let indexer = Indexer::new(&raw_data.anime_name, &raw_data.anime_raw_magnet);
-        let result = downloader.download_indexer_to_file(&indexer, &dest);
+        let result = downloader.download_raw(raw_data, &dest);
 
         match result {
-            Ok(_) => log::info!("Enqueued raw: {:#?}", raw_data),
+            Ok(_) => {
+                log::info!("Enqueued raw: {:#?}", raw_data);
+                hooks.fire(HookEvent::TorrentAdded {
+                    name: raw_data.anime_name.clone(),
+                    hash: raw_data.anime_raw_magnet.info_hash().to_owned(),
+                });
+            }
             Err(e) => log::error!("\n{e:?}"),
         }
     }
@@ -148,32 +678,110 @@ fn search_raws(args: &Args) -> Result<OperationSuccess<AnimeRawData>, OperationE
 
     log::info!("Waiting for downloads to finish...");
 
-    let pb = ProgressBar::new(0);
-    let result = downloader.wait_for_completion(|torrents: &TorrentList| {
-        let total_bytes = torrents.0.iter().map(|t| t.size().clone() as u64).sum();
-        let total_downloaded_bytes = torrents
-            .0
-            .iter()
-            .map(|t| t.downloaded().clone() as u64)
-            .sum();
-        pb.set_length(total_bytes);
-        pb.set_position(total_downloaded_bytes);
+    let progress = TorrentProgress::new();
+    let on_completed = hooks.on_torrents_update();
+    let result = downloader.wait_for_completion(&args.wait_options, |torrents: &TorrentList| {
+        progress.update(torrents);
+        on_completed(torrents);
     });
-    pb.finish();
+    progress.finish();
 
     match result {
-        Ok(_) => log::info!("Finished downloading raws"),
+        Ok(summary) => {
+            log::info!("Finished downloading {} raws", summary.finished.len());
+            for hash in &summary.stalled {
+                log::warn!("Raw stalled: {}", downloader.enqueued_name(hash));
+            }
+            for hash in &summary.timed_out {
+                log::warn!("Raw timed out: {}", downloader.enqueued_name(hash));
+            }
+
+            if args.mux {
+                mux_finished_raws(args, &downloader, &summary.finished);
+            }
+        }
         Err(e) => log::error!("\n{e:?}"),
     }
 
-    log::info!("Finished downloading raws");
-
     Ok(OperationSuccess::Downloaded(indexers))
 }
 
-fn search_subs(args: &Args) -> Result<OperationSuccess<Indexer>, OperationError> {
-    log::info!("Searching for anime subtitles for: {}", args.anime_name);
-    let indexers = subs::search::fetch_best_indexers_for(args.anime_name.as_str());
+// The subtitles are searched before the raws, so the ones of the finished raws are saved.
+// A failure is only logged, the raws are still there to be played with the sidecar files
+fn mux_finished_raws(args: &Args, downloader: &AnimeRawDownloader, hashes: &[String]) {
+    let subs_folder = Path::new(subs::download::DEFAULT_FOLDER).join(args.anime_name());
+    let subtitles = match library::muxing::find_japanese_subtitles(&subs_folder) {
+        Ok(subtitles) => subtitles,
+        Err(e) => {
+            log::error!("\n{e:?}");
+            return;
+        }
+    };
+
+    let torrents = match downloader.list(hashes) {
+        Ok(torrents) => torrents,
+        Err(e) => {
+            log::error!("\n{e:?}");
+            return;
+        }
+    };
+
+    let mut muxed = 0;
+    for torrent in &torrents.0 {
+        match library::muxing::torrent_videos(torrent) {
+            Ok(videos) => {
+                muxed += library::muxing::mux_videos(&videos, &subtitles, &args.mux_options).0
+            }
+            Err(e) => log::error!("\n{e:?}"),
+        }
+    }
+
+    log::info!("Muxed subtitles into {} raws", muxed);
+}
+
+// Every language is searched even when one fails, so one missing translation doesn't
+// cost the others. The search only fails when no language was found
+fn search_subs(
+    args: &Args,
+    aliases: &TitleAliases,
+    hooks: &Hooks,
+) -> Result<OperationSuccess<Indexer>, OperationError> {
+    let mut subs_indexers = Vec::new();
+    let mut last_error = None;
+
+    for language in &args.sub_lang {
+        match search_subs_in(args, *language, aliases, hooks) {
+            Ok(OperationSuccess::DryRun(indexers) | OperationSuccess::Downloaded(indexers)) => {
+                subs_indexers.extend(indexers)
+            }
+            Err(e) if args.sub_lang.len() > 1 => {
+                log::error!("\n{e:?}");
+                last_error = Some(e);
+            }
+            Err(e) => return Err(e),
+        }
+    }
+
+    match last_error {
+        Some(e) if subs_indexers.is_empty() => Err(e),
+        _ if args.dry_run => Ok(OperationSuccess::DryRun(subs_indexers)),
+        _ => Ok(OperationSuccess::Downloaded(subs_indexers)),
+    }
+}
+
+fn search_subs_in(
+    args: &Args,
+    language: SubLanguage,
+    aliases: &TitleAliases,
+    hooks: &Hooks,
+) -> Result<OperationSuccess<Indexer>, OperationError> {
+    log::info!(
+        "Searching for {} anime subtitles for: {}",
+        language.code(),
+        args.anime_name()
+    );
+    let indexers =
+        subs::search::fetch_best_indexers_for(args.anime_name(), args.season, language, aliases);
 
     let anime_indexers = match indexers {
         Ok(indexers) => indexers,
@@ -183,14 +791,30 @@ fn search_subs(args: &Args) -> Result<OperationSuccess<Indexer>, OperationError>
         }
     };
 
+    if let Some(count) = args.explain {
+        for (rank, anime_indexer) in anime_indexers.iter().take(count).enumerate() {
+            log::info!("{}. {}", rank + 1, anime_indexer);
+        }
+    }
+
     let anime_indexer = match anime_indexers.first() {
-        Some(anime_indexer) => anime_indexer,
-        None => {
-            log::error!("Subs not found for: {}", args.anime_name);
+        Some(anime_indexer) if anime_indexer.score() >= args.min_score => anime_indexer.indexer(),
+        Some(anime_indexer) => {
+            log::error!(
+                "No confident subs match for: {}, the best is {}",
+                args.anime_name(),
+                anime_indexer
+            );
+            log::error!("Pass --explain to see the candidates, or lower --min-score");
             return Err(Report::new(OperationError::SearchError).attach_printable(
-                "Subs not found for: ".to_string() + args.anime_name.as_str(),
+                "No confident subs match for: ".to_string() + args.anime_name(),
             ));
         }
+        None => {
+            log::error!("Subs not found for: {}", args.anime_name());
+            return Err(Report::new(OperationError::SearchError)
+                .attach_printable("Subs not found for: ".to_string() + args.anime_name()));
+        }
     };
 
     log::debug!("Found anime indexer: {:#?}", anime_indexer);
@@ -211,15 +835,32 @@ fn search_subs(args: &Args) -> Result<OperationSuccess<Indexer>, OperationError>
     }
 
     log::trace!("Creating downloader...");
-    let downloader = AnimeSubsDownloader::new();
+    let downloader = AnimeSubsDownloader::new().with_language(language);
+    // One folder per series, like the raws, so they can be paired by episode
+    let dest = Destination::GivenFolderGuessFileBasename(format!(
+        "{}/{}",
+        subs::download::DEFAULT_FOLDER,
+        args.anime_name()
+    ));
 
     log::info!("Downloading subs...");
     let pb = ProgressBar::new(subs_indexers.len() as u64);
     for subs_indexer in &subs_indexers {
-        let result = downloader.download_indexer_to_file(&subs_indexer, &Destination::Default);
+        // Subs saved by a previous run are skipped, they don't trigger the hooks again
+        let path = downloader.indexer_file_path(subs_indexer, &dest);
+        let already_saved = downloader.saved_file_path(subs_indexer, &dest).is_some();
+
+        let result = downloader.download_indexer_to_file(subs_indexer, &dest);
 
         match result {
-            Ok(_) => log::trace!("Downloaded subs: {}", subs_indexer.name()),
+            Ok(_) if already_saved => log::trace!("Subs already saved: {}", subs_indexer.name()),
+            Ok(_) => {
+                log::trace!("Downloaded subs: {}", subs_indexer.name());
+                hooks.fire(HookEvent::SubtitleSaved {
+                    name: subs_indexer.name().to_owned(),
+                    path: path.display().to_string(),
+                });
+            }
             Err(err) => log::error!("\n{err:?}"),
         }
 
@@ -232,8 +873,258 @@ fn search_subs(args: &Args) -> Result<OperationSuccess<Indexer>, OperationError>
     Ok(OperationSuccess::Downloaded(subs_indexers))
 }
 
+fn watch(
+    args: &Args,
+    watchlist_path: &Path,
+    state_path: &Path,
+    aliases: TitleAliases,
+    hooks: Hooks,
+    action: &WatchAction,
+) -> Result<(), OperationError> {
+    let mut watchlist = Watchlist::load(watchlist_path)
+        .attach_printable("Failed to load watchlist")
+        .change_context(OperationError::WatchlistError)?;
+
+    match action {
+        WatchAction::Add {
+            anime_name,
+            raw_group,
+            resolution,
+            subs_id,
+            season,
+        } => {
+            watchlist.add(
+                WatchlistEntry::new(
+                    anime_name,
+                    raw_group.as_deref(),
+                    resolution.as_deref(),
+                    subs_id.as_deref(),
+                )
+                .with_season(*season),
+            );
+            watchlist
+                .save(watchlist_path)
+                .attach_printable("Failed to save watchlist")
+                .change_context(OperationError::WatchlistError)?;
+            log::info!("Added {} to the watchlist", anime_name);
+        }
+        WatchAction::Remove { anime_name } => {
+            if !watchlist.remove(anime_name) {
+                log::warn!("{} is not in the watchlist", anime_name);
+                return Ok(());
+            }
+            watchlist
+                .save(watchlist_path)
+                .attach_printable("Failed to save watchlist")
+                .change_context(OperationError::WatchlistError)?;
+            log::info!("Removed {} from the watchlist", anime_name);
+        }
+        WatchAction::List => {
+            log::info!("Watching {} series", watchlist.0.len());
+            for entry in &watchlist.0 {
+                log::info!("{:#?}", entry);
+            }
+        }
+        WatchAction::Run {
+            interval,
+            once,
+            mux,
+            mux_options,
+            metadata,
+            min_score,
+            sub_lang,
+        } => {
+            let state = WatchState::load(state_path)
+                .attach_printable("Failed to load watch state")
+                .change_context(OperationError::WatchlistError)?;
+
+            let torrent_client =
+                torrent::create_torrent_client(&args.torrent_client, args.download_root.clone())
+                    .change_context(OperationError::DownloadError)?;
+
+            let mut daemon = WatchDaemon::new(
+                watchlist,
+                state,
+                state_path.to_path_buf(),
+                torrent_client,
+                aliases,
+                hooks,
+            )
+            .with_min_score(*min_score)
+            .with_languages(sub_lang.clone());
+            if *metadata {
+                daemon = daemon.with_metadata(AniListClient::new(anilist::DEFAULT_URL));
+            }
+            if *mux {
+                daemon = daemon.with_mux(mux_options.clone());
+            }
+
+            let result = match once {
+                true => daemon.poll_once(),
+                false => daemon.run(Duration::from_secs(interval * 60)),
+            };
+
+            result.change_context(OperationError::DownloadError)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn scan(scanner: &LibraryScanner, folder: &Path, dry_run: bool) -> Result<(), OperationError> {
+    let count = scanner
+        .scan(folder, dry_run)
+        .change_context(OperationError::ScanError)?;
+
+    log::info!("Saved {} subtitles", count);
+    Ok(())
+}
+
+// Subtitles that fail to export are reported and skipped, so one broken file doesn't stop
+// a whole season
+fn export(paths: &[PathBuf], options: &ExportOptions) -> Result<(), OperationError> {
+    let subtitles = study::find_subtitles(paths).change_context(OperationError::ExportError)?;
+
+    let mut exported = 0;
+    for subtitle in &subtitles {
+        match study::export_subtitle(subtitle, options) {
+            Ok(files) => {
+                exported += 1;
+                for file in files {
+                    log::info!("Exported: {}", file.display());
+                }
+            }
+            Err(e) => log::warn!("\n{:?}", e),
+        }
+    }
+
+    if exported == 0 {
+        return Err(Report::new(OperationError::ExportError)
+            .attach_printable("None of the subtitles could be exported"));
+    }
+
+    log::info!("Exported {} of {} subtitles", exported, subtitles.len());
+    Ok(())
+}
+
+fn lookup_metadata(anime_name: &str) -> Result<Option<AnimeMetadata>, OperationError> {
+    let metadata = AniListClient::new(anilist::DEFAULT_URL)
+        .search(anime_name)
+        .change_context(OperationError::MetadataError)?;
+
+    match &metadata {
+        Some(metadata) => {
+            log::info!("Found on AniList: {}", metadata.titles().join(" / "));
+            log::info!(
+                "Season: {}, episodes: {}, status: {:?}",
+                metadata.season().as_deref().unwrap_or("unknown"),
+                metadata
+                    .episodes()
+                    .map_or_else(|| "unknown".to_owned(), |episodes| episodes.to_string()),
+                metadata.status()
+            );
+        }
+        None => log::warn!("{} was not found on AniList", anime_name),
+    }
+
+    Ok(metadata)
+}
+
+fn grep_subs(query: &str, paths: &[PathBuf]) -> Result<(), OperationError> {
+    let subtitles = study::find_subtitles(paths).change_context(OperationError::GrepError)?;
+    let index = SubtitleIndex::build(&subtitles).change_context(OperationError::GrepError)?;
+    log::debug!(
+        "Indexed {} lines of {} subtitles",
+        index.line_count(),
+        subtitles.len()
+    );
+
+    // Matches go to stdout so they can be piped, the rest is logged
+    let found = index.search(query);
+    for line in &found {
+        let episode = line
+            .episode()
+            .map_or_else(|| "-".to_owned(), |episode| format!("{:02}", episode));
+        println!(
+            "{} #{} {} {}",
+            line.series(),
+            episode,
+            format_timestamp(*line.start()),
+            line.text()
+        );
+    }
+
+    log::info!("Found {} lines", found.len());
+    Ok(())
+}
+
+// The queue commands use features only qBittorrent's WebUI API has
+fn queue(
+    torrent_client: &TorrentClientConfig,
+    download_root: Option<PathBuf>,
+    action: &QueueAction,
+) -> Result<(), OperationError> {
+    if torrent_client.backend != TorrentClientBackend::QBitTorrent {
+        return Err(Report::new(OperationError::QueueError)
+            .attach_printable("The queue commands only support qBittorrent"));
+    }
+
+    let client = QBitTorrentClient::new(download_root);
+    let runtime = Runtime::new().unwrap();
+
+    let result = runtime.block_on(async {
+        match action {
+            QueueAction::List { category, tag } => {
+                let filter = TorrentInfoFilter {
+                    category: category.clone(),
+                    tag: tag.clone(),
+                    ..Default::default()
+                };
+                let torrents = client.list(&filter).await?;
+
+                log::info!("Found {} torrents", torrents.0.len());
+                for torrent in &torrents.0 {
+                    log::info!(
+                        "{} [{:?}] {} ({}/{} bytes)",
+                        torrent.hash(),
+                        torrent.state(),
+                        torrent.name(),
+                        torrent.downloaded(),
+                        torrent.size()
+                    );
+                }
+                Ok(())
+            }
+            QueueAction::Pause { hashes } => client.pause(hashes).await,
+            QueueAction::Resume { hashes } => client.resume(hashes).await,
+            QueueAction::Delete {
+                hashes,
+                delete_files,
+            } => client.remove(hashes, *delete_files).await,
+            QueueAction::Recheck { hashes } => client.recheck(hashes).await,
+            QueueAction::Reannounce { hashes } => client.reannounce(hashes).await,
+            QueueAction::Priority { priority, hashes } => {
+                client.set_queue_priority(hashes, *priority).await
+            }
+            QueueAction::Move { location, hashes } => client.set_location(hashes, location).await,
+            QueueAction::Limit {
+                download,
+                upload,
+                hashes,
+            } => client.set_limits(hashes, *download, *upload).await,
+            QueueAction::FilePriority {
+                hash,
+                priority,
+                files,
+            } => client.set_file_priority(hash, files, *priority).await,
+        }
+    });
+
+    result.change_context(OperationError::QueueError)
+}
+
 fn main() {
-    let args = Args::parse();
+    let mut args = Args::parse();
     log::info!("Starting Anime Downloader");
 
     let level = match args.verbose {
@@ -254,6 +1145,94 @@ fn main() {
         log::set_max_level(log::LevelFilter::Trace);
     }
 
+    let hooks = match Hooks::load(&args.hooks) {
+        Ok(hooks) => hooks,
+        Err(e) => {
+            log::error!("\n{:?}", e);
+            log::error!("Failed to load hooks, aborting");
+            return;
+        }
+    };
+
+    let mut aliases = match TitleAliases::load(&args.aliases) {
+        Ok(aliases) => aliases,
+        Err(e) => {
+            log::error!("\n{:?}", e);
+            log::error!("Failed to load aliases, aborting");
+            return;
+        }
+    };
+
+    if let Some(command) = &args.command {
+        let result = match command {
+            Command::Watch {
+                watchlist,
+                state,
+                action,
+            } => watch(&args, watchlist, state, aliases, hooks, action),
+            Command::Scan {
+                folder,
+                dry_run,
+                sync,
+                sync_options,
+                mux,
+                mux_options,
+                min_score,
+                sub_lang,
+            } => {
+                let sync_options = sync.then(|| sync_options.clone());
+                let mux_options = mux.then(|| mux_options.clone());
+                let scanner = LibraryScanner::new(aliases, hooks, sync_options, mux_options)
+                    .with_min_score(*min_score)
+                    .with_languages(sub_lang.clone());
+                scan(&scanner, folder, *dry_run)
+            }
+            Command::Mux {
+                video,
+                subtitle,
+                options,
+            } => mux::mux_subtitle(video, subtitle, options)
+                .map(|_| log::info!("Muxed subtitles into: {}", video.display()))
+                .change_context(OperationError::MuxError),
+            Command::Sync {
+                video,
+                subtitle,
+                output,
+                options,
+            } => sync::sync_subtitle(video, subtitle, output.as_deref(), options)
+                .map(|path| log::info!("Saved synchronized subtitles: {}", path.display()))
+                .change_context(OperationError::SyncError),
+            Command::Export { paths, options } => export(paths, options),
+            Command::GrepSubs { query, paths } => grep_subs(query, paths),
+            Command::Info { anime_name } => lookup_metadata(anime_name).map(|_| ()),
+            Command::Queue { action } => {
+                queue(&args.torrent_client, args.download_root.clone(), action)
+            }
+        };
+
+        match result {
+            Ok(_) => log::info!("Done!"),
+            Err(e) => log::error!("\n{:?}", e),
+        }
+        return;
+    }
+
+    if args.metadata {
+        match lookup_metadata(args.anime_name()) {
+            Ok(Some(metadata)) => {
+                aliases.add(metadata.titles());
+                if let Some(title) = metadata.title() {
+                    args.anime_name = Some(title.to_owned());
+                }
+            }
+            Ok(None) => log::warn!("Searching with the given name instead"),
+            Err(e) => {
+                log::error!("\n{:?}", e);
+                return;
+            }
+        }
+    }
+
     log::info!("Search type: {:#?}", args.search_type);
 
     if !args.dry_run {
@@ -263,8 +1242,8 @@ fn main() {
             dry_run: true,
             ..args.clone()
         };
-        if args.search_type == SearchType::Raw || args.search_type == SearchType::Both {
-            match search_raws(&args) {
+        if args.search_type == SearchType::Subtitles || args.search_type == SearchType::Both {
+            match search_subs(&args, &aliases, &hooks) {
                 Ok(_) => (), //TODO: reutilizar o resultado na busca final
                 Err(e) => {
                     log::error!("\n{:?}", e);
@@ -273,8 +1252,8 @@ fn main() {
                 }
             }
         }
-        if args.search_type == SearchType::Subtitles || args.search_type == SearchType::Both {
-            match search_subs(&args) {
+        if args.search_type == SearchType::Raw || args.search_type == SearchType::Both {
+            match search_raws(&args, &hooks) {
                 Ok(_) => (), //TODO: reutilizar o resultado na busca final
                 Err(e) => {
                     log::error!("\n{:?}", e);
@@ -285,8 +1264,9 @@ fn main() {
         }
     }
 
-    if args.search_type == SearchType::Raw || args.search_type == SearchType::Both {
-        match search_raws(&args) {
+    // Subtitles go first, so they are saved by the time the raws can be muxed
+    if args.search_type == SearchType::Subtitles || args.search_type == SearchType::Both {
+        match search_subs(&args, &aliases, &hooks) {
             Ok(_) => (),
             Err(e) => {
                 log::error!("\n{:?}", e);
@@ -295,8 +1275,8 @@ fn main() {
         }
     }
 
-    if args.search_type == SearchType::Subtitles || args.search_type == SearchType::Both {
-        match search_subs(&args) {
+    if args.search_type == SearchType::Raw || args.search_type == SearchType::Both {
+        match search_raws(&args, &hooks) {
             Ok(_) => (),
             Err(e) => {
                 log::error!("\n{:?}", e);
@@ -305,5 +1285,9 @@ fn main() {
         }
     }
 
+    hooks.fire(HookEvent::RunFinished {
+        name: args.anime_name().to_owned(),
+    });
+
     log::info!("Done!");
 }
@@ -0,0 +1,217 @@
+use error_stack::{IntoReport, Report, Result, ResultExt};
+use reqwest::blocking::Client;
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+
+use super::{AiringStatus, AnimeMetadata, MetadataError};
+
+pub const DEFAULT_URL: &str = "https://graphql.anilist.co";
+
+// AniList picks the best match of the search itself, including synonyms and native titles
+const SEARCH_QUERY: &str = "query ($search: String) {
+  Media(search: $search, type: ANIME) {
+    id
+    title { romaji english native }
+    synonyms
+    season
+    seasonYear
+    episodes
+    status
+  }
+}";
+
+pub struct AniListClient {
+    http_client: Client,
+    url: String,
+}
+
+#[derive(Serialize, Debug)]
+struct GraphQlRequest<'a> {
+    query: &'a str,
+    variables: SearchVariables<'a>,
+}
+
+#[derive(Serialize, Debug)]
+struct SearchVariables<'a> {
+    search: &'a str,
+}
+
+#[derive(Deserialize, Debug)]
+struct GraphQlResponse {
+    data: Option<MediaData>,
+    #[serde(default)]
+    errors: Vec<GraphQlError>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+struct MediaData {
+    media: Option<Media>,
+}
+
+#[derive(Deserialize, Debug)]
+struct GraphQlError {
+    message: String,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct Media {
+    id: u64,
+    title: MediaTitle,
+    #[serde(default)]
+    synonyms: Vec<String>,
+    season: Option<String>,
+    season_year: Option<u32>,
+    episodes: Option<u32>,
+    status: Option<AiringStatus>,
+}
+
+#[derive(Deserialize, Debug)]
+struct MediaTitle {
+    romaji: Option<String>,
+    english: Option<String>,
+    native: Option<String>,
+}
+
+impl Media {
+    fn into_metadata(self) -> AnimeMetadata {
+        let season = match (self.season, self.season_year) {
+            (Some(season), Some(year)) => Some(format!("{} {}", season, year)),
+            (season, year) => season.or(year.map(|year| year.to_string())),
+        };
+
+        AnimeMetadata {
+            id: self.id,
+            romaji: self.title.romaji,
+            english: self.title.english,
+            native: self.title.native,
+            synonyms: self.synonyms,
+            season,
+            episodes: self.episodes,
+            status: self.status,
+        }
+    }
+}
+
+impl AniListClient {
+    pub fn new(url: &str) -> Self {
+        AniListClient {
+            http_client: Client::new(),
+            url: url.to_owned(),
+        }
+    }
+
+    // None when AniList has no anime matching the query
+    pub fn search(&self, query: &str) -> Result<Option<AnimeMetadata>, MetadataError> {
+        let request = GraphQlRequest {
+            query: SEARCH_QUERY,
+            variables: SearchVariables { search: query },
+        };
+
+        let res = self
+            .http_client
+            .post(&self.url)
+            .json(&request)
+            .send()
+            .into_report()
+            .attach_printable_lazy(|| format!("Failed to search AniList for: {}", query))
+            .change_context(MetadataError)?;
+
+        // AniList answers 404 along with a "Not Found." error when nothing matches
+        if res.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+
+        if !res.status().is_success() {
+            return Err(Report::new(MetadataError)
+                .attach_printable(format!("AniList failed with status: {}", res.status())));
+        }
+
+        let response: GraphQlResponse = res
+            .json()
+            .into_report()
+            .attach_printable_lazy(|| format!("Failed to read AniList response for: {}", query))
+            .change_context(MetadataError)?;
+
+        if let Some(error) = response.errors.first() {
+            return Err(Report::new(MetadataError)
+                .attach_printable(format!("AniList error: {}", error.message)));
+        }
+
+        Ok(response
+            .data
+            .and_then(|data| data.media)
+            .map(Media::into_metadata))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mockito::Matcher;
+
+    #[test]
+    fn test_search() {
+        let mut server = mockito::Server::new();
+        let mock = server
+            .mock("POST", "/")
+            .match_header("content-type", "application/json")
+            .match_body(Matcher::PartialJsonString(
+                r#"{"variables": {"search": "bocchi"}}"#.to_string(),
+            ))
+            .with_status(200)
+            .with_body(
+                r#"{"data": {"Media": {
+                    "id": 130003,
+                    "title": {"romaji": "Bocchi the Rock!", "english": "BOCCHI THE ROCK!", "native": "ぼっち・ざ・ろっく！"},
+                    "synonyms": ["BTR"],
+                    "season": "FALL",
+                    "seasonYear": 2022,
+                    "episodes": 12,
+                    "status": "FINISHED"
+                }}}"#,
+            )
+            .create();
+
+        let metadata = AniListClient::new(&server.url())
+            .search("bocchi")
+            .unwrap()
+            .unwrap();
+
+        mock.assert();
+        assert_eq!(metadata.title(), Some("Bocchi the Rock!"));
+        assert_eq!(metadata.native().as_deref(), Some("ぼっち・ざ・ろっく！"));
+        assert_eq!(metadata.season().as_deref(), Some("FALL 2022"));
+        assert_eq!(metadata.episodes(), &Some(12));
+        assert_eq!(metadata.status(), &Some(AiringStatus::Finished));
+    }
+
+    #[test]
+    fn test_search_not_found() {
+        let mut server = mockito::Server::new();
+        let _mock = server
+            .mock("POST", "/")
+            .with_status(404)
+            .with_body(r#"{"errors": [{"message": "Not Found.", "status": 404}], "data": {"Media": null}}"#)
+            .create();
+
+        let metadata = AniListClient::new(&server.url())
+            .search("not an anime")
+            .unwrap();
+
+        assert_eq!(metadata, None);
+    }
+
+    #[test]
+    fn test_search_error() {
+        let mut server = mockito::Server::new();
+        let _mock = server
+            .mock("POST", "/")
+            .with_status(200)
+            .with_body(r#"{"errors": [{"message": "Too Many Requests."}], "data": null}"#)
+            .create();
+
+        assert!(AniListClient::new(&server.url()).search("bocchi").is_err());
+    }
+}
@@ -0,0 +1,138 @@
+use std::collections::BTreeSet;
+use std::error::Error;
+
+use derive_getters::Getters;
+use serde::Deserialize;
+
+pub mod anilist;
+
+#[derive(Debug)]
+pub struct MetadataError;
+
+impl std::fmt::Display for MetadataError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Failed to look up anime metadata")
+    }
+}
+
+impl Error for MetadataError {}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum AiringStatus {
+    Finished,
+    Releasing,
+    NotYetReleased,
+    Cancelled,
+    Hiatus,
+    // Statuses AniList adds later shouldn't fail the whole lookup
+    #[serde(other)]
+    Unknown,
+}
+
+// The canonical entry of a series, with every title it's known by
+#[derive(Debug, Clone, PartialEq, Eq, Getters)]
+pub struct AnimeMetadata {
+    id: u64,
+    romaji: Option<String>,
+    english: Option<String>,
+    native: Option<String>,
+    synonyms: Vec<String>,
+    // e.g. "FALL 2022"
+    season: Option<String>,
+    episodes: Option<u32>,
+    status: Option<AiringStatus>,
+}
+
+impl AnimeMetadata {
+    // Romaji first, it's how raws and kitsunekko name most series
+    pub fn titles(&self) -> Vec<String> {
+        let mut titles: Vec<String> = Vec::new();
+        let all = [&self.romaji, &self.english, &self.native]
+            .into_iter()
+            .flatten()
+            .chain(&self.synonyms);
+
+        for title in all {
+            if !titles.contains(title) {
+                titles.push(title.clone());
+            }
+        }
+
+        titles
+    }
+
+    pub fn title(&self) -> Option<&str> {
+        self.romaji
+            .as_deref()
+            .or(self.english.as_deref())
+            .or(self.native.as_deref())
+    }
+
+    // Raws of episodes past the count belong to a sequel, or are misnumbered
+    pub fn has_episode(&self, episode: u32) -> bool {
+        self.episodes.is_none_or(|count| episode <= count)
+    }
+
+    // Only a finished series has a final episode count, airing ones may get more
+    pub fn is_complete(&self, episodes: &BTreeSet<u32>) -> bool {
+        match (self.status, self.episodes) {
+            (Some(AiringStatus::Finished), Some(count)) => {
+                (1..=count).all(|episode| episodes.contains(&episode))
+            }
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bocchi(status: AiringStatus) -> AnimeMetadata {
+        AnimeMetadata {
+            id: 130003,
+            romaji: Some("Bocchi the Rock!".to_owned()),
+            english: Some("BOCCHI THE ROCK!".to_owned()),
+            native: Some("ぼっち・ざ・ろっく！".to_owned()),
+            synonyms: vec!["Bocchi the Rock!".to_owned(), "BTR".to_owned()],
+            season: Some("FALL 2022".to_owned()),
+            episodes: Some(3),
+            status: Some(status),
+        }
+    }
+
+    #[test]
+    fn test_titles() {
+        assert_eq!(
+            bocchi(AiringStatus::Finished).titles(),
+            vec![
+                "Bocchi the Rock!",
+                "BOCCHI THE ROCK!",
+                "ぼっち・ざ・ろっく！",
+                "BTR"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_is_complete() {
+        let episodes = BTreeSet::from([1, 2, 3]);
+
+        assert!(bocchi(AiringStatus::Finished).is_complete(&episodes));
+        assert!(!bocchi(AiringStatus::Releasing).is_complete(&episodes));
+        assert!(!bocchi(AiringStatus::Finished).is_complete(&BTreeSet::from([1, 3])));
+    }
+
+    #[test]
+    fn test_has_episode() {
+        assert!(bocchi(AiringStatus::Finished).has_episode(3));
+        assert!(!bocchi(AiringStatus::Finished).has_episode(4));
+    }
+
+    #[test]
+    fn test_deserialize_unknown_status() {
+        let status: AiringStatus = serde_json::from_str(r#""ON_BREAK""#).unwrap();
+        assert_eq!(status, AiringStatus::Unknown);
+    }
+}
@@ -0,0 +1,354 @@
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use clap::ValueEnum;
+use error_stack::{IntoReport, Report, Result, ResultExt};
+use serde::Deserialize;
+
+const LANGUAGE: &str = "jpn";
+const TRACK_NAME: &str = "Japanese";
+
+#[derive(Debug)]
+pub struct MuxError;
+
+impl std::fmt::Display for MuxError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Failed to mux subtitles into the video")
+    }
+}
+
+impl Error for MuxError {}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum MuxTool {
+    Mkvmerge,
+    Ffmpeg,
+}
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct MuxOptions {
+    #[arg(
+        long,
+        default_value = "mkvmerge",
+        help = "The program muxing the subtitles"
+    )]
+    pub mux_tool: MuxTool,
+
+    #[arg(
+        long,
+        default_value = "false",
+        help = "Delete the subtitle file once it's inside the video"
+    )]
+    pub delete_sidecar: bool,
+}
+
+// Adds the subtitles to the MKV as its default Japanese track, the other subtitle tracks
+// lose their default flag. The video is only replaced once the muxed copy was written completely
+pub fn mux_subtitle(video: &Path, subtitle: &Path, options: &MuxOptions) -> Result<(), MuxError> {
+    let is_mkv = video
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("mkv"));
+    if !is_mkv {
+        return Err(Report::new(MuxError)
+            .attach_printable(format!("Only MKV videos can be muxed: {}", video.display())));
+    }
+
+    let output = muxing_path(video);
+    let command = match options.mux_tool {
+        MuxTool::Mkvmerge => {
+            mkvmerge_command(video, subtitle, &output, &mkvmerge_subtitle_tracks(video)?)
+        }
+        MuxTool::Ffmpeg => ffmpeg_command(video, subtitle, &output, count_subtitle_tracks(video)?),
+    };
+
+    if let Err(e) = run(command, options.mux_tool) {
+        let _ = fs::remove_file(&output);
+        return Err(e.attach_printable(format!("Failed to mux: {}", video.display())));
+    }
+
+    fs::rename(&output, video)
+        .into_report()
+        .attach_printable_lazy(|| format!("Failed to replace video: {}", video.display()))
+        .change_context(MuxError)?;
+
+    if options.delete_sidecar {
+        fs::remove_file(subtitle)
+            .into_report()
+            .attach_printable_lazy(|| format!("Failed to delete: {}", subtitle.display()))
+            .change_context(MuxError)?;
+    }
+
+    Ok(())
+}
+
+// e.g. "Bocchi - 01.muxing.mkv" next to "Bocchi - 01.mkv"
+fn muxing_path(video: &Path) -> PathBuf {
+    video.with_extension("muxing.mkv")
+}
+
+// The flags before a file apply to its tracks, `subtitle_tracks` are the ids of the
+// subtitle tracks already in the video
+fn mkvmerge_command(
+    video: &Path,
+    subtitle: &Path,
+    output: &Path,
+    subtitle_tracks: &[u64],
+) -> Command {
+    let mut command = Command::new("mkvmerge");
+    command.arg("--quiet").arg("--output").arg(output);
+    for track in subtitle_tracks {
+        command.args(["--default-track-flag", &format!("{}:no", track)]);
+    }
+    command
+        .arg(video)
+        .args(["--language", &format!("0:{}", LANGUAGE)])
+        .args(["--track-name", &format!("0:{}", TRACK_NAME)])
+        .args(["--default-track-flag", "0:yes"])
+        .arg(subtitle);
+    command
+}
+
+// ffmpeg addresses the new track by its position among the subtitle tracks
+fn ffmpeg_command(video: &Path, subtitle: &Path, output: &Path, subtitle_tracks: usize) -> Command {
+    let track = format!("s:s:{}", subtitle_tracks);
+
+    let mut command = Command::new("ffmpeg");
+    command
+        .args(["-nostdin", "-v", "error", "-y", "-i"])
+        .arg(video)
+        .arg("-i")
+        .arg(subtitle)
+        .args(["-map", "0", "-map", "1:0", "-c", "copy"])
+        .args([
+            &format!("-metadata:{}", track),
+            &format!("language={}", LANGUAGE),
+        ])
+        .args([
+            &format!("-metadata:{}", track),
+            &format!("title={}", TRACK_NAME),
+        ])
+        .args(["-disposition:s", "0"])
+        .args([&format!("-disposition:{}", &track[2..]), "default"])
+        .arg(output);
+    command
+}
+
+#[derive(Deserialize, Debug)]
+struct MkvmergeIdentification {
+    #[serde(default)]
+    tracks: Vec<MkvmergeTrack>,
+}
+
+#[derive(Deserialize, Debug)]
+struct MkvmergeTrack {
+    id: u64,
+    #[serde(rename = "type")]
+    track_type: String,
+}
+
+// The ids mkvmerge gives to the subtitle tracks of the video, read from "mkvmerge -J"
+fn mkvmerge_subtitle_tracks(video: &Path) -> Result<Vec<u64>, MuxError> {
+    let output = Command::new("mkvmerge")
+        .arg("-J")
+        .arg(video)
+        .output()
+        .into_report()
+        .attach_printable("Failed to run mkvmerge, is it installed?")
+        .change_context(MuxError)?;
+
+    if !output.status.success() {
+        return Err(Report::new(MuxError)
+            .attach_printable(format!("mkvmerge failed to read: {}", video.display()))
+            .attach_printable(String::from_utf8_lossy(&output.stdout).into_owned()));
+    }
+
+    parse_mkvmerge_subtitle_tracks(&output.stdout)
+}
+
+fn parse_mkvmerge_subtitle_tracks(identification: &[u8]) -> Result<Vec<u64>, MuxError> {
+    let identification: MkvmergeIdentification = serde_json::from_slice(identification)
+        .into_report()
+        .attach_printable("Failed to parse the tracks listed by mkvmerge")
+        .change_context(MuxError)?;
+
+    Ok(identification
+        .tracks
+        .iter()
+        .filter(|track| track.track_type == "subtitles")
+        .map(|track| track.id)
+        .collect())
+}
+
+// One line per subtitle track of the video with the requested entry, e.g. its language
+fn probe_subtitle_tracks(video: &Path, entry: &str) -> Result<Vec<String>, MuxError> {
+    let output = Command::new("ffprobe")
+        .args([
+            "-v",
+            "error",
+            "-select_streams",
+            "s",
+            "-show_entries",
+            entry,
+        ])
+        .args(["-of", "csv=p=0"])
+        .arg(video)
+        .output()
+        .into_report()
+        .attach_printable("Failed to run ffprobe, is it installed?")
+        .change_context(MuxError)?;
+
+    if !output.status.success() {
+        return Err(Report::new(MuxError)
+            .attach_printable(format!("ffprobe failed to read: {}", video.display()))
+            .attach_printable(String::from_utf8_lossy(&output.stderr).into_owned()));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(|line| line.trim().to_owned())
+        .collect())
+}
+
+fn count_subtitle_tracks(video: &Path) -> Result<usize, MuxError> {
+    Ok(probe_subtitle_tracks(video, "stream=index")?
+        .iter()
+        .filter(|line| !line.is_empty())
+        .count())
+}
+
+// Videos that can't be probed count as having none
+pub fn has_japanese_track(video: &Path) -> bool {
+    match probe_subtitle_tracks(video, "stream_tags=language") {
+        Ok(languages) => languages
+            .iter()
+            .any(|language| language == LANGUAGE || language == "ja"),
+        Err(e) => {
+            log::debug!("\n{e:?}");
+            false
+        }
+    }
+}
+
+fn run(mut command: Command, tool: MuxTool) -> Result<(), MuxError> {
+    let output = command
+        .output()
+        .into_report()
+        .attach_printable_lazy(|| format!("Failed to run {:?}, is it installed?", tool))
+        .change_context(MuxError)?;
+
+    // mkvmerge exits with 1 when it only has warnings
+    let success = match tool {
+        MuxTool::Mkvmerge => matches!(output.status.code(), Some(0 | 1)),
+        MuxTool::Ffmpeg => output.status.success(),
+    };
+
+    if !success {
+        return Err(Report::new(MuxError)
+            .attach_printable(format!("{:?} exited with: {}", tool, output.status))
+            .attach_printable(String::from_utf8_lossy(&output.stderr).into_owned())
+            .attach_printable(String::from_utf8_lossy(&output.stdout).into_owned()));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(command: &Command) -> Vec<String> {
+        command
+            .get_args()
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn test_mkvmerge_command() {
+        let video = Path::new("raws/Bocchi - 01.mkv");
+        let command = mkvmerge_command(
+            video,
+            Path::new("raws/Bocchi - 01.srt"),
+            &muxing_path(video),
+            &[2, 3],
+        );
+
+        assert_eq!(command.get_program(), "mkvmerge");
+        assert_eq!(
+            args(&command),
+            vec![
+                "--quiet",
+                "--output",
+                "raws/Bocchi - 01.muxing.mkv",
+                "--default-track-flag",
+                "2:no",
+                "--default-track-flag",
+                "3:no",
+                "raws/Bocchi - 01.mkv",
+                "--language",
+                "0:jpn",
+                "--track-name",
+                "0:Japanese",
+                "--default-track-flag",
+                "0:yes",
+                "raws/Bocchi - 01.srt",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ffmpeg_command() {
+        let video = Path::new("Bocchi - 01.mkv");
+        let command = ffmpeg_command(video, Path::new("Bocchi - 01.ass"), &muxing_path(video), 2);
+
+        assert_eq!(
+            args(&command)[14..],
+            [
+                "-metadata:s:s:2",
+                "language=jpn",
+                "-metadata:s:s:2",
+                "title=Japanese",
+                "-disposition:s",
+                "0",
+                "-disposition:s:2",
+                "default",
+                "Bocchi - 01.muxing.mkv",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_mkvmerge_subtitle_tracks() {
+        let identification = br#"{
+            "container": {"type": "Matroska"},
+            "tracks": [
+                {"id": 0, "type": "video", "codec": "AVC/H.264/MPEG-4p10"},
+                {"id": 1, "type": "audio", "codec": "AAC"},
+                {"id": 2, "type": "subtitles", "codec": "SubRip/SRT"},
+                {"id": 3, "type": "subtitles", "codec": "SubStationAlpha"}
+            ]
+        }"#;
+
+        assert_eq!(
+            parse_mkvmerge_subtitle_tracks(identification).unwrap(),
+            vec![2, 3]
+        );
+        assert!(parse_mkvmerge_subtitle_tracks(b"not json").is_err());
+    }
+
+    #[test]
+    fn test_mux_rejects_other_containers() {
+        let result = mux_subtitle(
+            Path::new("Bocchi - 01.mp4"),
+            Path::new("Bocchi - 01.srt"),
+            &MuxOptions {
+                mux_tool: MuxTool::Mkvmerge,
+                delete_sidecar: false,
+            },
+        );
+
+        assert!(result.is_err());
+    }
+}
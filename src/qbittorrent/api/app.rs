@@ -0,0 +1,139 @@
+use std::error::Error;
+
+use error_stack::{IntoReport, Report, Result, ResultExt};
+use reqwest::{header::HeaderMap, Method};
+
+use super::torrents::{create_request, get_body_string};
+use super::QBitTorrentApi;
+
+//--------------------- Types ---------------------
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct WebApiVersion {
+    major: u32,
+    minor: u32,
+    patch: u32,
+}
+
+impl WebApiVersion {
+    // qBittorrent 5.0 renamed pause/resume to stop/start, in the endpoints, add options and states
+    const STOP_START: WebApiVersion = WebApiVersion::new(2, 11, 0);
+
+    pub const fn new(major: u32, minor: u32, patch: u32) -> Self {
+        WebApiVersion {
+            major,
+            minor,
+            patch,
+        }
+    }
+
+    // Accepts "2.8" as well as "2.8.3"
+    pub fn parse(version: &str) -> Option<Self> {
+        let mut numbers = version
+            .trim()
+            .split('.')
+            .map(|number| number.parse::<u32>());
+
+        let major = numbers.next()?.ok()?;
+        let minor = numbers.next().unwrap_or(Ok(0)).ok()?;
+        let patch = numbers.next().unwrap_or(Ok(0)).ok()?;
+
+        Some(WebApiVersion::new(major, minor, patch))
+    }
+
+    pub fn has_stop_start(&self) -> bool {
+        *self >= Self::STOP_START
+    }
+}
+
+impl std::fmt::Display for WebApiVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+//--------------------- Functions ---------------------
+
+#[derive(Debug)]
+pub struct AppInfoError;
+
+impl std::fmt::Display for AppInfoError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Failed to retrieve qBittorrent information")
+    }
+}
+
+impl Error for AppInfoError {}
+
+//--------------------- API ---------------------
+
+impl QBitTorrentApi {
+    // Remembered after the first call, it can only change by restarting qBittorrent
+    pub async fn webapi_version(&self) -> Result<WebApiVersion, AppInfoError> {
+        if let Some(version) = *self.webapi_version.lock().unwrap() {
+            return Ok(version);
+        }
+
+        let api_url = format_endpoint!(self, "app/webapiVersion");
+
+        let req = create_request(
+            &self.http_client,
+            &api_url,
+            Method::GET,
+            HeaderMap::default(),
+            &[] as &[(&str, &str)],
+        );
+
+        let res = req
+            .send()
+            .await
+            .into_report()
+            .attach_printable("Failed to send request")
+            .change_context(AppInfoError)?;
+
+        if !res.status().is_success() {
+            return Err(Report::new(AppInfoError)
+                .attach_printable(format!("Response failed with status: {}", res.status())));
+        }
+
+        let body = get_body_string(res)
+            .await
+            .attach_printable("Failed to read response body")
+            .change_context(AppInfoError)?;
+
+        let version = WebApiVersion::parse(&body).ok_or_else(|| {
+            Report::new(AppInfoError).attach_printable(format!("Invalid version: {}", body))
+        })?;
+
+        log::debug!("qBittorrent WebUI API version: {}", version);
+        *self.webapi_version.lock().unwrap() = Some(version);
+
+        Ok(version)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_version() {
+        assert_eq!(
+            WebApiVersion::parse("2.11.2\n"),
+            Some(WebApiVersion::new(2, 11, 2))
+        );
+        assert_eq!(
+            WebApiVersion::parse("2.8"),
+            Some(WebApiVersion::new(2, 8, 0))
+        );
+        assert_eq!(WebApiVersion::parse("v2"), None);
+        assert_eq!(WebApiVersion::parse("2.x"), None);
+    }
+
+    #[test]
+    fn test_has_stop_start() {
+        assert!(!WebApiVersion::new(2, 9, 3).has_stop_start());
+        assert!(WebApiVersion::new(2, 11, 0).has_stop_start());
+        assert!(WebApiVersion::new(3, 0, 0).has_stop_start());
+    }
+}
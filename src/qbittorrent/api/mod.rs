@@ -1,12 +1,22 @@
+use std::sync::Mutex;
+
 use reqwest::Client;
 
+use app::WebApiVersion;
+
+//TODO: mover para um arquivo de configuração
+const API_URL: &str = "http://127.0.0.1:8080/api/v2/";
+
 pub struct QBitTorrentApi {
     http_client: Client,
+    api_url: String,
+    // Asked once on the first request that depends on it
+    webapi_version: Mutex<Option<WebApiVersion>>,
 }
 
 macro_rules! format_endpoint {
-    ($endpoint:expr) => {
-        format!("{}{}", API_URL, $endpoint)
+    ($api:expr, $endpoint:expr) => {
+        format!("{}{}", $api.api_url, $endpoint)
     };
 }
 
@@ -14,8 +24,21 @@ impl QBitTorrentApi {
     pub fn new() -> Self {
         QBitTorrentApi {
             http_client: Client::new(),
+            api_url: API_URL.to_owned(),
+            webapi_version: Mutex::new(None),
+        }
+    }
+
+    // Talks to the WebUI at another address, e.g. a mock server
+    #[cfg(test)]
+    pub fn with_url(api_url: &str) -> Self {
+        QBitTorrentApi {
+            api_url: api_url.to_owned(),
+            ..QBitTorrentApi::new()
         }
     }
 }
 
-pub mod torrents;
\ No newline at end of file
+pub mod app;
+pub mod sync;
+pub mod torrents;
@@ -0,0 +1,205 @@
+use std::collections::HashMap;
+use std::error::Error;
+
+use error_stack::{IntoReport, Report, Result, ResultExt};
+use reqwest::{header::HeaderMap, Method};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+use super::torrents::{create_request, get_body_json, Torrent, TorrentList};
+use super::QBitTorrentApi;
+
+//--------------------- Types ---------------------
+
+// One answer of sync/maindata. Unless it's a full update, torrents only
+// carry the fields that changed since the rid the request was made with
+#[derive(Deserialize, Debug)]
+pub struct MainData {
+    rid: i64,
+    #[serde(default)]
+    full_update: bool,
+    #[serde(default)]
+    torrents: HashMap<String, Map<String, Value>>,
+    #[serde(default)]
+    torrents_removed: Vec<String>,
+}
+
+// Local copy of qBittorrent's torrents, kept up to date by applying the deltas of sync/maindata.
+// Torrents stay as raw fields since a delta may change any subset of them. They are keyed by
+// their lowercase hash, so lookups don't depend on the case of the requested hashes
+#[derive(Debug, Default)]
+pub struct TorrentSyncState {
+    rid: i64,
+    torrents: HashMap<String, Map<String, Value>>,
+}
+
+impl TorrentSyncState {
+    // The rid of the last applied update, 0 asks for a full update
+    pub fn rid(&self) -> i64 {
+        self.rid
+    }
+
+    pub fn apply(&mut self, data: MainData) {
+        if data.full_update {
+            self.torrents.clear();
+        }
+
+        for (hash, fields) in data.torrents {
+            self.torrents
+                .entry(hash.to_lowercase())
+                .or_default()
+                .extend(fields);
+        }
+
+        for hash in &data.torrents_removed {
+            self.torrents.remove(&hash.to_lowercase());
+        }
+
+        self.rid = data.rid;
+    }
+
+    // Only the requested torrents are deserialized, the client may hold thousands of others
+    pub fn list(&self, hashes: &[String]) -> TorrentList {
+        let torrents = hashes
+            .iter()
+            .filter_map(|hash| {
+                let hash = hash.to_lowercase();
+                let fields = self.torrents.get(&hash)?;
+
+                // The hash is the key of the map, torrents don't repeat it in their fields
+                let mut fields = fields.clone();
+                fields.insert("hash".to_string(), Value::String(hash.clone()));
+
+                match serde_json::from_value::<Torrent>(Value::Object(fields)) {
+                    Ok(torrent) => Some(torrent),
+                    Err(e) => {
+                        log::debug!("Incomplete sync data for torrent {}: {}", hash, e);
+                        None
+                    }
+                }
+            })
+            .collect();
+
+        TorrentList(torrents)
+    }
+}
+
+//--------------------- Functions ---------------------
+
+#[derive(Debug)]
+pub struct SyncError;
+
+impl std::fmt::Display for SyncError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Failed to sync with qBittorrent")
+    }
+}
+
+impl Error for SyncError {}
+
+//--------------------- API ---------------------
+
+impl QBitTorrentApi {
+    pub async fn maindata(&self, rid: i64) -> Result<MainData, SyncError> {
+        let api_url = format_endpoint!(self, "sync/maindata");
+
+        let req = create_request(
+            &self.http_client,
+            &api_url,
+            Method::GET,
+            HeaderMap::default(),
+            &[] as &[(&str, &str)],
+        )
+        .query(&[("rid", rid)]);
+
+        let res = req
+            .send()
+            .await
+            .into_report()
+            .attach_printable("Failed to send request")
+            .change_context(SyncError)?;
+
+        if !res.status().is_success() {
+            return Err(Report::new(SyncError)
+                .attach_printable(format!("Response failed with status: {}", res.status())));
+        }
+
+        get_body_json(res)
+            .await
+            .attach_printable("Failed to read response body")
+            .change_context(SyncError)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::qbittorrent::api::torrents::TorrentState;
+
+    fn main_data(json: &str) -> MainData {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn test_apply_deltas() {
+        let mut state = TorrentSyncState::default();
+        let hashes = ["568807a73ecd33fff3ac19f47805f0940cbdb9ac".to_string()];
+
+        state.apply(main_data(
+            r#"{"rid": 1, "full_update": true, "torrents": {
+                "568807a73ecd33fff3ac19f47805f0940cbdb9ac": {"name": "One Piece", "size": 10, "state": "downloading", "downloaded": 2, "eta": 60},
+                "4936206e05d1bb04084f50032d6b3704f271eff7": {"name": "Bougyoryoku", "size": 10, "state": "stalledUP", "downloaded": 10, "eta": 0}
+            }}"#,
+        ));
+        assert_eq!(state.rid(), 1);
+        assert!(!state.list(&hashes).all_finished(&hashes));
+
+        state.apply(main_data(
+            r#"{"rid": 2, "torrents": {
+                "568807a73ecd33fff3ac19f47805f0940cbdb9ac": {"state": "uploading", "downloaded": 10}
+            }}"#,
+        ));
+        let torrents = state.list(&hashes);
+        let torrent = torrents.find(&hashes[0]).unwrap();
+        assert_eq!(*torrent.state(), TorrentState::Uploading);
+        assert_eq!(*torrent.downloaded(), 10);
+        assert_eq!(torrent.name(), "One Piece");
+        assert!(torrents.all_finished(&hashes));
+
+        state.apply(main_data(
+            r#"{"rid": 3, "torrents_removed": ["568807a73ecd33fff3ac19f47805f0940cbdb9ac"]}"#,
+        ));
+        assert!(state.list(&hashes).0.is_empty());
+    }
+
+    #[test]
+    fn test_list_ignores_hash_case() {
+        let mut state = TorrentSyncState::default();
+
+        state.apply(main_data(
+            r#"{"rid": 1, "full_update": true, "torrents": {
+                "568807A73ECD33FFF3AC19F47805F0940CBDB9AC": {"name": "One Piece", "size": 10, "state": "downloading"}
+            }}"#,
+        ));
+
+        let torrents = state.list(&["568807a73ecd33fff3ac19f47805f0940cbdb9ac".to_string()]);
+        assert_eq!(torrents.0.len(), 1);
+        assert_eq!(torrents.0[0].name(), "One Piece");
+    }
+
+    #[test]
+    fn test_full_update_replaces_state() {
+        let mut state = TorrentSyncState::default();
+        let hashes = ["4936206e05d1bb04084f50032d6b3704f271eff7".to_string()];
+
+        state.apply(main_data(
+            r#"{"rid": 5, "full_update": true, "torrents": {
+                "4936206e05d1bb04084f50032d6b3704f271eff7": {"name": "Bougyoryoku", "size": 10, "state": "stalledUP"}
+            }}"#,
+        ));
+        state.apply(main_data(r#"{"rid": 1, "full_update": true}"#));
+
+        assert_eq!(state.rid(), 1);
+        assert!(state.list(&hashes).0.is_empty());
+    }
+}
@@ -1,15 +1,17 @@
 use reqwest::{
     header::{HeaderMap, InvalidHeaderValue, CONTENT_TYPE},
-    Client, Method,
+    multipart, Client, Method,
 };
 
-use serde::{Deserialize, Serialize};
+use clap::ValueEnum;
 use derive_getters::Getters;
+use serde::{Deserialize, Serialize, Serializer};
 
 use error_stack::{IntoReport, Report, Result, ResultExt};
 use std::{collections::HashMap, error::Error};
 
-use super::{QBitTorrentApi};
+use super::app::WebApiVersion;
+use super::QBitTorrentApi;
 
 //--------------------- Types ---------------------
 
@@ -18,24 +20,336 @@ pub struct Torrent {
     hash: String,
     name: String,
     size: i64,
-    state: String,
+    state: TorrentState,
+    #[serde(default)]
     downloaded: i64,
+    #[serde(default)]
     eta: i64,
+    // From 0 to 1
+    #[serde(default)]
+    progress: f64,
+    // Bytes per second
+    #[serde(default)]
+    dlspeed: i64,
+    #[serde(default)]
+    num_seeds: i64,
+    #[serde(default)]
+    num_leechs: i64,
+    #[serde(default)]
+    save_path: String,
+}
+
+const ETA_UNKNOWN: i64 = 8_640_000;
+
+// Every state documented by the WebUI API, qBittorrent 5 renamed the paused ones to stopped
+#[derive(Deserialize, Serialize, Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TorrentState {
+    #[serde(rename = "error")]
+    Error,
+    #[serde(rename = "missingFiles")]
+    MissingFiles,
+    #[serde(rename = "uploading")]
+    Uploading,
+    #[serde(rename = "pausedUP")]
+    PausedUp,
+    #[serde(rename = "stoppedUP")]
+    StoppedUp,
+    #[serde(rename = "queuedUP")]
+    QueuedUp,
+    #[serde(rename = "stalledUP")]
+    StalledUp,
+    #[serde(rename = "checkingUP")]
+    CheckingUp,
+    #[serde(rename = "forcedUP")]
+    ForcedUp,
+    #[serde(rename = "allocating")]
+    Allocating,
+    #[serde(rename = "downloading")]
+    Downloading,
+    #[serde(rename = "metaDL")]
+    MetaDl,
+    #[serde(rename = "forcedMetaDL")]
+    ForcedMetaDl,
+    #[serde(rename = "pausedDL")]
+    PausedDl,
+    #[serde(rename = "stoppedDL")]
+    StoppedDl,
+    #[serde(rename = "queuedDL")]
+    QueuedDl,
+    #[serde(rename = "stalledDL")]
+    StalledDl,
+    #[serde(rename = "checkingDL")]
+    CheckingDl,
+    #[serde(rename = "forcedDL")]
+    ForcedDl,
+    #[serde(rename = "checkingResumeData")]
+    CheckingResumeData,
+    #[serde(rename = "moving")]
+    Moving,
+    #[serde(rename = "unknown", other)]
+    Unknown,
+}
+
+impl TorrentState {
+    // A completed torrent being rechecked may turn out incomplete, so checkingUP isn't finished yet
+    pub fn finished(&self) -> bool {
+        matches!(
+            self,
+            TorrentState::Uploading
+                | TorrentState::PausedUp
+                | TorrentState::StoppedUp
+                | TorrentState::QueuedUp
+                | TorrentState::StalledUp
+                | TorrentState::ForcedUp
+        )
+    }
+
+    // Force started torrents report forcedMetaDL instead of metaDL
+    pub fn is_fetching_metadata(&self) -> bool {
+        matches!(self, TorrentState::MetaDl | TorrentState::ForcedMetaDl)
+    }
 }
 
 impl Torrent {
+    // Other backends map their own states onto qBittorrent's
+    pub fn new(
+        hash: &str,
+        name: &str,
+        size: i64,
+        state: TorrentState,
+        downloaded: i64,
+        eta: i64,
+    ) -> Self {
+        Torrent {
+            hash: hash.to_owned(),
+            name: name.to_owned(),
+            size,
+            state,
+            downloaded,
+            eta,
+            progress: 0.0,
+            dlspeed: 0,
+            num_seeds: 0,
+            num_leechs: 0,
+            save_path: String::new(),
+        }
+    }
+
+    pub fn with_transfer(
+        self,
+        progress: f64,
+        dlspeed: i64,
+        num_seeds: i64,
+        num_leechs: i64,
+    ) -> Self {
+        Torrent {
+            progress,
+            dlspeed,
+            num_seeds,
+            num_leechs,
+            ..self
+        }
+    }
+
+    pub fn with_save_path(self, save_path: &str) -> Self {
+        Torrent {
+            save_path: save_path.to_owned(),
+            ..self
+        }
+    }
+
+    // qBittorrent uses 8640000 (100 days) when the ETA is unknown
+    pub fn eta_known(&self) -> bool {
+        (0..ETA_UNKNOWN).contains(&self.eta)
+    }
+
     pub fn finished(&self) -> bool {
-        self.state.to_uppercase().contains("UP")
+        self.state.finished()
+    }
+
+    // "downloaded" also counts wasted and redundant data, so it can go past the size
+    pub fn completed_bytes(&self) -> u64 {
+        (self.progress.clamp(0.0, 1.0) * self.size as f64) as u64
     }
 }
 
 #[derive(Deserialize, Serialize, Debug)]
 pub struct TorrentList(pub Vec<Torrent>);
 
-//--------------------- Functions ---------------------
+// The position of a file in the list is the id used by torrents/filePrio
+#[derive(Deserialize, Serialize, Debug, Getters)]
+pub struct TorrentFile {
+    name: String,
+    size: i64,
+    progress: f64,
+    priority: i64,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct TorrentFileList(pub Vec<TorrentFile>);
+
+// Filters of torrents/info, unset ones match every torrent
+#[derive(Serialize, Debug, Default, Clone)]
+pub struct TorrentInfoFilter {
+    #[serde(
+        skip_serializing_if = "Vec::is_empty",
+        serialize_with = "serialize_pipe_separated"
+    )]
+    pub hashes: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub category: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+}
+
+impl TorrentInfoFilter {
+    pub fn for_hashes(hashes: &[String]) -> Self {
+        TorrentInfoFilter {
+            hashes: hashes.to_vec(),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum QueuePriority {
+    Increase,
+    Decrease,
+    Top,
+    Bottom,
+}
+
+impl QueuePriority {
+    fn endpoint(&self) -> &'static str {
+        match self {
+            QueuePriority::Increase => "torrents/increasePrio",
+            QueuePriority::Decrease => "torrents/decreasePrio",
+            QueuePriority::Top => "torrents/topPrio",
+            QueuePriority::Bottom => "torrents/bottomPrio",
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum FilePriority {
+    DoNotDownload = 0,
+    Normal = 1,
+    High = 6,
+    Maximal = 7,
+}
+
+// Optional parameters of torrents/add, unset ones are left to the client defaults
+#[derive(Serialize, Debug, Default, Clone)]
+pub struct TorrentAddOptions {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub savepath: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub category: Option<String>,
+    #[serde(
+        skip_serializing_if = "Vec::is_empty",
+        serialize_with = "serialize_comma_separated"
+    )]
+    pub tags: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rename: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub paused: Option<bool>,
+    #[serde(rename = "sequentialDownload", skip_serializing_if = "Option::is_none")]
+    pub sequential_download: Option<bool>,
+    #[serde(rename = "firstLastPiecePrio", skip_serializing_if = "Option::is_none")]
+    pub first_last_piece_prio: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub skip_checking: Option<bool>,
+    #[serde(rename = "stopCondition", skip_serializing_if = "Option::is_none")]
+    pub stop_condition: Option<StopCondition>,
+}
+
+// When qBittorrent should stop a torrent on its own, available since qBittorrent 4.5
+#[allow(dead_code)]
+#[derive(Serialize, Debug, Copy, Clone, PartialEq, Eq)]
+pub enum StopCondition {
+    None,
+    MetadataReceived,
+    FilesChecked,
+}
+
+impl TorrentAddOptions {
+    // Tagging with the series makes everything we enqueued easy to filter in the WebUI
+    pub fn for_series(series_name: &str) -> Self {
+        TorrentAddOptions {
+            tags: vec![series_name.to_owned()],
+            ..Default::default()
+        }
+    }
+}
+
+// A comma inside a value would split it in two, e.g. a series named "Love, Chunibyo"
+fn serialize_comma_separated<S>(
+    values: &[String],
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    let values: Vec<String> = values.iter().map(|value| value.replace(',', "")).collect();
+    serializer.serialize_str(&values.join(","))
+}
+
+fn serialize_pipe_separated<S>(
+    values: &[String],
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.serialize_str(&values.join("|"))
+}
+
+#[derive(Serialize, Debug)]
+struct TorrentAddForm<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    urls: Option<&'a str>,
+    // qBittorrent 5 reads "stopped" where older versions read "paused"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stopped: Option<bool>,
+    #[serde(flatten)]
+    options: TorrentAddOptions,
+}
+
+impl<'a> TorrentAddForm<'a> {
+    fn new(urls: Option<&'a str>, options: &TorrentAddOptions, version: WebApiVersion) -> Self {
+        let mut options = options.clone();
+        let stopped = match version.has_stop_start() {
+            true => options.paused.take(),
+            false => None,
+        };
+
+        TorrentAddForm {
+            urls,
+            stopped,
+            options,
+        }
+    }
+}
+
+impl TorrentList {
+    pub fn find(&self, hash: &str) -> Option<&Torrent> {
+        self.0.iter().find(|t| t.hash.eq_ignore_ascii_case(hash))
+    }
+
+    // A torrent missing from the list may not have been registered by the client yet
+    pub fn all_finished(&self, hashes: &[String]) -> bool {
+        hashes.iter().all(|hash| match self.find(hash) {
+            Some(torrent) => torrent.finished(),
+            None => {
+                log::debug!("Torrent not listed by the client yet: {}", hash);
+                false
+            }
+        })
+    }
+}
 
-//TODO: mover para um arquivo de configuração
-const API_URL: &str = "http://127.0.0.1:8080/api/v2/";
+//--------------------- Functions ---------------------
 
 #[derive(Debug)]
 pub struct TorrentAddError;
@@ -59,6 +373,17 @@ impl std::fmt::Display for TorrentInfoError {
 
 impl Error for TorrentInfoError {}
 
+#[derive(Debug)]
+pub struct TorrentCommandError;
+
+impl std::fmt::Display for TorrentCommandError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Failed to run torrent command")
+    }
+}
+
+impl Error for TorrentCommandError {}
+
 //--------------------- API ---------------------
 
 fn create_form_content_type() -> Result<reqwest::header::HeaderValue, InvalidHeaderValue> {
@@ -80,12 +405,12 @@ fn create_defaulted_headers() -> Result<HeaderMap, InvalidHeaderValue> {
     Ok(headers)
 }
 
-fn create_request(
+pub(super) fn create_request(
     http_client: &Client,
     api_url: &str,
     method: Method,
     headers: HeaderMap,
-    params: &std::collections::HashMap<&str, &str>,
+    params: &(impl Serialize + ?Sized),
 ) -> reqwest::RequestBuilder {
     http_client
         .request(method, api_url)
@@ -94,7 +419,35 @@ fn create_request(
         .basic_auth("username", Some("password"))
 }
 
-async fn get_body_string(res: reqwest::Response) -> Result<String, reqwest::Error> {
+// Multipart requests set their own content type with the boundary, so no default headers
+fn create_multipart_request(
+    http_client: &Client,
+    api_url: &str,
+    form: multipart::Form,
+) -> reqwest::RequestBuilder {
+    http_client
+        .post(api_url)
+        .multipart(form)
+        .basic_auth("username", Some("password"))
+}
+
+// The same fields the url encoded form would have, sent as multipart text parts
+fn add_form_fields(form: &TorrentAddForm) -> Result<Vec<(String, String)>, serde_json::Error> {
+    let fields = match serde_json::to_value(form).into_report()? {
+        serde_json::Value::Object(fields) => fields,
+        _ => unreachable!("TorrentAddForm always serializes to an object"),
+    };
+
+    Ok(fields
+        .into_iter()
+        .map(|(name, value)| match value {
+            serde_json::Value::String(value) => (name, value),
+            value => (name, value.to_string()),
+        })
+        .collect())
+}
+
+pub(super) async fn get_body_string(res: reqwest::Response) -> Result<String, reqwest::Error> {
     let status = res.status();
     let content_len = res.content_length();
     let body = res.text().await.into_report().attach_printable_lazy(|| {
@@ -107,7 +460,7 @@ async fn get_body_string(res: reqwest::Response) -> Result<String, reqwest::Erro
     Ok(body)
 }
 
-async fn get_body_json<T>(res: reqwest::Response) -> Result<T, reqwest::Error>
+pub(super) async fn get_body_json<T>(res: reqwest::Response) -> Result<T, reqwest::Error>
 where
     T: for<'de> Deserialize<'de>,
 {
@@ -123,27 +476,39 @@ where
     Ok(body)
 }
 
+// Refused torrents are still answered with 200, only the body tells
+fn check_add_response(body: String) -> Result<String, TorrentAddError> {
+    if body.trim() == "Fails." {
+        return Err(
+            Report::new(TorrentAddError).attach_printable("qBittorrent refused the torrent")
+        );
+    }
+
+    Ok(body)
+}
+
 impl QBitTorrentApi {
     //TODO: múltiplos torrents
-    pub async fn add(&self, uri: &str) -> Result<String, TorrentAddError> {
+    pub async fn add(
+        &self,
+        uri: &str,
+        options: &TorrentAddOptions,
+    ) -> Result<String, TorrentAddError> {
         // Monta a URL da API do QBitTorrent para adicionar um novo torrent
-        let api_url = format_endpoint!("torrents/add");
+        let api_url = format_endpoint!(self, "torrents/add");
 
         // Define os parâmetros da solicitação POST
         let headers = create_defaulted_headers()
             .attach_printable("Failed creating default headers")
             .change_context(TorrentAddError)?;
 
-        //TODO: Criar um tipo para os parâmetros
-        let params_map = std::collections::HashMap::from([("urls", uri), ("savepath", ".")]);
+        let version = self
+            .webapi_version()
+            .await
+            .change_context(TorrentAddError)?;
+        let form = TorrentAddForm::new(Some(uri), options, version);
 
-        let req = create_request(
-            &self.http_client,
-            &api_url,
-            Method::POST,
-            headers,
-            &params_map,
-        );
+        let req = create_request(&self.http_client, &api_url, Method::POST, headers, &form);
 
         // Envia a solicitação POST para a API do QBitTorrent
         let res = req
@@ -164,20 +529,72 @@ impl QBitTorrentApi {
             .attach_printable("Failed to read response body")
             .change_context(TorrentAddError)?;
 
-        Ok(body)
+        check_add_response(body)
     }
 
-    pub async fn info(&self) -> Result<TorrentList, TorrentInfoError> {
-        // Monta a URL da API do QBitTorrent para adicionar um novo torrent
-        let api_url = format_endpoint!("torrents/info");
+    // Uploads the .torrent file itself, so the metadata doesn't have to come from DHT or trackers
+    pub async fn add_file(
+        &self,
+        file_name: &str,
+        torrent_file: Vec<u8>,
+        options: &TorrentAddOptions,
+    ) -> Result<String, TorrentAddError> {
+        let api_url = format_endpoint!(self, "torrents/add");
+
+        let torrent_part = multipart::Part::bytes(torrent_file)
+            .file_name(file_name.to_owned())
+            .mime_str("application/x-bittorrent")
+            .into_report()
+            .attach_printable("Failed to create torrent file part")
+            .change_context(TorrentAddError)?;
+
+        let version = self
+            .webapi_version()
+            .await
+            .change_context(TorrentAddError)?;
+
+        let form = add_form_fields(&TorrentAddForm::new(None, options, version))
+            .attach_printable("Failed to serialize torrent options")
+            .change_context(TorrentAddError)?
+            .into_iter()
+            .fold(multipart::Form::new(), |form, (name, value)| {
+                form.text(name, value)
+            })
+            .part("torrents", torrent_part);
+
+        let res = create_multipart_request(&self.http_client, &api_url, form)
+            .send()
+            .await
+            .into_report()
+            .attach_printable("Failed to send request")
+            .change_context(TorrentAddError)?;
+
+        if !res.status().is_success() {
+            return Err(Report::new(TorrentAddError)
+                .attach_printable(format!("Response failed with status: {}", res.status())));
+        }
+
+        let body = get_body_string(res)
+            .await
+            .attach_printable("Failed to read response body")
+            .change_context(TorrentAddError)?;
+
+        check_add_response(body)
+            .attach_printable_lazy(|| format!("Rejected torrent file: {}", file_name))
+    }
+
+    pub async fn info(&self, filter: &TorrentInfoFilter) -> Result<TorrentList, TorrentInfoError> {
+        // Monta a URL da API do QBitTorrent para listar os torrents
+        let api_url = format_endpoint!(self, "torrents/info");
 
         let req = create_request(
             &self.http_client,
             &api_url,
             Method::GET,
             HeaderMap::default(),
-            &HashMap::default(),
-        );
+            &HashMap::<&str, &str>::default(),
+        )
+        .query(filter);
 
         // Envia a solicitação POST para a API do QBitTorrent
         let res = req
@@ -200,19 +617,466 @@ impl QBitTorrentApi {
 
         Ok(body)
     }
+
+    // Empty until the metadata of the torrent is known
+    pub async fn files(&self, hash: &str) -> Result<TorrentFileList, TorrentInfoError> {
+        let api_url = format_endpoint!(self, "torrents/files");
+
+        let req = create_request(
+            &self.http_client,
+            &api_url,
+            Method::GET,
+            HeaderMap::default(),
+            &HashMap::<&str, &str>::default(),
+        )
+        .query(&[("hash", hash)]);
+
+        let res = req
+            .send()
+            .await
+            .into_report()
+            .attach_printable("Failed to send request")
+            .change_context(TorrentInfoError)?;
+
+        if !res.status().is_success() {
+            return Err(Report::new(TorrentInfoError)
+                .attach_printable(format!("Response failed with status: {}", res.status())));
+        }
+
+        let body: TorrentFileList = get_body_json(res)
+            .await
+            .attach_printable("Failed to read response body")
+            .change_context(TorrentInfoError)?;
+
+        Ok(body)
+    }
+
+    // Called stop since qBittorrent 5
+    pub async fn pause(&self, hashes: &[String]) -> Result<(), TorrentCommandError> {
+        let endpoint = match self.has_stop_start().await? {
+            true => "torrents/stop",
+            false => "torrents/pause",
+        };
+        self.send_command(endpoint, &[("hashes", hashes.join("|"))])
+            .await
+    }
+
+    // Called start since qBittorrent 5
+    pub async fn resume(&self, hashes: &[String]) -> Result<(), TorrentCommandError> {
+        let endpoint = match self.has_stop_start().await? {
+            true => "torrents/start",
+            false => "torrents/resume",
+        };
+        self.send_command(endpoint, &[("hashes", hashes.join("|"))])
+            .await
+    }
+
+    pub async fn delete(
+        &self,
+        hashes: &[String],
+        delete_files: bool,
+    ) -> Result<(), TorrentCommandError> {
+        let params = [
+            ("hashes", hashes.join("|")),
+            ("deleteFiles", delete_files.to_string()),
+        ];
+        self.send_command("torrents/delete", &params).await
+    }
+
+    pub async fn recheck(&self, hashes: &[String]) -> Result<(), TorrentCommandError> {
+        self.send_command("torrents/recheck", &[("hashes", hashes.join("|"))])
+            .await
+    }
+
+    pub async fn reannounce(&self, hashes: &[String]) -> Result<(), TorrentCommandError> {
+        self.send_command("torrents/reannounce", &[("hashes", hashes.join("|"))])
+            .await
+    }
+
+    // Only works when torrent queueing is enabled in qBittorrent
+    pub async fn set_queue_priority(
+        &self,
+        hashes: &[String],
+        priority: QueuePriority,
+    ) -> Result<(), TorrentCommandError> {
+        self.send_command(priority.endpoint(), &[("hashes", hashes.join("|"))])
+            .await
+    }
+
+    pub async fn set_location(
+        &self,
+        hashes: &[String],
+        location: &str,
+    ) -> Result<(), TorrentCommandError> {
+        let params = [
+            ("hashes", hashes.join("|")),
+            ("location", location.to_owned()),
+        ];
+        self.send_command("torrents/setLocation", &params).await
+    }
+
+    // Limits are in bytes per second, 0 means unlimited
+    pub async fn set_download_limit(
+        &self,
+        hashes: &[String],
+        limit: u64,
+    ) -> Result<(), TorrentCommandError> {
+        let params = [("hashes", hashes.join("|")), ("limit", limit.to_string())];
+        self.send_command("torrents/setDownloadLimit", &params)
+            .await
+    }
+
+    pub async fn set_upload_limit(
+        &self,
+        hashes: &[String],
+        limit: u64,
+    ) -> Result<(), TorrentCommandError> {
+        let params = [("hashes", hashes.join("|")), ("limit", limit.to_string())];
+        self.send_command("torrents/setUploadLimit", &params).await
+    }
+
+    // File ids are the indexes of the files inside the torrent
+    pub async fn set_file_priority(
+        &self,
+        hash: &str,
+        file_ids: &[usize],
+        priority: FilePriority,
+    ) -> Result<(), TorrentCommandError> {
+        let file_ids = file_ids
+            .iter()
+            .map(|id| id.to_string())
+            .collect::<Vec<_>>()
+            .join("|");
+        let params = [
+            ("hash", hash.to_owned()),
+            ("id", file_ids),
+            ("priority", (priority as i32).to_string()),
+        ];
+        self.send_command("torrents/filePrio", &params).await
+    }
+
+    async fn has_stop_start(&self) -> Result<bool, TorrentCommandError> {
+        let version = self
+            .webapi_version()
+            .await
+            .change_context(TorrentCommandError)?;
+
+        Ok(version.has_stop_start())
+    }
+
+    // Commands answer with an empty body, so only the status matters
+    async fn send_command(
+        &self,
+        endpoint: &str,
+        params: &[(&str, String)],
+    ) -> Result<(), TorrentCommandError> {
+        let api_url = format_endpoint!(self, endpoint);
+
+        let headers = create_defaulted_headers()
+            .attach_printable("Failed creating default headers")
+            .change_context(TorrentCommandError)?;
+
+        let req = create_request(&self.http_client, &api_url, Method::POST, headers, params);
+
+        let res = req
+            .send()
+            .await
+            .into_report()
+            .attach_printable_lazy(|| format!("Failed to send request to: {}", endpoint))
+            .change_context(TorrentCommandError)?;
+
+        if !res.status().is_success() {
+            return Err(Report::new(TorrentCommandError).attach_printable(format!(
+                "Response from {} failed with status: {}",
+                endpoint,
+                res.status()
+            )));
+        }
+
+        Ok(())
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use mockito::{Matcher, Mock};
     use tokio::runtime::Runtime;
 
+    fn torrent_list() -> TorrentList {
+        serde_json::from_str(
+            r#"[
+                {"hash": "568807a73ecd33fff3ac19f47805f0940cbdb9ac", "name": "One Piece", "size": 10, "state": "stalledUP", "downloaded": 10, "eta": 0},
+                {"hash": "4936206e05d1bb04084f50032d6b3704f271eff7", "name": "Bougyoryoku", "size": 10, "state": "downloading", "downloaded": 2, "eta": 60}
+            ]"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_all_finished_ignores_other_torrents() {
+        let torrents = torrent_list();
+        assert!(torrents.all_finished(&["568807A73ECD33FFF3AC19F47805F0940CBDB9AC".to_string()]));
+        assert!(!torrents.all_finished(&["4936206e05d1bb04084f50032d6b3704f271eff7".to_string()]));
+    }
+
+    #[test]
+    fn test_torrent_states_across_versions() {
+        let states: Vec<TorrentState> = serde_json::from_str(
+            r#"["pausedUP", "stoppedUP", "checkingUP", "stoppedDL", "someFutureState"]"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            states,
+            vec![
+                TorrentState::PausedUp,
+                TorrentState::StoppedUp,
+                TorrentState::CheckingUp,
+                TorrentState::StoppedDl,
+                TorrentState::Unknown,
+            ]
+        );
+        assert!(states[0].finished());
+        assert!(states[1].finished());
+        assert!(!states[2].finished());
+        assert!(!states[3].finished());
+    }
+
+    #[test]
+    fn test_fetching_metadata_states() {
+        assert!(TorrentState::MetaDl.is_fetching_metadata());
+        assert!(TorrentState::ForcedMetaDl.is_fetching_metadata());
+        assert!(!TorrentState::Downloading.is_fetching_metadata());
+        assert!(!TorrentState::StalledDl.is_fetching_metadata());
+    }
+
+    #[test]
+    fn test_check_add_response() {
+        assert!(check_add_response("Ok.".to_string()).is_ok());
+        assert!(check_add_response("Fails.".to_string()).is_err());
+    }
+
+    #[test]
+    fn test_all_finished_waits_for_missing_torrents() {
+        let torrents = torrent_list();
+        assert!(!torrents.all_finished(&[
+            "568807a73ecd33fff3ac19f47805f0940cbdb9ac".to_string(),
+            "0000000000000000000000000000000000000000".to_string(),
+        ]));
+    }
+
+    #[test]
+    fn test_add_form_fields() {
+        let options = TorrentAddOptions {
+            paused: Some(true),
+            stop_condition: Some(StopCondition::MetadataReceived),
+            ..TorrentAddOptions::for_series("One Piece")
+        };
+
+        let form = TorrentAddForm::new(None, &options, WebApiVersion::new(2, 9, 3));
+        let fields = add_form_fields(&form).unwrap();
+        assert!(fields.contains(&("tags".to_string(), "One Piece".to_string())));
+        assert!(fields.contains(&("paused".to_string(), "true".to_string())));
+        assert!(fields.contains(&("stopCondition".to_string(), "MetadataReceived".to_string())));
+        assert!(!fields
+            .iter()
+            .any(|(name, _)| name == "savepath" || name == "urls"));
+    }
+
+    #[test]
+    fn test_add_form_uses_stopped_since_qbittorrent_5() {
+        let options = TorrentAddOptions {
+            paused: Some(true),
+            ..TorrentAddOptions::default()
+        };
+
+        let form = TorrentAddForm::new(None, &options, WebApiVersion::new(2, 11, 2));
+        let fields = add_form_fields(&form).unwrap();
+        assert!(fields.contains(&("stopped".to_string(), "true".to_string())));
+        assert!(!fields.iter().any(|(name, _)| name == "paused"));
+    }
+
+    #[test]
+    fn test_add_form_strips_commas_from_tags() {
+        let mut options = TorrentAddOptions::for_series("Love, Chunibyo & Other Delusions");
+        options.tags.push("raws".to_string());
+
+        let form = TorrentAddForm::new(None, &options, WebApiVersion::new(2, 9, 3));
+        let fields = add_form_fields(&form).unwrap();
+        assert!(fields.contains(&(
+            "tags".to_string(),
+            "Love Chunibyo & Other Delusions,raws".to_string()
+        )));
+    }
+
+    #[test]
+    fn test_add_form_serialization() {
+        let options = TorrentAddOptions {
+            savepath: Some(".".to_string()),
+            category: Some("anime".to_string()),
+            sequential_download: Some(true),
+            ..TorrentAddOptions::for_series("One Piece")
+        };
+        let form = TorrentAddForm::new(
+            Some("magnet:?xt=urn:btih:568807a73ecd33fff3ac19f47805f0940cbdb9ac"),
+            &options,
+            WebApiVersion::new(2, 9, 3),
+        );
+
+        let request = Client::new()
+            .post("http://127.0.0.1/")
+            .form(&form)
+            .build()
+            .unwrap();
+        let body = std::str::from_utf8(request.body().unwrap().as_bytes().unwrap()).unwrap();
+
+        assert_eq!(
+            body,
+            "urls=magnet%3A%3Fxt%3Durn%3Abtih%3A568807a73ecd33fff3ac19f47805f0940cbdb9ac&savepath=.&category=anime&tags=One+Piece&sequentialDownload=true"
+        );
+    }
+
+    #[test]
+    fn test_info_filter_serialization() {
+        let filter = TorrentInfoFilter {
+            tag: Some("One Piece".to_string()),
+            ..TorrentInfoFilter::for_hashes(&[
+                "568807a73ecd33fff3ac19f47805f0940cbdb9ac".to_string(),
+                "4936206e05d1bb04084f50032d6b3704f271eff7".to_string(),
+            ])
+        };
+
+        let request = Client::new()
+            .get("http://127.0.0.1/")
+            .query(&filter)
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            request.url().query(),
+            Some("hashes=568807a73ecd33fff3ac19f47805f0940cbdb9ac%7C4936206e05d1bb04084f50032d6b3704f271eff7&tag=One+Piece")
+        );
+    }
+
+    fn mock_api(server: &mut mockito::Server, webapi_version: &str) -> (QBitTorrentApi, Mock) {
+        let version_mock = server
+            .mock("GET", "/api/v2/app/webapiVersion")
+            .with_body(webapi_version)
+            .create();
+        let api = QBitTorrentApi::with_url(&format!("{}/api/v2/", server.url()));
+        (api, version_mock)
+    }
+
+    fn hashes() -> Vec<String> {
+        vec![
+            "568807a73ecd33fff3ac19f47805f0940cbdb9ac".to_string(),
+            "4936206e05d1bb04084f50032d6b3704f271eff7".to_string(),
+        ]
+    }
+
+    const HASHES_PARAM: &str =
+        "568807a73ecd33fff3ac19f47805f0940cbdb9ac|4936206e05d1bb04084f50032d6b3704f271eff7";
+
+    #[test]
+    fn test_pause_and_resume() {
+        let mut server = mockito::Server::new();
+        let (api, _version_mock) = mock_api(&mut server, "2.9.3");
+        let pause_mock = server
+            .mock("POST", "/api/v2/torrents/pause")
+            .match_body(Matcher::UrlEncoded("hashes".into(), HASHES_PARAM.into()))
+            .create();
+        let resume_mock = server
+            .mock("POST", "/api/v2/torrents/resume")
+            .match_body(Matcher::UrlEncoded("hashes".into(), HASHES_PARAM.into()))
+            .create();
+
+        let runtime = Runtime::new().unwrap();
+        runtime.block_on(api.pause(&hashes())).unwrap();
+        runtime.block_on(api.resume(&hashes())).unwrap();
+
+        pause_mock.assert();
+        resume_mock.assert();
+    }
+
+    #[test]
+    fn test_pause_and_resume_since_qbittorrent_5() {
+        let mut server = mockito::Server::new();
+        let (api, _version_mock) = mock_api(&mut server, "2.11.2");
+        let stop_mock = server.mock("POST", "/api/v2/torrents/stop").create();
+        let start_mock = server.mock("POST", "/api/v2/torrents/start").create();
+
+        let runtime = Runtime::new().unwrap();
+        runtime.block_on(api.pause(&hashes())).unwrap();
+        runtime.block_on(api.resume(&hashes())).unwrap();
+
+        stop_mock.assert();
+        start_mock.assert();
+    }
+
+    #[test]
+    fn test_set_queue_priority() {
+        let mut server = mockito::Server::new();
+        let (api, _version_mock) = mock_api(&mut server, "2.9.3");
+        let top_mock = server
+            .mock("POST", "/api/v2/torrents/topPrio")
+            .match_body(Matcher::UrlEncoded("hashes".into(), HASHES_PARAM.into()))
+            .create();
+        let decrease_mock = server
+            .mock("POST", "/api/v2/torrents/decreasePrio")
+            .create();
+
+        let runtime = Runtime::new().unwrap();
+        runtime
+            .block_on(api.set_queue_priority(&hashes(), QueuePriority::Top))
+            .unwrap();
+        runtime
+            .block_on(api.set_queue_priority(&hashes(), QueuePriority::Decrease))
+            .unwrap();
+
+        top_mock.assert();
+        decrease_mock.assert();
+    }
+
+    #[test]
+    fn test_delete() {
+        let mut server = mockito::Server::new();
+        let (api, _version_mock) = mock_api(&mut server, "2.9.3");
+        let delete_mock = server
+            .mock("POST", "/api/v2/torrents/delete")
+            .match_body(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("hashes".into(), HASHES_PARAM.into()),
+                Matcher::UrlEncoded("deleteFiles".into(), "true".into()),
+            ]))
+            .create();
+
+        let runtime = Runtime::new().unwrap();
+        runtime.block_on(api.delete(&hashes(), true)).unwrap();
+
+        delete_mock.assert();
+    }
+
+    #[test]
+    fn test_command_failure() {
+        let mut server = mockito::Server::new();
+        let (api, _version_mock) = mock_api(&mut server, "2.9.3");
+        let _delete_mock = server
+            .mock("POST", "/api/v2/torrents/delete")
+            .with_status(403)
+            .create();
+
+        let runtime = Runtime::new().unwrap();
+        assert!(runtime.block_on(api.delete(&hashes(), false)).is_err());
+    }
+
     #[test]
     fn test_add_torrent_ok() {
         let api = QBitTorrentApi::new();
         let runtime = Runtime::new().unwrap();
         let uri = "magnet:?xt=urn:btih:4936206e05d1bb04084f50032d6b3704f271eff7&dn=%5BOhys-Raws%5D%20Bougyoryoku%202%20-%2006%20%28AT-X%201280x720%20x264%20AAC%29.mp4&tr=http%3A%2F%2Fnyaa.tracker.wf%3A7777%2Fannounce&tr=udp%3A%2F%2Fopen.stealth.si%3A80%2Fannounce&tr=udp%3A%2F%2Ftracker.opentrackr.org%3A1337%2Fannounce&tr=udp%3A%2F%2Fexodus.desync.com%3A6969%2Fannounce&tr=udp%3A%2F%2Ftracker.torrent.eu.org%3A451%2Fannounce";
-        let body = runtime.block_on(api.add(uri)).unwrap();
+        let body = runtime
+            .block_on(api.add(uri, &TorrentAddOptions::default()))
+            .unwrap();
         assert_eq!(body, "Ok.");
     }
 
@@ -221,15 +1085,17 @@ mod tests {
         let api = QBitTorrentApi::new();
         let runtime = Runtime::new().unwrap();
         let uri = "__BROKEN__MAGNET__LINK__";
-        let body = runtime.block_on(api.add(uri)).unwrap();
-        assert_eq!(body, "Fails.");
+        let result = runtime.block_on(api.add(uri, &TorrentAddOptions::default()));
+        assert!(result.is_err());
     }
 
     #[test]
     fn test_info() {
         let api = QBitTorrentApi::new();
         let runtime = Runtime::new().unwrap();
-        let body = runtime.block_on(api.info()).unwrap();
+        let body = runtime
+            .block_on(api.info(&TorrentInfoFilter::default()))
+            .unwrap();
         dbg!(&body);
     }
 }
@@ -1,16 +1,24 @@
 use std::error::Error;
+use std::path::PathBuf;
 
 use crate::core::download::downloader::Destination;
-use crate::core::indexer::Indexer;
+use crate::core::magnet::MagnetLink;
+use crate::torrent::{self, TorrentClient, TorrentClientError, WaitOptions, WaitSummary};
 use tokio::runtime::Runtime;
 
 use super::api;
-use super::api::torrents::TorrentList;
+use super::api::sync::TorrentSyncState;
+use super::api::torrents::{
+    FilePriority, QueuePriority, TorrentAddOptions, TorrentInfoFilter, TorrentList,
+};
 
-use error_stack::{Result, ResultExt};
+use error_stack::{Report, Result, ResultExt};
 
 pub struct QBitTorrentClient {
     api: api::QBitTorrentApi,
+    download_root: Option<PathBuf>,
+    // Runs the async API for the blocking TorrentClient methods
+    runtime: Runtime,
 }
 
 #[derive(Debug)]
@@ -25,51 +33,327 @@ impl std::fmt::Display for QBitTorrentClientError {
 impl Error for QBitTorrentClientError {}
 
 impl QBitTorrentClient {
-    pub fn new() -> Self {
+    // Relative save paths are resolved against download_root, which should be
+    // a folder as seen by qBittorrent (it may run on another machine or container)
+    pub fn new(download_root: Option<PathBuf>) -> Self {
         QBitTorrentClient {
             api: api::QBitTorrentApi::new(),
+            download_root,
+            runtime: Runtime::new().unwrap(),
         }
     }
 
-    pub fn wait_for_completion<'a>(&self, on_update: impl Fn(&TorrentList) + Send + Sync + 'a) -> Result<(), QBitTorrentClientError>{
-        let has_unfinished_torrents =
-            |torrents: TorrentList| torrents.0.iter().any(|t| !t.finished());
+    // Returns the info-hash of the added torrent, which is how it can be tracked afterwards
+    pub async fn add_magnet(
+        &self,
+        magnet: MagnetLink,
+        dest: Destination,
+        options: TorrentAddOptions,
+    ) -> Result<String, QBitTorrentClientError> {
+        let options = self.apply_destination(&dest, options);
 
-        let rt = Runtime::new().unwrap();
+        let uri = magnet.to_string();
+        self.api
+            .add(uri.as_str(), &options)
+            .await
+            .attach_printable_lazy(|| format!("Failed to add torrent with uri: {}", uri))
+            .change_context(QBitTorrentClientError)?;
 
-        rt.block_on(async move {
-            loop {
-                log::debug!("Waiting for torrents to finish");
+        Ok(magnet.info_hash().to_owned())
+    }
 
-                let info = self.api
-                    .info()
-                    .await
-                    .attach_printable("Failed to get torrent info")
-                    .change_context(QBitTorrentClientError)?;
+    // Uploading the .torrent file doesn't depend on peers to know the metadata,
+    // the magnet is the fallback when there is no file or qBittorrent rejects it.
+    // The metadata is already there, so qBittorrent never meets a MetadataReceived
+    // stop condition and the upload is paused instead
+    pub async fn add_torrent(
+        &self,
+        magnet: MagnetLink,
+        torrent_file: Option<Vec<u8>>,
+        dest: Destination,
+        options: TorrentAddOptions,
+    ) -> Result<String, QBitTorrentClientError> {
+        let Some(torrent_file) = torrent_file else {
+            return self.add_magnet(magnet, dest, options).await;
+        };
 
-                log::trace!("Torrent info: {:?}", info);
+        let file_name = format!("{}.torrent", magnet.info_hash());
+        let file_options = TorrentAddOptions {
+            paused: Some(torrent::add_paused(&options, true)),
+            ..options.clone()
+        };
+        let result = self
+            .api
+            .add_file(
+                &file_name,
+                torrent_file,
+                &self.apply_destination(&dest, file_options),
+            )
+            .await;
 
-                on_update(&info);
+        match result {
+            Ok(_) => Ok(magnet.info_hash().to_owned()),
+            Err(e) => {
+                log::warn!("Failed to add torrent file, falling back to magnet\n{e:?}");
+                self.add_magnet(magnet, dest, options).await
+            }
+        }
+    }
 
-                if !has_unfinished_torrents(info) {
-                    break;
-                }
+    pub async fn list(
+        &self,
+        filter: &TorrentInfoFilter,
+    ) -> Result<TorrentList, QBitTorrentClientError> {
+        self.api
+            .info(filter)
+            .await
+            .attach_printable("Failed to list torrents")
+            .change_context(QBitTorrentClientError)
+    }
 
-                tokio::time::sleep(std::time::Duration::from_secs(1)).await;
-            }
+    pub async fn pause(&self, hashes: &[String]) -> Result<(), QBitTorrentClientError> {
+        self.api
+            .pause(hashes)
+            .await
+            .attach_printable_lazy(|| format!("Failed to pause torrents: {:?}", hashes))
+            .change_context(QBitTorrentClientError)
+    }
 
-            Ok(()) as Result<(), QBitTorrentClientError>
-        })
+    pub async fn resume(&self, hashes: &[String]) -> Result<(), QBitTorrentClientError> {
+        self.api
+            .resume(hashes)
+            .await
+            .attach_printable_lazy(|| format!("Failed to resume torrents: {:?}", hashes))
+            .change_context(QBitTorrentClientError)
     }
 
-    pub async fn add_uri(&self, uri: String, _dest: Destination) -> Result<(), QBitTorrentClientError> {
-        self.api.add(uri.as_str())
+    pub async fn remove(
+        &self,
+        hashes: &[String],
+        delete_files: bool,
+    ) -> Result<(), QBitTorrentClientError> {
+        self.api
+            .delete(hashes, delete_files)
             .await
-            .attach_printable_lazy(|| format!("Failed to add torrent with uri: {}", uri))
-            .change_context(QBitTorrentClientError)?;
+            .attach_printable_lazy(|| format!("Failed to delete torrents: {:?}", hashes))
+            .change_context(QBitTorrentClientError)
+    }
+
+    pub async fn recheck(&self, hashes: &[String]) -> Result<(), QBitTorrentClientError> {
+        self.api
+            .recheck(hashes)
+            .await
+            .attach_printable_lazy(|| format!("Failed to recheck torrents: {:?}", hashes))
+            .change_context(QBitTorrentClientError)
+    }
+
+    pub async fn reannounce(&self, hashes: &[String]) -> Result<(), QBitTorrentClientError> {
+        self.api
+            .reannounce(hashes)
+            .await
+            .attach_printable_lazy(|| format!("Failed to reannounce torrents: {:?}", hashes))
+            .change_context(QBitTorrentClientError)
+    }
+
+    pub async fn set_queue_priority(
+        &self,
+        hashes: &[String],
+        priority: QueuePriority,
+    ) -> Result<(), QBitTorrentClientError> {
+        self.api
+            .set_queue_priority(hashes, priority)
+            .await
+            .attach_printable_lazy(|| {
+                format!(
+                    "Failed to set priority {:?} of torrents: {:?}",
+                    priority, hashes
+                )
+            })
+            .change_context(QBitTorrentClientError)
+    }
+
+    // Relative locations follow the same rules as the save path of new torrents
+    pub async fn set_location(
+        &self,
+        hashes: &[String],
+        location: &str,
+    ) -> Result<(), QBitTorrentClientError> {
+        let location = self.resolve_save_path(location);
+        self.api
+            .set_location(hashes, &location)
+            .await
+            .attach_printable_lazy(|| {
+                format!("Failed to move torrents {:?} to: {}", hashes, location)
+            })
+            .change_context(QBitTorrentClientError)
+    }
+
+    // Unset limits are left untouched, 0 removes the limit
+    pub async fn set_limits(
+        &self,
+        hashes: &[String],
+        download_limit: Option<u64>,
+        upload_limit: Option<u64>,
+    ) -> Result<(), QBitTorrentClientError> {
+        if let Some(limit) = download_limit {
+            self.api
+                .set_download_limit(hashes, limit)
+                .await
+                .attach_printable_lazy(|| {
+                    format!("Failed to set download limit of torrents: {:?}", hashes)
+                })
+                .change_context(QBitTorrentClientError)?;
+        }
+
+        if let Some(limit) = upload_limit {
+            self.api
+                .set_upload_limit(hashes, limit)
+                .await
+                .attach_printable_lazy(|| {
+                    format!("Failed to set upload limit of torrents: {:?}", hashes)
+                })
+                .change_context(QBitTorrentClientError)?;
+        }
 
         Ok(())
     }
+
+    pub async fn set_file_priority(
+        &self,
+        hash: &str,
+        file_ids: &[usize],
+        priority: FilePriority,
+    ) -> Result<(), QBitTorrentClientError> {
+        self.api
+            .set_file_priority(hash, file_ids, priority)
+            .await
+            .attach_printable_lazy(|| {
+                format!(
+                    "Failed to set priority of files {:?} of torrent: {}",
+                    file_ids, hash
+                )
+            })
+            .change_context(QBitTorrentClientError)
+    }
+
+    // Default means qBittorrent's own default save path, and a given basename becomes the torrent name
+    fn apply_destination(
+        &self,
+        dest: &Destination,
+        options: TorrentAddOptions,
+    ) -> TorrentAddOptions {
+        torrent::apply_destination(dest, options, self.download_root.as_deref())
+    }
+
+    fn resolve_save_path(&self, folder: &str) -> String {
+        torrent::resolve_save_path(folder, self.download_root.as_deref())
+    }
+}
+
+impl TorrentClient for QBitTorrentClient {
+    fn add(
+        &self,
+        magnet: &MagnetLink,
+        torrent_url: Option<&str>,
+        dest: &Destination,
+        options: &TorrentAddOptions,
+    ) -> Result<String, TorrentClientError> {
+        let torrent_file = torrent::fetch_torrent_file_or_warn(torrent_url);
+
+        self.runtime
+            .block_on(self.add_torrent(magnet.clone(), torrent_file, dest.clone(), options.clone()))
+            .change_context(TorrentClientError)
+    }
+
+    fn list(&self, hashes: &[String]) -> Result<TorrentList, TorrentClientError> {
+        self.runtime
+            .block_on(self.list(&TorrentInfoFilter::for_hashes(hashes)))
+            .change_context(TorrentClientError)
+    }
+
+    fn remove(&self, hashes: &[String], delete_files: bool) -> Result<(), TorrentClientError> {
+        self.runtime
+            .block_on(self.remove(hashes, delete_files))
+            .change_context(TorrentClientError)
+    }
+
+    // Follows sync/maindata deltas instead of listing every torrent on each poll,
+    // which matters when the client also holds thousands of seeding torrents
+    fn wait_for_completion(
+        &self,
+        hashes: &[String],
+        options: &WaitOptions,
+        on_update: &dyn Fn(&TorrentList),
+    ) -> Result<WaitSummary, TorrentClientError> {
+        let mut sync_state = TorrentSyncState::default();
+
+        torrent::wait::poll_until_finished(
+            hashes,
+            options,
+            || {
+                let data = self
+                    .runtime
+                    .block_on(self.api.maindata(sync_state.rid()))
+                    .attach_printable("Failed to sync torrents")
+                    .change_context(TorrentClientError)?;
+                sync_state.apply(data);
+
+                Ok(sync_state.list(hashes))
+            },
+            on_update,
+            |stalled| TorrentClient::remove(self, stalled, true),
+        )
+    }
+
+    // The torrent should have been added paused or with the MetadataReceived stop condition,
+    // so nothing was downloaded before the unwanted files get the DoNotDownload priority
+    fn select_files(
+        &self,
+        hash: &str,
+        wanted: &dyn Fn(&str) -> bool,
+        resume: bool,
+    ) -> Result<usize, TorrentClientError> {
+        let files = torrent::wait_for_files(hash, || {
+            let files = self
+                .runtime
+                .block_on(self.api.files(hash))
+                .change_context(TorrentClientError)?;
+            Ok(files.0.iter().map(|file| file.name().clone()).collect())
+        })?;
+
+        let (selected, skipped) = torrent::partition_files(&files, wanted);
+        let hashes = [hash.to_owned()];
+
+        if selected.is_empty() {
+            TorrentClient::remove(self, &hashes, true)?;
+            return Err(Report::new(TorrentClientError)
+                .attach_printable(format!("No wanted file in torrent: {}", hash)));
+        }
+
+        log::debug!(
+            "Selected {} of {} files in torrent: {}",
+            selected.len(),
+            files.len(),
+            hash
+        );
+
+        self.runtime
+            .block_on(async {
+                if !skipped.is_empty() {
+                    self.set_file_priority(hash, &skipped, FilePriority::DoNotDownload)
+                        .await?;
+                }
+
+                if resume {
+                    self.resume(&hashes).await?;
+                }
+
+                Ok(()) as Result<(), QBitTorrentClientError>
+            })
+            .change_context(TorrentClientError)?;
+
+        Ok(selected.len())
+    }
 }
 
 #[test]
@@ -78,3 +362,120 @@ fn test_download_uri_to_file() {
     // let uri = "magnet:?xt=urn:btih:4936206e05d1bb04084f50032d6b3704f271eff7&dn=%5BOhys-Raws%5D%20Bougyoryoku%202%20-%2006%20%28AT-X%201280x720%20x264%20AAC%29.mp4&tr=http%3A%2F%2Fnyaa.tracker.wf%3A7777%2Fannounce&tr=udp%3A%2F%2Fopen.stealth.si%3A80%2Fannounce&tr=udp%3A%2F%2Ftracker.opentrackr.org%3A1337%2Fannounce&tr=udp%3A%2F%2Fexodus.desync.com%3A6969%2Fannounce&tr=udp%3A%2F%2Ftracker.torrent.eu.org%3A451%2Fannounce";
     // qbt.download_uri_to_file(uri, &Destination::Default).unwrap();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply_destination_given_folder() {
+        let client = QBitTorrentClient::new(Some(PathBuf::from("/downloads")));
+        let dest = Destination::GivenFolderGivenFileBasename(
+            "raws/One Piece".to_string(),
+            "One Piece 1051".to_string(),
+        );
+
+        let options = client.apply_destination(&dest, TorrentAddOptions::for_series("One Piece"));
+        assert_eq!(
+            options.savepath.as_deref(),
+            Some("/downloads/raws/One Piece")
+        );
+        assert_eq!(options.rename.as_deref(), Some("One Piece 1051"));
+        assert_eq!(options.tags, vec!["One Piece".to_string()]);
+    }
+
+    #[test]
+    fn test_apply_destination_absolute_folder_ignores_root() {
+        let client = QBitTorrentClient::new(Some(PathBuf::from("/downloads")));
+        let dest = Destination::GivenFolderGuessFileBasename("/media/anime".to_string());
+
+        let options = client.apply_destination(&dest, TorrentAddOptions::default());
+        assert_eq!(options.savepath.as_deref(), Some("/media/anime"));
+        assert_eq!(options.rename, None);
+    }
+
+    #[test]
+    fn test_apply_destination_default_keeps_client_default() {
+        let client = QBitTorrentClient::new(None);
+
+        let options = client.apply_destination(&Destination::Default, TorrentAddOptions::default());
+        assert_eq!(options.savepath, None);
+        assert_eq!(options.rename, None);
+
+        let options = client.apply_destination(
+            &Destination::GivenFolderGuessFileBasename("raws".to_string()),
+            TorrentAddOptions::default(),
+        );
+        assert_eq!(options.savepath.as_deref(), Some("raws"));
+    }
+
+    #[test]
+    fn test_torrent_file_is_paused_until_files_are_selected() {
+        use crate::qbittorrent::api::torrents::StopCondition;
+        use mockito::Matcher;
+
+        let hash = "568807a73ecd33fff3ac19f47805f0940cbdb9ac";
+        let mut server = mockito::Server::new();
+        let _version_mock = server
+            .mock("GET", "/api/v2/app/webapiVersion")
+            .with_body("2.9.3")
+            .create();
+        let add_mock = server
+            .mock("POST", "/api/v2/torrents/add")
+            .match_body(Matcher::Regex(
+                "name=\"paused\"\r\n\r\ntrue\r\n".to_string(),
+            ))
+            .with_body("Ok.")
+            .create();
+        let _files_mock = server
+            .mock("GET", "/api/v2/torrents/files")
+            .match_query(Matcher::UrlEncoded("hash".into(), hash.into()))
+            .with_body(
+                r#"[{"name": "Bocchi - 01.mkv", "size": 1, "progress": 0, "priority": 1},
+                    {"name": "Bocchi - 02.mkv", "size": 1, "progress": 0, "priority": 1}]"#,
+            )
+            .create();
+        let priority_mock = server
+            .mock("POST", "/api/v2/torrents/filePrio")
+            .match_body(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("id".into(), "1".into()),
+                Matcher::UrlEncoded("priority".into(), "0".into()),
+            ]))
+            .create();
+        let resume_mock = server
+            .mock("POST", "/api/v2/torrents/resume")
+            .match_body(Matcher::UrlEncoded("hashes".into(), hash.into()))
+            .create();
+
+        let client = QBitTorrentClient {
+            api: api::QBitTorrentApi::with_url(&format!("{}/api/v2/", server.url())),
+            download_root: None,
+            runtime: Runtime::new().unwrap(),
+        };
+        let magnet = MagnetLink::parse(&format!("magnet:?xt=urn:btih:{}", hash)).unwrap();
+        let options = TorrentAddOptions {
+            paused: Some(false),
+            stop_condition: Some(StopCondition::MetadataReceived),
+            ..TorrentAddOptions::default()
+        };
+
+        let added = client
+            .runtime
+            .block_on(client.add_torrent(
+                magnet,
+                Some(b"d4:infod4:name5:bocchiee".to_vec()),
+                Destination::Default,
+                options,
+            ))
+            .unwrap();
+        let selected =
+            TorrentClient::select_files(&client, &added, &|name| name.contains("01"), true)
+                .unwrap();
+
+        assert_eq!(added, hash);
+        assert_eq!(selected, 1);
+        add_mock.assert();
+        priority_mock.assert();
+        resume_mock.assert();
+    }
+}
@@ -1,32 +1,164 @@
-use std::sync::Arc;
+use std::ops::RangeInclusive;
+use std::sync::Mutex;
 
 use crate::core::download::downloader::{Destination, FileDownloader, FileDownloaderError, Uri};
-use crate::core::download::prelude::*;
+use crate::core::episode::parse_episode_number;
 use crate::core::indexer::Indexer;
-use crate::qbittorrent::api::torrents::TorrentList;
-use crate::qbittorrent::client::{QBitTorrentClient, QBitTorrentClientError};
+use crate::core::magnet::MagnetLink;
+use crate::qbittorrent::api::torrents::{StopCondition, TorrentAddOptions, TorrentList};
+use crate::raws::search::AnimeRawData;
+use crate::torrent::{TorrentClient, TorrentClientError, WaitOptions, WaitSummary};
 
-use error_stack::{IntoReport, Result, ResultExt};
-use tokio::runtime::Runtime;
+use error_stack::{Result, ResultExt};
 
-//TODO: folder to save the files should be configurable
+pub const DEFAULT_FOLDER: &str = "raws";
 
 pub struct AnimeRawDownloader {
-    inner_downloader: Arc<QBitTorrentClient>,
-    runtime: Runtime,
-    // default_folder: String,
+    inner_downloader: Box<dyn TorrentClient>,
+    // Info-hash and name of each enqueued raw
+    enqueued: Mutex<Vec<(String, String)>>,
+    add_options: TorrentAddOptions,
+    default_folder: String,
+    episodes: Vec<RangeInclusive<u32>>,
 }
 
 impl AnimeRawDownloader {
-    pub fn new() -> Self {
+    // With episodes given, only the matching files of batch torrents are downloaded
+    pub fn new(
+        torrent_client: Box<dyn TorrentClient>,
+        add_options: TorrentAddOptions,
+        episodes: Vec<RangeInclusive<u32>>,
+    ) -> Self {
         Self {
-            inner_downloader: Arc::new(QBitTorrentClient::new()),
-            runtime: Runtime::new().unwrap(),
+            inner_downloader: torrent_client,
+            enqueued: Mutex::new(Vec::new()),
+            add_options,
+            default_folder: DEFAULT_FOLDER.to_string(),
+            episodes,
         }
     }
 
-    pub fn wait_for_completion<'a>(&self, on_update: impl Fn(&TorrentList) + Send + Sync + 'a) -> Result<(), QBitTorrentClientError>{
-        self.inner_downloader.wait_for_completion(on_update)
+    fn is_wanted_file(&self, file_name: &str) -> bool {
+        parse_episode_number(file_name)
+            .is_some_and(|episode| self.episodes.iter().any(|range| range.contains(&episode)))
+    }
+
+    // A paused magnet never fetches its metadata, so the torrent is instead
+    // stopped by the client once the file list is known
+    fn add_options_for_selection(&self) -> TorrentAddOptions {
+        if self.episodes.is_empty() {
+            return self.add_options.clone();
+        }
+
+        TorrentAddOptions {
+            paused: Some(false),
+            stop_condition: Some(StopCondition::MetadataReceived),
+            ..self.add_options.clone()
+        }
+    }
+
+    fn add_and_select(
+        &self,
+        magnet: &MagnetLink,
+        torrent_url: Option<&str>,
+        dest: &Destination,
+    ) -> Result<String, TorrentClientError> {
+        let hash = self.inner_downloader.add(
+            magnet,
+            torrent_url,
+            dest,
+            &self.add_options_for_selection(),
+        )?;
+
+        if !self.episodes.is_empty() {
+            let resume = !self.add_options.paused.unwrap_or(false);
+            self.inner_downloader
+                .select_files(&hash, &|file_name| self.is_wanted_file(file_name), resume)
+                .attach_printable_lazy(|| {
+                    format!("Failed to select episodes {:?}", self.episodes)
+                })?;
+        }
+
+        Ok(hash)
+    }
+
+    // Same folder rules as the subtitles, so raws and subs can end up side by side
+    fn resolve_destination(&self, destination: &Destination) -> Destination {
+        match destination {
+            Destination::DefaultFolderGivenFileBasename(file_basename) => {
+                Destination::GivenFolderGivenFileBasename(
+                    self.default_folder.clone(),
+                    file_basename.clone(),
+                )
+            }
+            Destination::Default => {
+                Destination::GivenFolderGuessFileBasename(self.default_folder.clone())
+            }
+            given_folder => given_folder.clone(),
+        }
+    }
+
+    fn enqueue(
+        &self,
+        name: &str,
+        magnet: MagnetLink,
+        torrent_url: Option<&str>,
+        dest: &Destination,
+    ) -> Result<(), FileDownloaderError> {
+        let hash = self
+            .add_and_select(&magnet, torrent_url, &self.resolve_destination(dest))
+            .change_context(FileDownloaderError)?;
+
+        self.enqueued.lock().unwrap().push((hash, name.to_owned()));
+        Ok(())
+    }
+
+    // Uses nyaa's .torrent file when possible, the magnet alone may stall on poorly seeded raws
+    pub fn download_raw(
+        &self,
+        raw_data: &AnimeRawData,
+        dest: &Destination,
+    ) -> Result<(), FileDownloaderError> {
+        self.enqueue(
+            &raw_data.anime_name,
+            raw_data.anime_raw_magnet.clone(),
+            Some(&raw_data.anime_raw_torrent_url),
+            dest,
+        )
+        .attach_printable_lazy(|| format!("Failed to enqueue raw: {}", raw_data.anime_name))
+    }
+
+    // Waits only for the torrents enqueued by this downloader
+    pub fn wait_for_completion(
+        &self,
+        options: &WaitOptions,
+        on_update: impl Fn(&TorrentList),
+    ) -> Result<WaitSummary, TorrentClientError> {
+        let hashes: Vec<String> = self
+            .enqueued
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(hash, _)| hash.clone())
+            .collect();
+
+        self.inner_downloader
+            .wait_for_completion(&hashes, options, &on_update)
+    }
+
+    // The client's view of the torrents, e.g. where the finished ones were saved
+    pub fn list(&self, hashes: &[String]) -> Result<TorrentList, TorrentClientError> {
+        self.inner_downloader.list(hashes)
+    }
+
+    // The name the raw was enqueued with, to report torrents by something readable
+    pub fn enqueued_name(&self, hash: &str) -> String {
+        self.enqueued
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|(enqueued_hash, _)| enqueued_hash == hash)
+            .map_or_else(|| hash.to_owned(), |(_, name)| name.clone())
     }
 }
 
@@ -34,15 +166,15 @@ impl FileDownloader for AnimeRawDownloader {
     fn download_uri_to_file(
         &self,
         uri: &Uri,
-        _dest: &Destination,
+        dest: &Destination,
     ) -> Result<(), FileDownloaderError> {
-        let inner_downloader = self.inner_downloader.clone();
-        let uri = uri.to_owned();
-        let future = async move {
-            inner_downloader.add_uri(uri, Destination::Default).await
-        }; 
-        self.runtime.spawn(future);
-        Ok(())
+        let magnet = MagnetLink::parse(uri)
+            .attach_printable("Refusing to enqueue invalid magnet")
+            .change_context(FileDownloaderError)?;
+
+        let name = magnet.display_name().clone().unwrap_or_else(|| uri.to_string());
+        self.enqueue(&name, magnet, None, dest)
+            .attach_printable_lazy(|| format!("Failed to enqueue uri: {}", uri))
     }
 
     fn download_indexer_to_file(
@@ -61,3 +193,68 @@ impl FileDownloader for AnimeRawDownloader {
         unimplemented!()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::qbittorrent::client::QBitTorrentClient;
+
+    fn create_downloader(
+        add_options: TorrentAddOptions,
+        episodes: Vec<RangeInclusive<u32>>,
+    ) -> AnimeRawDownloader {
+        AnimeRawDownloader::new(
+            Box::new(QBitTorrentClient::new(None)),
+            add_options,
+            episodes,
+        )
+    }
+
+    #[test]
+    fn test_is_wanted_file() {
+        let downloader = create_downloader(TorrentAddOptions::default(), vec![1..=1, 5..=7]);
+
+        assert!(downloader.is_wanted_file("Batch/[Ohys-Raws] Bocchi - 01 (BS11).mp4"));
+        assert!(downloader.is_wanted_file("Batch/[Ohys-Raws] Bocchi - 06 (BS11).mp4"));
+        assert!(!downloader.is_wanted_file("Batch/[Ohys-Raws] Bocchi - 04 (BS11).mp4"));
+        assert!(!downloader.is_wanted_file("Batch/readme.txt"));
+    }
+
+    #[test]
+    fn test_enqueued_name() {
+        let downloader = create_downloader(TorrentAddOptions::default(), Vec::new());
+        downloader.enqueued.lock().unwrap().push((
+            "568807a73ecd33fff3ac19f47805f0940cbdb9ac".to_string(),
+            "[Ohys-Raws] Bocchi - 01 (BS11).mp4".to_string(),
+        ));
+
+        assert_eq!(
+            downloader.enqueued_name("568807a73ecd33fff3ac19f47805f0940cbdb9ac"),
+            "[Ohys-Raws] Bocchi - 01 (BS11).mp4"
+        );
+        assert_eq!(downloader.enqueued_name("unknown"), "unknown");
+    }
+
+    #[test]
+    fn test_add_options_for_selection() {
+        let add_options = TorrentAddOptions {
+            paused: Some(true),
+            ..TorrentAddOptions::default()
+        };
+
+        let downloader = create_downloader(add_options.clone(), Vec::new());
+        assert_eq!(downloader.add_options_for_selection().paused, Some(true));
+        assert!(downloader
+            .add_options_for_selection()
+            .stop_condition
+            .is_none());
+
+        let downloader = create_downloader(add_options, vec![1..=3]);
+        let options = downloader.add_options_for_selection();
+        assert_eq!(options.paused, Some(false));
+        assert_eq!(
+            options.stop_condition,
+            Some(StopCondition::MetadataReceived)
+        );
+    }
+}
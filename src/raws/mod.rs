@@ -1,2 +1,3 @@
 pub mod search;
-pub mod download;
\ No newline at end of file
+pub mod download;
+pub mod progress;
\ No newline at end of file
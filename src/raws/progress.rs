@@ -0,0 +1,160 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::time::Duration;
+
+use indicatif::{HumanBytes, HumanDuration, MultiProgress, ProgressBar, ProgressStyle};
+use lazy_static::lazy_static;
+
+use crate::qbittorrent::api::torrents::{Torrent, TorrentList};
+
+const NAME_WIDTH: usize = 40;
+
+lazy_static! {
+    static ref TORRENT_STYLE: ProgressStyle = ProgressStyle::with_template(
+        "{prefix:40} [{bar:30.cyan/blue}] {bytes}/{total_bytes} {msg}",
+    )
+    .unwrap()
+    .progress_chars("=> ");
+    static ref OVERALL_STYLE: ProgressStyle = ProgressStyle::with_template(
+        "{prefix:40.bold} [{bar:30.green}] {bytes}/{total_bytes} ({percent}%)",
+    )
+    .unwrap()
+    .progress_chars("=> ");
+}
+
+// One bar per enqueued torrent plus an overall one, bars are added as torrents show up in the client
+pub struct TorrentProgress {
+    multi_progress: MultiProgress,
+    overall: ProgressBar,
+    bars: RefCell<HashMap<String, ProgressBar>>,
+}
+
+impl TorrentProgress {
+    pub fn new() -> Self {
+        let multi_progress = MultiProgress::new();
+        let overall = multi_progress.add(ProgressBar::new(0));
+        overall.set_style(OVERALL_STYLE.clone());
+        overall.set_prefix("Total");
+
+        TorrentProgress {
+            multi_progress,
+            overall,
+            bars: RefCell::new(HashMap::new()),
+        }
+    }
+
+    pub fn update(&self, torrents: &TorrentList) {
+        let mut bars = self.bars.borrow_mut();
+
+        for torrent in &torrents.0 {
+            let bar = bars.entry(torrent.hash().clone()).or_insert_with(|| {
+                let bar = self
+                    .multi_progress
+                    .insert_before(&self.overall, ProgressBar::new(0));
+                bar.set_style(TORRENT_STYLE.clone());
+                bar.set_prefix(shorten_name(torrent.name()));
+                bar
+            });
+
+            bar.set_length(*torrent.size() as u64);
+            bar.set_position(torrent.completed_bytes());
+            bar.set_message(describe_transfer(torrent));
+
+            if torrent.finished() && !bar.is_finished() {
+                bar.finish();
+            }
+        }
+
+        self.overall
+            .set_length(torrents.0.iter().map(|t| *t.size() as u64).sum());
+        self.overall
+            .set_position(torrents.0.iter().map(|t| t.completed_bytes()).sum());
+    }
+
+    pub fn finish(&self) {
+        for bar in self.bars.borrow().values() {
+            bar.finish();
+        }
+        self.overall.finish();
+    }
+}
+
+fn shorten_name(name: &str) -> String {
+    match name.char_indices().nth(NAME_WIDTH - 3) {
+        Some((index, _)) => format!("{}...", &name[..index]),
+        None => name.to_owned(),
+    }
+}
+
+// e.g. "Downloading 1.20 MiB/s eta 3 minutes, 12 seeds 4 peers"
+fn describe_transfer(torrent: &Torrent) -> String {
+    let eta = match torrent.eta_known() {
+        true => HumanDuration(Duration::from_secs(*torrent.eta() as u64)).to_string(),
+        false => "∞".to_string(),
+    };
+
+    format!(
+        "{:?} {}/s eta {}, {} seeds {} peers",
+        torrent.state(),
+        HumanBytes(*torrent.dlspeed() as u64),
+        eta,
+        torrent.num_seeds(),
+        torrent.num_leechs()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::qbittorrent::api::torrents::TorrentState;
+
+    #[test]
+    fn test_shorten_name() {
+        assert_eq!(shorten_name("One Piece"), "One Piece");
+
+        let name = "[Ohys-Raws] Bougyoryoku 2 - 06 (AT-X 1280x720 x264 AAC).mp4";
+        let short_name = shorten_name(name);
+        assert_eq!(short_name.chars().count(), NAME_WIDTH);
+        assert!(short_name.ends_with("..."));
+    }
+
+    #[test]
+    fn test_describe_transfer() {
+        let torrent = Torrent::new(
+            "568807a73ecd33fff3ac19f47805f0940cbdb9ac",
+            "One Piece",
+            10,
+            TorrentState::Downloading,
+            2,
+            8_640_000,
+        )
+        .with_transfer(0.2, 2048, 12, 4);
+
+        assert_eq!(
+            describe_transfer(&torrent),
+            "Downloading 2.00 KiB/s eta ∞, 12 seeds 4 peers"
+        );
+    }
+
+    #[test]
+    fn test_update_uses_progress() {
+        let torrent = Torrent::new(
+            "568807a73ecd33fff3ac19f47805f0940cbdb9ac",
+            "One Piece",
+            1000,
+            TorrentState::Downloading,
+            1500,
+            60,
+        )
+        .with_transfer(0.25, 2048, 12, 4);
+
+        let progress = TorrentProgress::new();
+        progress.update(&TorrentList(vec![torrent]));
+
+        let bars = progress.bars.borrow();
+        let bar = &bars["568807a73ecd33fff3ac19f47805f0940cbdb9ac"];
+        assert_eq!(bar.position(), 250);
+        assert_eq!(bar.length(), Some(1000));
+        assert_eq!(progress.overall.position(), 250);
+    }
+}
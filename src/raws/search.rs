@@ -3,8 +3,8 @@ use regex::{CaptureMatches, Regex};
 use std::error::Error;
 
 use crate::core::{
-    indexer::Indexer,
-    scrapper::{HttpScrapper, self},
+    magnet::MagnetLink,
+    scrapper::{self, HttpScrapper},
 };
 
 macro_rules! create_anime_raw_query_url {
@@ -16,6 +16,15 @@ macro_rules! create_anime_raw_query_url {
     };
 }
 
+macro_rules! create_anime_raw_torrent_url {
+    ($view_path: expr) => {
+        format!(
+            "https://nyaa.si{}.torrent",
+            $view_path.replacen("/view/", "/download/", 1)
+        )
+    };
+}
+
 lazy_static! {
     static ref MAGNET_REGEX: Regex = match Regex::new(
         r#"(?m)href="(/view/[^"]+?)" title="([^"]+?)"(?:.|[\n\r ])+?(magnet:[^"]+)(?:.|[\n\r ])+?text-center(?:.|[\n\r ])+?text-center(?:.|[\n\r ])+?text-center">(\d+)"#,
@@ -28,7 +37,8 @@ lazy_static! {
 #[derive(Debug, Hash, Eq, PartialEq, Clone)]
 pub struct AnimeRawData {
     pub anime_name: String,
-    pub anime_raw_magnet: String,
+    pub anime_raw_magnet: MagnetLink,
+    pub anime_raw_torrent_url: String,
 }
 
 impl scrapper::ScrapperData for AnimeRawData {
@@ -37,14 +47,21 @@ impl scrapper::ScrapperData for AnimeRawData {
         Self: Sized,
     {
         capture
-            .map(|capture| {
+            .filter_map(|capture| {
                 //TODO: safety check for capture count here
                 let anime_name = capture[2].to_string();
-                let anime_raw_magnet = capture[3].to_string();
-                AnimeRawData {
+                let anime_raw_magnet = match MagnetLink::parse(&capture[3]) {
+                    Ok(magnet) => magnet,
+                    Err(e) => {
+                        log::warn!("Skipping raw with invalid magnet: {}\n{e:?}", anime_name);
+                        return None;
+                    }
+                };
+                Some(AnimeRawData {
                     anime_name,
                     anime_raw_magnet,
-                }
+                    anime_raw_torrent_url: create_anime_raw_torrent_url!(&capture[1]),
+                })
             })
             .collect()
     }
@@ -53,7 +70,7 @@ impl scrapper::ScrapperData for AnimeRawData {
 pub fn search_anime_raws(anime_name: &str) -> Result<Vec<AnimeRawData>, Box<dyn Error>> {
     println!("Searching for anime: {}", anime_name);
     let scrapper = HttpScrapper::<AnimeRawData>::new(MAGNET_REGEX.clone());
-    return scrapper.scrap_page(&create_anime_raw_query_url!(anime_name).as_str());
+    scrapper.scrap_page(create_anime_raw_query_url!(anime_name).as_str())
 }
 
 #[cfg(test)]
@@ -64,11 +81,11 @@ mod tests {
         let anime_name = "One Piece";
         let result = search_anime_raws(anime_name)
             .expect("Failed to search anime")
-            .get(0)
+            .first()
             .expect("No results found")
             .to_owned();
 
-        assert!(result.name().contains("One Piece"));
+        assert!(result.anime_name.contains("One Piece"));
     }
 
     #[test]
@@ -76,11 +93,14 @@ mod tests {
         let anime_name = "One Piece";
         let result = search_anime_raws(anime_name)
             .expect("Failed to search anime")
-            .get(0)
+            .first()
             .expect("No results found")
             .to_owned();
 
-        assert!(result.uri().starts_with("magnet:?xt=urn:btih:"));
+        assert!(result
+            .anime_raw_magnet
+            .to_string()
+            .starts_with("magnet:?xt=urn:btih:"));
     }
 
     #[test]
@@ -106,18 +126,26 @@ mod tests {
         <td class="text-center">13</td>
         <td class="text-assert_matches!()center">1</td>
     </tr>"#;
-        let result = HttpScrapper::<Indexer>::new(MAGNET_REGEX.clone())
+        let result = HttpScrapper::<AnimeRawData>::new(MAGNET_REGEX.clone())
             .scrap_raw_data(html)
             .expect("Failed to parse response text");
 
-        let result = result.get(0).expect("No results found");
+        let result = result.first().expect("No results found");
         assert_eq!(
-            result.name(),
+            result.anime_name,
             "[Fumi-Raws] (One Piece (1051) - (フジテレビ 1920x1080).mkv"
         );
         assert_eq!(
-            result.uri(),
-            "magnet:?xt=urn:btih:568807a73ecd33fff3ac19f47805f0940cbdb9ac&amp;dn=%5BFumi-Raws%5D%20%28One%20Piece%20%281051%29%20-%20%28%E3%83%95%E3%82%B8%E3%83%86%E3%83%AC%E3%83%93%201920x1080%29.mkv&amp;tr=http%3A%2F%2Fnyaa.tracker.wf%3A7777%2Fannounce&amp;tr=udp%3A%2F%2Fopen.stealth.si%3A80%2Fannounce&amp;tr=udp%3A%2F%2Ftracker.opentrackr.org%3A1337%2Fannounce&amp;tr=udp%3A%2F%2Fexodus.desync.com%3A6969%2Fannounce&amp;tr=udp%3A%2F%2Ftracker.torrent.eu.org%3A451%2Fannounce"
+            result.anime_raw_magnet.info_hash(),
+            "568807a73ecd33fff3ac19f47805f0940cbdb9ac"
+        );
+        assert_eq!(
+            result.anime_raw_magnet.to_string(),
+            "magnet:?xt=urn:btih:568807a73ecd33fff3ac19f47805f0940cbdb9ac&dn=%5BFumi-Raws%5D%20%28One%20Piece%20%281051%29%20-%20%28%E3%83%95%E3%82%B8%E3%83%86%E3%83%AC%E3%83%93%201920x1080%29.mkv&tr=http%3A%2F%2Fnyaa.tracker.wf%3A7777%2Fannounce&tr=udp%3A%2F%2Fopen.stealth.si%3A80%2Fannounce&tr=udp%3A%2F%2Ftracker.opentrackr.org%3A1337%2Fannounce&tr=udp%3A%2F%2Fexodus.desync.com%3A6969%2Fannounce&tr=udp%3A%2F%2Ftracker.torrent.eu.org%3A451%2Fannounce"
+        );
+        assert_eq!(
+            result.anime_raw_torrent_url,
+            "https://nyaa.si/download/1636619.torrent"
         );
     }
 }
@@ -0,0 +1,120 @@
+use clap::ValueEnum;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum ExportFormat {
+    Csv,
+    Tsv,
+    // Tab separated with the header lines Anki reads when importing a text file
+    Anki,
+}
+
+impl ExportFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Tsv => "tsv",
+            ExportFormat::Anki => "txt",
+        }
+    }
+}
+
+// A table as text in the given format. Anki files have no header row, their columns are
+// named in the #columns line instead, and the notes go to `deck`
+pub fn write_table(
+    format: ExportFormat,
+    deck: &str,
+    header: &[&str],
+    rows: &[Vec<String>],
+) -> String {
+    let mut lines = Vec::with_capacity(rows.len() + 4);
+
+    match format {
+        ExportFormat::Csv => lines.push(csv_line(header.iter().copied())),
+        ExportFormat::Tsv => lines.push(tsv_line(header.iter().copied())),
+        ExportFormat::Anki => {
+            lines.push("#separator:tab".to_owned());
+            lines.push("#html:false".to_owned());
+            lines.push(format!("#deck:{}", tsv_field(deck)));
+            lines.push(format!("#columns:{}", tsv_line(header.iter().copied())));
+        }
+    }
+
+    for row in rows {
+        let fields = row.iter().map(String::as_str);
+        lines.push(match format {
+            ExportFormat::Csv => csv_line(fields),
+            ExportFormat::Tsv | ExportFormat::Anki => tsv_line(fields),
+        });
+    }
+
+    let mut content = lines.join("\n");
+    content.push('\n');
+    content
+}
+
+fn csv_line<'a>(fields: impl Iterator<Item = &'a str>) -> String {
+    fields.map(csv_field).collect::<Vec<_>>().join(",")
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
+
+fn tsv_line<'a>(fields: impl Iterator<Item = &'a str>) -> String {
+    fields.map(tsv_field).collect::<Vec<_>>().join("\t")
+}
+
+// TSV has no quoting, so separators inside a field become spaces
+fn tsv_field(field: &str) -> String {
+    field.replace(['\t', '\n', '\r'], " ")
+}
+
+// "00:01:02.345", which ffmpeg also accepts for -ss and -to
+pub fn format_timestamp(ms: i64) -> String {
+    let ms = ms.max(0);
+    format!(
+        "{:02}:{:02}:{:02}.{:03}",
+        ms / 3_600_000,
+        ms / 60_000 % 60,
+        ms / 1000 % 60,
+        ms % 1000
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_csv() {
+        let rows = vec![vec!["a \"b\", c".to_owned(), "d".to_owned()]];
+
+        assert_eq!(
+            write_table(ExportFormat::Csv, "Bocchi", &["Sentence", "Start"], &rows),
+            "Sentence,Start\n\"a \"\"b\"\", c\",d\n"
+        );
+    }
+
+    #[test]
+    fn test_write_anki() {
+        let rows = vec![vec![
+            "「はい、そうです」".to_owned(),
+            "00:00:01.500".to_owned(),
+        ]];
+
+        assert_eq!(
+            write_table(ExportFormat::Anki, "Bocchi 01", &["Sentence", "Start"], &rows),
+            "#separator:tab\n#html:false\n#deck:Bocchi 01\n#columns:Sentence\tStart\n「はい、そうです」\t00:00:01.500\n"
+        );
+    }
+
+    #[test]
+    fn test_format_timestamp() {
+        assert_eq!(format_timestamp(3_723_456), "01:02:03.456");
+        assert_eq!(format_timestamp(-20), "00:00:00.000");
+    }
+}
@@ -0,0 +1,412 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use derive_getters::Getters;
+use error_stack::{IntoReport, Report, Result, ResultExt};
+
+use crate::core::episode::parse_episode_number;
+use crate::core::extensions::SUBTITLE_EXTENSIONS;
+use crate::library::scan::{has_extension, VIDEO_EXTENSIONS};
+use crate::sync::subtitle::{Cue, SubtitleTrack};
+
+pub mod export;
+pub mod search;
+pub mod text;
+
+use export::{format_timestamp, write_table, ExportFormat};
+
+#[derive(Debug)]
+pub struct StudyError;
+
+impl std::fmt::Display for StudyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Failed to export subtitles for study")
+    }
+}
+
+impl Error for StudyError {}
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct ExportOptions {
+    #[arg(
+        value_enum,
+        long,
+        default_value = "tsv",
+        help = "The format of the exported lists"
+    )]
+    pub format: ExportFormat,
+
+    #[arg(
+        long,
+        help = "Where to write the lists, next to the subtitles by default"
+    )]
+    pub output_dir: Option<PathBuf>,
+
+    #[arg(
+        long,
+        help = "The Anki deck receiving the notes, named after the subtitles by default"
+    )]
+    pub deck: Option<String>,
+
+    #[arg(
+        long,
+        default_value = "false",
+        help = "Also export the words of the subtitles with how often they appear"
+    )]
+    pub vocabulary: bool,
+
+    #[arg(
+        long,
+        default_value = "false",
+        help = "Add the matching raw and the time range of each sentence in it, to cut audio clips"
+    )]
+    pub clips: bool,
+
+    #[arg(
+        long,
+        default_value = "250",
+        help = "Milliseconds added before and after each audio clip"
+    )]
+    pub clip_padding: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Getters)]
+pub struct Sentence {
+    text: String,
+    start: i64,
+    end: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Getters)]
+pub struct Word {
+    word: String,
+    count: usize,
+    // The first sentence using the word
+    example: Sentence,
+}
+
+// The cues of the subtitles in the order they are shown, with their text as written. Cues
+// without text and lines repeated by typesetting effects are left out
+pub fn shown_cues(content: &str) -> Vec<(Cue, String)> {
+    let track = SubtitleTrack::parse(content);
+
+    let mut cues: Vec<_> = track.cues().into_iter().zip(track.cue_texts()).collect();
+    cues.sort_by_key(|(cue, _)| cue.start);
+
+    let mut shown: Vec<(Cue, String)> = Vec::new();
+    let mut last: Option<(Cue, String)> = None;
+    for (cue, cue_text) in cues {
+        let text = text::clean_text(&cue_text);
+        if text.is_empty() {
+            continue;
+        }
+
+        let repeated = last
+            .as_ref()
+            .is_some_and(|(last_cue, last_text)| *last_text == text && last_cue.end >= cue.start);
+        if !repeated {
+            shown.push((cue, cue_text));
+        }
+        last = Some((cue, text));
+    }
+
+    shown
+}
+
+// The sentences of the subtitles in the order they are shown, each with the timing of its cue
+pub fn extract_sentences(content: &str) -> Vec<Sentence> {
+    shown_cues(content)
+        .into_iter()
+        .flat_map(|(cue, cue_text)| {
+            text::split_sentences(&text::clean_text(&cue_text))
+                .into_iter()
+                .map(move |text| Sentence {
+                    text,
+                    start: cue.start,
+                    end: cue.end,
+                })
+        })
+        .collect()
+}
+
+// The words of the sentences, most frequent first
+pub fn extract_vocabulary(sentences: &[Sentence]) -> Vec<Word> {
+    let mut words: Vec<Word> = Vec::new();
+    let mut positions: HashMap<String, usize> = HashMap::new();
+
+    for sentence in sentences {
+        for word in text::find_words(&sentence.text) {
+            match positions.get(&word) {
+                Some(&position) => words[position].count += 1,
+                None => {
+                    positions.insert(word.clone(), words.len());
+                    words.push(Word {
+                        word,
+                        count: 1,
+                        example: sentence.clone(),
+                    });
+                }
+            }
+        }
+    }
+
+    // Stable, so words seen as often stay in the order they first appear
+    words.sort_by_key(|word| std::cmp::Reverse(word.count));
+    words
+}
+
+// The raw next to the subtitles, e.g. "Bocchi - 01.mkv" for "Bocchi - 01.ja.srt", or else
+// the only video of the folder with the same episode number
+pub fn find_matching_video(subtitle: &Path) -> Option<PathBuf> {
+    let folder = subtitle.parent()?;
+    let stem = subtitle.file_stem()?.to_str()?;
+
+    let videos: Vec<PathBuf> = fs::read_dir(folder)
+        .ok()?
+        .filter_map(|entry| Some(entry.ok()?.path()))
+        .filter(|path| has_extension(path, &VIDEO_EXTENSIONS))
+        .collect();
+
+    let same_name = videos.iter().find(|video| {
+        video
+            .file_stem()
+            .and_then(|video_stem| video_stem.to_str())
+            .is_some_and(|video_stem| {
+                stem == video_stem || stem.starts_with(&format!("{}.", video_stem))
+            })
+    });
+    if same_name.is_some() {
+        return same_name.cloned();
+    }
+
+    let episode = parse_episode_number(subtitle.file_name()?.to_str()?)?;
+    let mut same_episode = videos.into_iter().filter(|video| {
+        video
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(parse_episode_number)
+            == Some(episode)
+    });
+
+    match (same_episode.next(), same_episode.next()) {
+        (Some(video), None) => Some(video),
+        _ => None,
+    }
+}
+
+// Every subtitle file among the paths, looking inside folders recursively
+pub fn find_subtitles(paths: &[PathBuf]) -> Result<Vec<PathBuf>, StudyError> {
+    let mut subtitles = Vec::new();
+
+    for path in paths {
+        if !path.is_dir() {
+            subtitles.push(path.clone());
+            continue;
+        }
+
+        let entries = fs::read_dir(path)
+            .into_report()
+            .attach_printable_lazy(|| format!("Failed to read folder: {}", path.display()))
+            .change_context(StudyError)?;
+
+        let mut children = Vec::new();
+        for entry in entries {
+            let child = entry
+                .into_report()
+                .attach_printable_lazy(|| format!("Failed to read folder: {}", path.display()))
+                .change_context(StudyError)?
+                .path();
+
+            if child.is_dir() || has_extension(&child, &SUBTITLE_EXTENSIONS) {
+                children.push(child);
+            }
+        }
+
+        children.sort();
+        subtitles.extend(find_subtitles(&children)?);
+    }
+
+    Ok(subtitles)
+}
+
+// Writes the sentence list of the subtitles, and their vocabulary when asked. Returns the
+// written files
+pub fn export_subtitle(
+    subtitle: &Path,
+    options: &ExportOptions,
+) -> Result<Vec<PathBuf>, StudyError> {
+    let content = fs::read(subtitle)
+        .into_report()
+        .attach_printable_lazy(|| format!("Failed to read subtitles: {}", subtitle.display()))
+        .change_context(StudyError)?;
+    // Some subtitles on kitsunekko aren't valid UTF-8, the broken characters get replaced
+    let content = String::from_utf8_lossy(&content);
+
+    let sentences = extract_sentences(&content);
+    if sentences.is_empty() {
+        return Err(Report::new(StudyError)
+            .attach_printable(format!("No sentences in: {}", subtitle.display())));
+    }
+
+    let stem = subtitle
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let deck = options.deck.clone().unwrap_or_else(|| stem.clone());
+    let folder = match &options.output_dir {
+        Some(output_dir) => output_dir.as_path(),
+        None => subtitle.parent().unwrap_or(Path::new(".")),
+    };
+
+    let video = match options.clips {
+        true => {
+            let video = find_matching_video(subtitle);
+            if video.is_none() {
+                log::warn!("No raw found for the clips of: {}", subtitle.display());
+            }
+            video
+        }
+        false => None,
+    };
+
+    let mut header = vec!["Sentence", "Start", "End"];
+    if options.clips {
+        header.extend(["Video", "Clip start", "Clip end"]);
+    }
+
+    let padding = options.clip_padding as i64;
+    let rows: Vec<Vec<String>> = sentences
+        .iter()
+        .map(|sentence| {
+            let mut row = vec![
+                sentence.text.clone(),
+                format_timestamp(sentence.start),
+                format_timestamp(sentence.end),
+            ];
+            if options.clips {
+                match &video {
+                    Some(video) => row.extend([
+                        video.display().to_string(),
+                        format_timestamp(sentence.start - padding),
+                        format_timestamp(sentence.end + padding),
+                    ]),
+                    None => row.extend([String::new(), String::new(), String::new()]),
+                }
+            }
+            row
+        })
+        .collect();
+
+    let mut written = vec![write_list(
+        &folder.join(format!("{}.sentences.{}", stem, options.format.extension())),
+        write_table(options.format, &deck, &header, &rows),
+    )?];
+
+    if options.vocabulary {
+        let rows: Vec<Vec<String>> = extract_vocabulary(&sentences)
+            .into_iter()
+            .map(|word| {
+                vec![
+                    word.word,
+                    word.count.to_string(),
+                    word.example.text,
+                    format_timestamp(word.example.start),
+                ]
+            })
+            .collect();
+
+        written.push(write_list(
+            &folder.join(format!(
+                "{}.vocabulary.{}",
+                stem,
+                options.format.extension()
+            )),
+            write_table(
+                options.format,
+                &deck,
+                &["Word", "Count", "Example", "Start"],
+                &rows,
+            ),
+        )?);
+    }
+
+    Ok(written)
+}
+
+fn write_list(path: &Path, content: String) -> Result<PathBuf, StudyError> {
+    fs::write(path, content)
+        .into_report()
+        .attach_printable_lazy(|| format!("Failed to write: {}", path.display()))
+        .change_context(StudyError)?;
+
+    Ok(path.to_path_buf())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::unique_temp_dir;
+
+    const ASS: &str = "[Script Info]\nTitle: Bocchi\n\n[Events]\nFormat: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\n\
+        Dialogue: 0,0:00:05.00,0:00:07.00,Default,,0,0,0,,{\\an8}ギター、弾(ひ)けるの？\\Nうん。ギター！\n\
+        Dialogue: 0,0:00:01.00,0:00:03.00,Default,,0,0,0,,{\\fad(200,200)}後藤(ごとう)です\n\
+        Dialogue: 1,0:00:01.00,0:00:03.00,Default,,0,0,0,,{\\blur3}後藤(ごとう)です\n\
+        Dialogue: 0,0:00:08.00,0:00:09.00,Sign,,0,0,0,,{\\p1}m 0 0 l 10 0 10 10{\\p0}\n";
+
+    #[test]
+    fn test_extract_sentences() {
+        let sentence = |text: &str, start, end| Sentence {
+            text: text.to_owned(),
+            start,
+            end,
+        };
+
+        assert_eq!(
+            extract_sentences(ASS),
+            vec![
+                sentence("後藤です", 1_000, 3_000),
+                sentence("ギター、弾けるの？", 5_000, 7_000),
+                sentence("うん。", 5_000, 7_000),
+                sentence("ギター！", 5_000, 7_000),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_extract_vocabulary() {
+        let vocabulary = extract_vocabulary(&extract_sentences(ASS));
+        let words: Vec<(&str, usize)> = vocabulary
+            .iter()
+            .map(|word| (word.word.as_str(), word.count))
+            .collect();
+
+        assert_eq!(words, vec![("ギター", 2), ("後藤", 1), ("弾", 1)]);
+        assert_eq!(vocabulary[0].example.start, 5_000);
+    }
+
+    #[test]
+    fn test_find_matching_video() {
+        let folder = unique_temp_dir("study");
+        for file in [
+            "[Ohys-Raws] Bocchi - 01.mkv",
+            "[Ohys-Raws] Bocchi - 02.mkv",
+            "Bocchi - 01.srt",
+            "Bocchi - 02.ja.srt",
+        ] {
+            fs::write(folder.join(file), "").unwrap();
+        }
+
+        assert_eq!(
+            find_matching_video(&folder.join("Bocchi - 01.srt")),
+            Some(folder.join("[Ohys-Raws] Bocchi - 01.mkv"))
+        );
+        assert_eq!(
+            find_matching_video(&folder.join("Bocchi - 02.ja.srt")),
+            Some(folder.join("[Ohys-Raws] Bocchi - 02.mkv"))
+        );
+
+        fs::remove_dir_all(&folder).unwrap();
+    }
+}
@@ -0,0 +1,175 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use derive_getters::Getters;
+use error_stack::{IntoReport, Result, ResultExt};
+
+use super::{shown_cues, text, StudyError};
+use crate::core::episode::{parse_episode_number, parse_series_name};
+
+#[derive(Debug, Clone, PartialEq, Eq, Getters)]
+pub struct SubtitleLine {
+    path: PathBuf,
+    series: String,
+    episode: Option<u32>,
+    start: i64,
+    text: String,
+}
+
+// Every cue of the indexed subtitles, searchable by its text or by the reading of its furigana.
+// The lines are found through the pairs of characters of their normalized text
+#[derive(Debug, Default)]
+pub struct SubtitleIndex {
+    lines: Vec<SubtitleLine>,
+    // The normalized text and, when the cue has furigana, its normalized reading
+    normalized: Vec<Vec<String>>,
+    bigrams: HashMap<(char, char), Vec<usize>>,
+}
+
+impl SubtitleIndex {
+    pub fn new() -> Self {
+        SubtitleIndex::default()
+    }
+
+    pub fn build(subtitles: &[PathBuf]) -> Result<Self, StudyError> {
+        let mut index = SubtitleIndex::new();
+
+        for subtitle in subtitles {
+            let content = fs::read(subtitle)
+                .into_report()
+                .attach_printable_lazy(|| {
+                    format!("Failed to read subtitles: {}", subtitle.display())
+                })
+                .change_context(StudyError)?;
+            index.add(subtitle, &String::from_utf8_lossy(&content));
+        }
+
+        Ok(index)
+    }
+
+    // Subtitles saved flat in the subs folder carry the series in their name, the others
+    // take it from their folder
+    pub fn add(&mut self, path: &Path, content: &str) {
+        let file_name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let series = parse_series_name(&file_name)
+            .or_else(|| {
+                path.parent()?
+                    .file_name()
+                    .map(|folder| folder.to_string_lossy().into_owned())
+            })
+            .unwrap_or_else(|| file_name.clone());
+        // e.g. "Lycoris Recoil/03.srt"
+        let episode = parse_episode_number(&file_name)
+            .or_else(|| path.file_stem()?.to_str()?.trim().parse().ok());
+
+        for (cue, cue_text) in shown_cues(content) {
+            let line_text = text::clean_text(&cue_text);
+
+            let mut forms = vec![text::normalize(&line_text)];
+            let reading = text::normalize(&text::reading_text(&cue_text));
+            if reading != forms[0] {
+                forms.push(reading);
+            }
+
+            let id = self.lines.len();
+            let mut line_bigrams: Vec<(char, char)> =
+                forms.iter().flat_map(|form| bigrams(form)).collect();
+            line_bigrams.sort_unstable();
+            line_bigrams.dedup();
+            for bigram in line_bigrams {
+                self.bigrams.entry(bigram).or_default().push(id);
+            }
+
+            self.lines.push(SubtitleLine {
+                path: path.to_path_buf(),
+                series: series.clone(),
+                episode,
+                start: cue.start,
+                text: line_text,
+            });
+            self.normalized.push(forms);
+        }
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.lines.len()
+    }
+
+    // The lines containing the query, in the order they were indexed
+    pub fn search(&self, query: &str) -> Vec<&SubtitleLine> {
+        let query = text::normalize(query);
+        if query.is_empty() {
+            return Vec::new();
+        }
+
+        let query_bigrams = bigrams(&query);
+        let candidates: Vec<usize> = if query_bigrams.is_empty() {
+            (0..self.lines.len()).collect()
+        } else {
+            // Only the lines with the rarest pair of the query need to be checked
+            let rarest = query_bigrams
+                .iter()
+                .map(|bigram| self.bigrams.get(bigram).map_or(&[][..], Vec::as_slice))
+                .min_by_key(|ids| ids.len())
+                .unwrap_or(&[]);
+            rarest.to_vec()
+        };
+
+        candidates
+            .into_iter()
+            .filter(|&id| self.normalized[id].iter().any(|form| form.contains(&query)))
+            .map(|id| &self.lines[id])
+            .collect()
+    }
+}
+
+fn bigrams(text: &str) -> Vec<(char, char)> {
+    let chars: Vec<char> = text.chars().collect();
+    chars.windows(2).map(|pair| (pair[0], pair[1])).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SRT: &str = "1\n00:00:01,000 --> 00:00:03,000\n後藤(ごとう)ひとりです\n\n2\n00:03:12,500 --> 00:03:14,000\nギター、弾けるの？\n";
+
+    fn index() -> SubtitleIndex {
+        let mut index = SubtitleIndex::new();
+        index.add(Path::new("subs/[Kamigami] Bocchi the Rock - 01.srt"), SRT);
+        index.add(
+            Path::new("subs/Lycoris Recoil/03.srt"),
+            "1\n00:00:05,000 --> 00:00:06,000\nｷﾞﾀｰ？\n",
+        );
+        index
+    }
+
+    #[test]
+    fn test_search() {
+        let index = index();
+        let found = index.search("ぎたー");
+
+        assert_eq!(index.line_count(), 3);
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[0].series(), "Bocchi the Rock");
+        assert_eq!(found[0].episode(), &Some(1));
+        assert_eq!(found[0].start(), &192_500);
+        assert_eq!(found[0].text(), "ギター、弾けるの？");
+        assert_eq!(found[1].series(), "Lycoris Recoil");
+        assert_eq!(found[1].episode(), &Some(3));
+    }
+
+    #[test]
+    fn test_search_by_reading() {
+        let index = index();
+
+        assert_eq!(index.search("ゴトウ")[0].text(), "後藤ひとりです");
+        assert_eq!(index.search("後藤ひとり").len(), 1);
+        assert!(index.search("ひとりぼっち").is_empty());
+        assert_eq!(index.search("弾").len(), 1);
+    }
+}
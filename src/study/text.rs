@@ -0,0 +1,150 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::core::title::{fold_width, to_hiragana};
+
+lazy_static! {
+    // {\an8}, {\fad(200,200)\b1} and other ASS override blocks
+    static ref ASS_OVERRIDE_REGEX: Regex = create_text_regex(r"\{[^}]*\}");
+    // Signs drawn with vector commands, they have no text to study
+    static ref ASS_DRAWING_REGEX: Regex = create_text_regex(r"\{[^}]*\\p[1-9][^}]*\}");
+    // <ruby>漢字<rp>(</rp><rt>かんじ</rt><rp>)</rp></ruby>, captured as the base and the reading
+    static ref RUBY_REGEX: Regex = create_text_regex(
+        r"<ruby>(?:<rb>)?([^<]*)(?:</rb>)?(?:<rp>[^<]*</rp>)?<rt>([^<]*)</rt>(?:<rp>[^<]*</rp>)?</ruby>"
+    );
+    static ref HTML_TAG_REGEX: Regex = create_text_regex(r"</?[a-zA-Z][^>]*>");
+    // 漢字（かんじ） or 漢字(かんじ), captured the same way
+    static ref FURIGANA_REGEX: Regex = create_text_regex(
+        r"(\p{Han}[\p{Han}々〆ヶ]*)[（(]([\p{Hiragana}\p{Katakana}ー]+)[）)]"
+    );
+    static ref SENTENCE_END_REGEX: Regex = create_text_regex(r"[^。！？!?…]+[。！？!?…]*");
+    // Runs of kanji, or of two katakana or more
+    static ref WORD_REGEX: Regex =
+        create_text_regex(r"\p{Han}[\p{Han}々〆ヶ]*|\p{Katakana}[\p{Katakana}ー]+");
+}
+
+fn create_text_regex(regex: &str) -> Regex {
+    match Regex::new(regex) {
+        Ok(regex) => regex,
+        Err(error) => panic!("Failed to create regex for subtitle text, error: {}", error),
+    }
+}
+
+// The text of a cue without markup, furigana or line breaks. Drawings become empty
+pub fn clean_text(text: &str) -> String {
+    strip_markup(text, "$1")
+}
+
+// Like `clean_text`, but the words with furigana are written with their reading instead,
+// e.g. "ごとうです" for "後藤(ごとう)です"
+pub fn reading_text(text: &str) -> String {
+    strip_markup(text, "$2")
+}
+
+fn strip_markup(text: &str, furigana_replacement: &str) -> String {
+    if ASS_DRAWING_REGEX.is_match(text) {
+        return String::new();
+    }
+
+    let text = ASS_OVERRIDE_REGEX.replace_all(text, "");
+    let text = RUBY_REGEX.replace_all(&text, furigana_replacement);
+    let text = HTML_TAG_REGEX.replace_all(&text, "");
+    let text = FURIGANA_REGEX.replace_all(&text, furigana_replacement);
+
+    // Line breaks only fit the text on screen, Japanese doesn't separate words with spaces
+    text.replace("\\N", "\n")
+        .replace("\\n", "\n")
+        .replace("\\h", " ")
+        .lines()
+        .map(str::trim)
+        .collect::<Vec<_>>()
+        .join("")
+        .trim()
+        .to_owned()
+}
+
+// Splits after 。！？ and the like, keeping the punctuation with its sentence
+pub fn split_sentences(text: &str) -> Vec<String> {
+    SENTENCE_END_REGEX
+        .find_iter(text)
+        .map(|sentence| sentence.as_str().trim().to_owned())
+        .filter(|sentence| !sentence.is_empty())
+        .collect()
+}
+
+// Without a dictionary words are approximated by runs of kanji or katakana
+pub fn find_words(text: &str) -> Vec<String> {
+    WORD_REGEX
+        .find_iter(text)
+        .map(|word| word.as_str().to_owned())
+        .collect()
+}
+
+// Folds the differences that don't matter when searching: full and half width characters,
+// katakana and hiragana, letter case, iteration marks (時々 is 時時) and spaces
+pub fn normalize(text: &str) -> String {
+    let mut normalized: Vec<char> = Vec::with_capacity(text.len());
+
+    for c in fold_width(text).chars().map(to_hiragana) {
+        match c {
+            '々' => {
+                if let Some(&last) = normalized.last() {
+                    normalized.push(last);
+                }
+            }
+            c if c.is_whitespace() => (),
+            c => normalized.extend(c.to_lowercase()),
+        }
+    }
+
+    normalized.into_iter().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clean_text() {
+        assert_eq!(
+            clean_text(r"{\an8}{\fad(200,200)}後藤(ごとう)ひとりです\N{\i1}よろしく{\i0}"),
+            "後藤ひとりですよろしく"
+        );
+        assert_eq!(
+            clean_text("<i><ruby>結束<rp>(</rp><rt>けっそく</rt><rp>)</rp></ruby>バンド</i>"),
+            "結束バンド"
+        );
+        assert_eq!(clean_text(r"{\p1}m 0 0 l 100 0 100 100{\p0}"), "");
+    }
+
+    #[test]
+    fn test_reading_text() {
+        assert_eq!(
+            reading_text(r"{\an8}後藤(ごとう)ひとりです\N<ruby>結束<rt>けっそく</rt></ruby>バンド"),
+            "ごとうひとりですけっそくバンド"
+        );
+    }
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(normalize("ギター　ＨＥＲＯ"), "ぎたーhero");
+        assert_eq!(normalize("ｷﾞﾀｰﾋｰﾛｰ ﾊﾟﾝ"), "ぎたーひーろーぱん");
+        assert_eq!(normalize("時々"), "時時");
+    }
+
+    #[test]
+    fn test_split_sentences() {
+        assert_eq!(
+            split_sentences("ライブ、楽しかった！また来てね。えっ…"),
+            vec!["ライブ、楽しかった！", "また来てね。", "えっ…"]
+        );
+    }
+
+    #[test]
+    fn test_find_words() {
+        assert_eq!(
+            find_words("後藤ひとりはギターが上手い"),
+            vec!["後藤", "ギター", "上手"]
+        );
+    }
+}
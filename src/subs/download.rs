@@ -7,15 +7,18 @@ use crate::core::download::downloader::{
     Destination, FileDownloader, FileDownloaderError, StringDownloaderError, Uri,
 };
 use crate::core::download::prelude::*;
+use crate::core::extensions::{ARCHIVE_EXTENSIONS, SUBTITLE_EXTENSIONS};
 use crate::core::indexer::Indexer;
+use crate::subs::language::SubLanguage;
 
-use error_stack::{IntoReport, Result, ResultExt};
+use error_stack::{IntoReport, Report, Result, ResultExt};
 
-const DEFAULT_FOLDER: &str = "subs";
+pub const DEFAULT_FOLDER: &str = "subs";
 const DEFAULT_EXTENSION: &str = "srt";
 pub struct AnimeSubsDownloader {
     inner_downloader: Box<dyn StringDownloader>,
     default_folder: String,
+    language: SubLanguage,
 }
 
 impl AnimeSubsDownloader {
@@ -23,14 +26,22 @@ impl AnimeSubsDownloader {
         AnimeSubsDownloader {
             inner_downloader: Box::new(ReqwestDownloader::new()),
             default_folder: DEFAULT_FOLDER.to_string(),
+            language: SubLanguage::default(),
         }
     }
 
-    fn create_file_path(
+    // The language the saved files are tagged with, e.g. "ep01.en.ass"
+    pub fn with_language(mut self, language: SubLanguage) -> Self {
+        self.language = language;
+        self
+    }
+
+    // The folder and basename of a file saved to the destination
+    fn destination_file_base(
         &self,
         destination: &Destination,
         file_basename_hint: Option<&str>,
-    ) -> PathBuf {
+    ) -> String {
         let folder_name = match destination {
             Destination::GivenFolderGivenFileBasename(folder, _) => folder,
             Destination::GivenFolderGuessFileBasename(folder) => folder,
@@ -54,9 +65,46 @@ impl AnimeSubsDownloader {
             None => generate_random_file_basename(),
         };
 
-        let file_name = format!("{}.{}", file_basename, DEFAULT_EXTENSION);
-        let file_path = format!("{}/{}", folder_name, file_name);
-        PathBuf::from(file_path)
+        format!("{}/{}", folder_name, file_basename)
+    }
+
+    fn create_file_path(
+        &self,
+        destination: &Destination,
+        file_basename_hint: Option<&str>,
+        extension: &str,
+    ) -> PathBuf {
+        let file_base = self.destination_file_base(destination, file_basename_hint);
+        PathBuf::from(format!(
+            "{}.{}.{}",
+            file_base,
+            self.language.code(),
+            extension
+        ))
+    }
+
+    // Where download_indexer_to_file saves the subtitles of the indexer. They keep their
+    // format, the extension only defaults to srt for names without a subtitle one
+    pub fn indexer_file_path(&self, indexer: &Indexer, destination: &Destination) -> PathBuf {
+        let (file_basename, extension) = split_subtitle_extension(indexer.name());
+        self.create_file_path(destination, Some(file_basename), extension)
+    }
+
+    // The subtitles of the indexer if they were already saved, also under the name they had
+    // before being tagged with their language, when only Japanese ones were downloaded
+    pub fn saved_file_path(&self, indexer: &Indexer, destination: &Destination) -> Option<PathBuf> {
+        let file_path = self.indexer_file_path(indexer, destination);
+        if file_path.exists() {
+            return Some(file_path);
+        }
+
+        let legacy_file_path = PathBuf::from(format!(
+            "{}.{}",
+            self.destination_file_base(destination, Some(indexer.name())),
+            DEFAULT_EXTENSION
+        ));
+        (self.language == SubLanguage::Japanese && legacy_file_path.exists())
+            .then_some(legacy_file_path)
     }
 
     fn save(&self, content: &String, path: &Path) -> Result<(), std::io::Error> {
@@ -93,6 +141,7 @@ impl StringDownloader for AnimeSubsDownloader {
         self.inner_downloader.download_indexer(indexer)
     }
 
+    #[allow(deprecated)]
     fn download_indexers(
         &self,
         indexers: &[Indexer],
@@ -102,6 +151,25 @@ impl StringDownloader for AnimeSubsDownloader {
     }
 }
 
+// Archives can't be extracted, saved as they are they would only be broken subtitles
+pub fn is_archive(name: &str) -> bool {
+    name.rsplit_once('.').is_some_and(|(_, extension)| {
+        ARCHIVE_EXTENSIONS.contains(&extension.to_lowercase().as_str())
+    })
+}
+
+// "ep01.ass" is "ep01" and "ass", other names keep their suffix and get the default extension
+fn split_subtitle_extension(name: &str) -> (&str, &str) {
+    match name.rsplit_once('.') {
+        Some((basename, extension))
+            if SUBTITLE_EXTENSIONS.contains(&extension.to_lowercase().as_str()) =>
+        {
+            (basename, extension)
+        }
+        _ => (name, DEFAULT_EXTENSION),
+    }
+}
+
 // Generate a random file name in the following format: "20230213-123123.ext"
 fn generate_random_file_basename() -> String {
     let mut rng = rand::thread_rng();
@@ -120,7 +188,7 @@ impl FileDownloader for AnimeSubsDownloader {
         uri: &Uri,
         dest: &Destination,
     ) -> Result<(), FileDownloaderError> {
-        let file_path = self.create_file_path(&dest, None);
+        let file_path = self.create_file_path(dest, None, DEFAULT_EXTENSION);
 
         if file_path.exists() {
             log::debug!(
@@ -145,15 +213,19 @@ impl FileDownloader for AnimeSubsDownloader {
         indexer: &Indexer,
         dest: &Destination,
     ) -> Result<(), FileDownloaderError> {
-        let file_path = self.create_file_path(&dest, Some(indexer.name()));
+        if is_archive(indexer.name()) {
+            return Err(Report::new(FileDownloaderError)
+                .attach_printable(format!("Not a subtitle file: {}", indexer.name())));
+        }
 
-        if file_path.exists() {
+        if let Some(file_path) = self.saved_file_path(indexer, dest) {
             log::debug!(
                 "File already exists, skipping download: '{}'",
                 file_path.to_str().unwrap_or("invalid path")
             );
             return Ok(());
         }
+        let file_path = self.indexer_file_path(indexer, dest);
 
         let content = self
             .download_indexer(indexer)
@@ -167,6 +239,7 @@ impl FileDownloader for AnimeSubsDownloader {
             .change_context(FileDownloaderError)
     }
 
+    #[allow(deprecated)]
     fn download_indexers_to_file(
         &self,
         indexers: &[Indexer],
@@ -183,28 +256,34 @@ impl FileDownloader for AnimeSubsDownloader {
         log::debug!("Saving downloaded subtitles to files");
         // TODO: async code (both download and save)
         for (indexer, content) in indexers.iter().zip(content.iter()) {
-            let file_path = self.create_file_path(&dest, Some(indexer.name()));
+            if is_archive(indexer.name()) {
+                log::warn!("Not a subtitle file, skipping save: '{}'", indexer.name());
+                continue;
+            }
 
-            if file_path.exists() {
+            if let Some(file_path) = self.saved_file_path(indexer, dest) {
                 log::debug!(
                     "File already exists, skipping save: '{}'", //TODO: also skip download (if possible)
                     file_path.to_str().unwrap_or("invalid path")
                 );
                 continue;
             }
+            let file_path = self.indexer_file_path(indexer, dest);
 
-            self.save(&content, &file_path)
+            self.save(content, &file_path)
                 .attach_printable("Failed to save file to path")
                 .change_context(FileDownloaderError)?;
         }
 
-        return Ok(());
+        Ok(())
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_util::unique_temp_dir;
+    use std::fs;
 
     #[test]
     fn test_generate_random_file_basename() {
@@ -212,14 +291,74 @@ mod tests {
         assert_eq!(file_name.len(), 26);
     }
 
+    #[test]
+    fn test_indexer_file_path() {
+        let indexer = Indexer::new("Bocchi the Rock! 01.ass", "https://kitsunekko.net/ep01");
+        let dest = Destination::GivenFolderGuessFileBasename("test".to_string());
+
+        let subs_downloader = AnimeSubsDownloader::new();
+        assert_eq!(
+            subs_downloader.indexer_file_path(&indexer, &dest),
+            PathBuf::from("test/Bocchi the Rock! 01.ja.ass")
+        );
+
+        let subs_downloader = AnimeSubsDownloader::new().with_language(SubLanguage::English);
+        let dest =
+            Destination::GivenFolderGivenFileBasename("test".to_string(), "ep01".to_string());
+        assert_eq!(
+            subs_downloader.indexer_file_path(&indexer, &dest),
+            PathBuf::from("test/ep01.en.ass")
+        );
+
+        let indexer = Indexer::new("Bocchi the Rock! 01", "https://kitsunekko.net/ep01");
+        assert_eq!(
+            subs_downloader.indexer_file_path(&indexer, &Destination::Default),
+            PathBuf::from("subs/Bocchi the Rock! 01.en.srt")
+        );
+    }
+
+    #[test]
+    fn test_download_rejects_archives() {
+        // Rejected before anything is downloaded
+        let indexer = Indexer::new("Bocchi the Rock! 01-12.zip", "http://127.0.0.1:1/ep01");
+        let subs_downloader = AnimeSubsDownloader::new();
+
+        assert!(subs_downloader
+            .download_indexer_to_file(&indexer, &Destination::Default)
+            .is_err());
+    }
+
+    #[test]
+    fn test_saved_file_path_finds_legacy_name() {
+        let folder = unique_temp_dir("subs-download-legacy");
+        fs::create_dir_all(&folder).unwrap();
+        fs::write(folder.join("Bocchi the Rock! 01.ass.srt"), "").unwrap();
+        let indexer = Indexer::new("Bocchi the Rock! 01.ass", "https://kitsunekko.net/ep01");
+        let dest = Destination::GivenFolderGuessFileBasename(folder.display().to_string());
+
+        assert_eq!(
+            AnimeSubsDownloader::new().saved_file_path(&indexer, &dest),
+            Some(folder.join("Bocchi the Rock! 01.ass.srt"))
+        );
+        assert_eq!(
+            AnimeSubsDownloader::new()
+                .with_language(SubLanguage::English)
+                .saved_file_path(&indexer, &dest),
+            None
+        );
+
+        fs::remove_dir_all(&folder).unwrap();
+    }
+
     #[test]
     fn test_save() {
         let content = "Hello world".to_string();
         let dest = Destination::Default;
         let file_basename_hint = Some("hello");
         let subs_downloader = AnimeSubsDownloader::new();
-        let file_path = subs_downloader.create_file_path(&dest, file_basename_hint);
-        subs_downloader.save(&content, &file_path);
+        let file_path =
+            subs_downloader.create_file_path(&dest, file_basename_hint, DEFAULT_EXTENSION);
+        subs_downloader.save(&content, &file_path).unwrap();
         //TODO: assert file exists (or use a mock)
     }
 
@@ -230,8 +369,9 @@ mod tests {
             Destination::GivenFolderGivenFileBasename("test".to_string(), "hello".to_string());
         let file_basename_hint = Some("hello");
         let subs_downloader = AnimeSubsDownloader::new();
-        let file_path = subs_downloader.create_file_path(&dest, file_basename_hint);
-        subs_downloader.save(&content, &file_path);
+        let file_path =
+            subs_downloader.create_file_path(&dest, file_basename_hint, DEFAULT_EXTENSION);
+        subs_downloader.save(&content, &file_path).unwrap();
         //TODO: assert file exists (or use a mock)
     }
 
@@ -241,8 +381,9 @@ mod tests {
         let dest = Destination::GivenFolderGuessFileBasename("test".to_string());
         let file_basename_hint = None;
         let subs_downloader = AnimeSubsDownloader::new();
-        let file_path = subs_downloader.create_file_path(&dest, file_basename_hint);
-        subs_downloader.save(&content, &file_path);
+        let file_path =
+            subs_downloader.create_file_path(&dest, file_basename_hint, DEFAULT_EXTENSION);
+        subs_downloader.save(&content, &file_path).unwrap();
         //TODO: assert file exists (or use a mock)
     }
 }
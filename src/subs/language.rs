@@ -0,0 +1,89 @@
+use std::path::Path;
+
+use clap::ValueEnum;
+
+// The subtitle languages kitsunekko has a folder for
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default, ValueEnum)]
+pub enum SubLanguage {
+    #[default]
+    #[value(name = "ja", alias = "japanese")]
+    Japanese,
+    #[value(name = "en", alias = "english")]
+    English,
+    #[value(name = "zh", alias = "chinese")]
+    Chinese,
+    #[value(name = "ko", alias = "korean")]
+    Korean,
+}
+
+impl SubLanguage {
+    // The ISO 639-1 code players read from "<video>.<code>.srt"
+    pub fn code(&self) -> &'static str {
+        match self {
+            SubLanguage::Japanese => "ja",
+            SubLanguage::English => "en",
+            SubLanguage::Chinese => "zh",
+            SubLanguage::Korean => "ko",
+        }
+    }
+
+    // The language a subtitle file is tagged with, "ep01.en.srt" is English. Untagged files
+    // (and unknown tags) are Japanese, as everything saved before the tags was
+    pub fn of_subtitle(path: &Path) -> SubLanguage {
+        let tag = path
+            .file_stem()
+            .and_then(|stem| Path::new(stem).extension())
+            .and_then(|tag| tag.to_str());
+
+        SubLanguage::value_variants()
+            .iter()
+            .copied()
+            .find(|language| tag == Some(language.code()))
+            .unwrap_or_default()
+    }
+
+    // English subtitles are at the root of kitsunekko, next to the folders of the others
+    pub fn kitsunekko_dir(&self) -> &'static str {
+        match self {
+            SubLanguage::Japanese => "subtitles/japanese/",
+            SubLanguage::English => "subtitles/",
+            SubLanguage::Chinese => "subtitles/chinese/",
+            SubLanguage::Korean => "subtitles/korean/",
+        }
+    }
+
+    // The folders of the other languages, listed along with the series in the English one
+    pub fn is_language_dir(name: &str) -> bool {
+        ["japanese", "chinese", "korean"].contains(&name.trim().to_lowercase().as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_language() {
+        assert_eq!(
+            SubLanguage::from_str("ja", false),
+            Ok(SubLanguage::Japanese)
+        );
+        assert_eq!(
+            SubLanguage::from_str("english", false),
+            Ok(SubLanguage::English)
+        );
+        assert!(SubLanguage::from_str("fr", false).is_err());
+    }
+
+    #[test]
+    fn test_of_subtitle() {
+        let of_subtitle = |path| SubLanguage::of_subtitle(Path::new(path));
+
+        assert_eq!(of_subtitle("ep01.en.srt"), SubLanguage::English);
+        assert_eq!(of_subtitle("ep01.ja.ass"), SubLanguage::Japanese);
+        assert_eq!(
+            of_subtitle("Bocchi.the.Rock.S01E01.srt"),
+            SubLanguage::Japanese
+        );
+    }
+}
@@ -1,3 +1,4 @@
 pub mod search;
 pub mod download;
-pub mod save;
\ No newline at end of file
+pub mod save;
+pub mod language;
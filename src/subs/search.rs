@@ -1,11 +1,17 @@
+use derive_getters::Getters;
 use fuzzy_matcher::{skim::SkimMatcherV2, FuzzyMatcher};
 use lazy_static::lazy_static;
 use priority_queue::PriorityQueue;
 use regex::Regex;
+use std::collections::HashSet;
 use std::{error::Error};
 
-use crate::core::scrapper::{self, HttpScrapper};
+use crate::core::aliases::TitleAliases;
 use crate::core::indexer::Indexer;
+use crate::core::scrapper::{self, HttpScrapper};
+use crate::core::season::{parse_season, parse_season_among};
+use crate::core::title::normalize_title;
+use crate::subs::language::SubLanguage;
 
 lazy_static! {
     static ref MAGNET_REGEX: Regex = match Regex::new(
@@ -14,6 +20,13 @@ lazy_static! {
         Ok(regex) => regex,
         Err(error) => panic!("Failed to create regex for magnet link, error: {}", error),
     };
+    // Folder rows are the same as file rows, only their link tells them apart
+    static ref LISTING_REGEX: Regex = match Regex::new(
+        r#"<tr><td(?: colspan="2")?><a href="([^"]+).+?<strong>([^<]+)"#,
+    ) {
+        Ok(regex) => regex,
+        Err(error) => panic!("Failed to create regex for kitsunekko listing, error: {}", error),
+    };
 }
 
 impl scrapper::ScrapperData for Indexer {
@@ -26,61 +39,266 @@ impl scrapper::ScrapperData for Indexer {
             .map(|capture| {
                 log::trace!(target: "Indexer", "Capture: {:?}", capture);
                 let name = capture[2].to_string();
-                let uri = format!("https://kitsunekko.net/{}", capture[1].to_string());
+                let uri = format!("https://kitsunekko.net/{}", &capture[1]);
                 Indexer::new(name.as_str(), uri.as_str())
             })
             .collect()
     }
 }
 
-pub fn fetch_indexers() -> Result<Vec<Indexer>, Box<dyn Error>> {
-    const ANIME_LIST_URL: &str = "https://kitsunekko.net/dirlist.php?dir=subtitles%2Fjapanese%2F";
+pub fn fetch_indexers(language: SubLanguage) -> Result<Vec<Indexer>, Box<dyn Error>> {
+    const ANIME_LIST_URL: &str = "https://kitsunekko.net/dirlist.php?dir=";
     let regex = Regex::new(r#"<tr><td colspan="2"><a href="/([^"]+).+?<strong>([^<]+)"#).unwrap();
 
-    return HttpScrapper::<Indexer>::new(regex).scrap_page(ANIME_LIST_URL);
+    let url = format!(
+        "{}{}",
+        ANIME_LIST_URL,
+        language.kitsunekko_dir().replace('/', "%2F")
+    );
+    let indexers = HttpScrapper::<Indexer>::new(regex).scrap_page(&url)?;
+    Ok(indexers
+        .into_iter()
+        .filter(|indexer| !SubLanguage::is_language_dir(indexer.name()))
+        .collect())
 }
 
-pub fn fuzzy_match_indexers(anime_name: &str, indexes: Vec<Indexer>) -> Vec<Indexer> {
-    let matcher = SkimMatcherV2::default();
-    let mut matches = PriorityQueue::new();
-    for index in indexes {
-        let score = if index.name().to_lowercase() == anime_name.to_lowercase() {
-            Some(std::i64::MAX)
-        } else {
-            matcher.fuzzy_match(&index.name().to_lowercase(), &anime_name.to_lowercase())
+// Added to exact matches of the normalized titles, more than any fuzzy match scores
+pub const EXACT_MATCH_BONUS: i64 = 100_000;
+
+// Below this score the best match is likely another series and isn't picked on its own.
+// Partial names (e.g. "Bocchi") score over 100, letters scattered in a title (e.g. "btr")
+// around 50
+pub const DEFAULT_MIN_SCORE: i64 = 60;
+
+// A kitsunekko folder and how its name scored against the anime name
+#[derive(Debug, Clone, Hash, PartialEq, Eq, Getters)]
+pub struct IndexerMatch {
+    indexer: Indexer,
+    // The normalized titles compared, without their season markers
+    normalized_name: String,
+    matched_title: String,
+    skim_score: i64,
+    exact_bonus: i64,
+    length_penalty: i64,
+    // None when the folder names another season than the one asked for
+    season_score: Option<i64>,
+}
+
+impl IndexerMatch {
+    pub fn score(&self) -> i64 {
+        self.skim_score + self.exact_bonus - self.length_penalty
+    }
+
+    pub fn into_indexer(self) -> Indexer {
+        self.indexer
+    }
+}
+
+impl std::fmt::Display for IndexerMatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let season = match self.season_score {
+            Some(score) => score.to_string(),
+            None => "mismatch".to_owned(),
         };
+        write!(
+            f,
+            "{} (\"{}\" vs \"{}\"): score {} = skim {} + exact {} - length {}, season {}",
+            self.indexer.name(),
+            self.normalized_name,
+            self.matched_title,
+            self.score(),
+            self.skim_score,
+            self.exact_bonus,
+            self.length_penalty,
+            season
+        )
+    }
+}
+
+// Compares the normalized titles, trying each alias of the anime and keeping its best score.
+// Season markers are left out of the titles and scored apart: folders naming another season
+// (or an OVA or movie when a series is asked) are dropped, unless no folder is left at all
+pub fn fuzzy_match_indexers(
+    anime_name: &str,
+    season: Option<u32>,
+    aliases: &TitleAliases,
+    indexes: Vec<Indexer>,
+) -> Vec<IndexerMatch> {
+    let matcher = SkimMatcherV2::default();
+    let anime_name = normalize_title(anime_name);
+    let indexes: Vec<(Indexer, String)> = indexes
+        .into_iter()
+        .map(|index| {
+            let index_name = normalize_title(index.name());
+            (index, index_name)
+        })
+        .collect();
+    // A bare trailing number is only read as a season when the title without it names another
+    // folder. The anime name may be partial ("Bocchi 2"), a folder starting with it will do
+    let index_titles: HashSet<String> = indexes
+        .iter()
+        .map(|(_, index_name)| parse_season(index_name).0)
+        .collect();
+    let (anime_title, mut asked) = parse_season_among(&anime_name, |bare| {
+        index_titles
+            .iter()
+            .any(|title| title.starts_with(bare) && !title.starts_with(&anime_name))
+    });
+    if let Some(season) = season {
+        asked.season = Some(season);
+        asked.sequel = season > 1;
+    }
+    let anime_names: Vec<String> = aliases
+        .expand(&anime_title)
+        .iter()
+        .map(|name| parse_season(&normalize_title(name)).0)
+        .collect();
+
+    let mut scored = Vec::new();
+    for (index, index_name) in indexes {
+        let (index_name, index_season) =
+            parse_season_among(&index_name, |bare| index_titles.contains(bare));
+        let season_score = asked.agreement(&index_season);
+        let best = anime_names
+            .iter()
+            .filter_map(|anime_name| {
+                let skim_score = matcher.fuzzy_match(&index_name, anime_name)?;
+                let exact = index_name == *anime_name;
+                Some(IndexerMatch {
+                    indexer: index.clone(),
+                    normalized_name: index_name.clone(),
+                    matched_title: anime_name.clone(),
+                    skim_score,
+                    exact_bonus: if exact { EXACT_MATCH_BONUS } else { 0 },
+                    length_penalty: (index_name.len() as i64 - anime_name.len() as i64).abs(),
+                    season_score,
+                })
+            })
+            .max_by_key(|index_match| index_match.score());
 
-        match score {
-            Some(score) => {
-                let size_diff = (index.name().len() as i64 - anime_name.len() as i64).abs();
-                let score = score as i64 - size_diff;
-                matches.push(index, score);
-            }
-            None => continue,
+        if let Some(best) = best {
+            scored.push(best);
+        }
+    }
+
+    let season_matches = scored
+        .iter()
+        .any(|index_match| index_match.season_score.is_some());
+    let mut matches = PriorityQueue::new();
+    for index_match in scored {
+        let title_score = index_match.score();
+        match (index_match.season_score, season_matches) {
+            (Some(season_score), _) => matches.push(index_match, (season_score, title_score)),
+            (None, false) => matches.push(index_match, (0, title_score)),
+            (None, true) => continue,
         };
     }
 
-    return matches.into_sorted_vec();
+    matches.into_sorted_vec()
 }
 
 pub fn fetch_best_indexers_for(
     anime_name: &str,
-) -> Result<Vec<Indexer>, Box<dyn Error>> {
-    let indexers = fetch_indexers()?;
-    let sorted_indexers = fuzzy_match_indexers(anime_name, indexers);
-    return Ok(sorted_indexers);
+    season: Option<u32>,
+    language: SubLanguage,
+    aliases: &TitleAliases,
+) -> Result<Vec<IndexerMatch>, Box<dyn Error>> {
+    let indexers = fetch_indexers(language)?;
+    let sorted_indexers = fuzzy_match_indexers(anime_name, season, aliases, indexers);
+    Ok(sorted_indexers)
+}
+
+// How deep fetch_sub_files goes into the subfolders of a series (seasons, BD and TV
+// releases, fansubs...)
+pub const MAX_FOLDER_DEPTH: usize = 3;
+
+// A row of a kitsunekko folder, subfolders link to their own listing instead of a file
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ListingEntry {
+    indexer: Indexer,
+    is_folder: bool,
+}
+
+impl scrapper::ScrapperData for ListingEntry {
+    fn from_captures(capture: regex::CaptureMatches) -> Vec<Self>
+    where
+        Self: Sized,
+    {
+        capture
+            .map(|capture| {
+                let href = &capture[1];
+                let is_folder = href.contains("dirlist.php");
+                // Files keep the URI they always had, the watch state remembers them by it
+                let uri = match is_folder {
+                    true => format!("https://kitsunekko.net/{}", href.trim_start_matches('/')),
+                    false => format!("https://kitsunekko.net/{}", href),
+                };
+                ListingEntry {
+                    indexer: Indexer::new(capture[2].trim(), &uri),
+                    is_folder,
+                }
+            })
+            .collect()
+    }
+}
+
+type Listing = Result<Vec<ListingEntry>, Box<dyn Error>>;
+
+fn fetch_listing(folder: &Indexer) -> Listing {
+    HttpScrapper::<ListingEntry>::new(LISTING_REGEX.clone()).scrap_page(folder.uri())
 }
 
-pub fn fetch_sub_files(anime_indexer: &Indexer) -> Result<Vec<Indexer>, Box<dyn Error>>{
-    let url = anime_indexer.uri().clone();
-    let regex = Regex::new(r#"<tr><td><a href="([^"]+).+?<strong>([^<]+)"#).unwrap();
-    let sub_files = HttpScrapper::<Indexer>::new(regex).scrap_page(&url);
-    return sub_files;
+// Every subtitle file of the series, those of subfolders named by their path in it
+// (e.g. "BD/Bocchi 01.ass") so they are saved in the same folders
+pub fn fetch_sub_files(anime_indexer: &Indexer) -> Result<Vec<Indexer>, Box<dyn Error>> {
+    collect_sub_files(anime_indexer, "", MAX_FOLDER_DEPTH, &fetch_listing)
+}
+
+// The files of the folder come first, then those of its subfolders. A subfolder failing to
+// load is only logged, it doesn't lose the files already found
+fn collect_sub_files(
+    folder: &Indexer,
+    prefix: &str,
+    depth: usize,
+    list: &dyn Fn(&Indexer) -> Listing,
+) -> Result<Vec<Indexer>, Box<dyn Error>> {
+    let (folders, files): (Vec<_>, Vec<_>) =
+        list(folder)?.into_iter().partition(|entry| entry.is_folder);
+
+    let mut sub_files: Vec<Indexer> = files
+        .into_iter()
+        .map(|file| {
+            Indexer::new(
+                &format!("{}{}", prefix, file.indexer.name()),
+                file.indexer.uri(),
+            )
+        })
+        .collect();
+
+    // Only links below the folder, the listing may link back to its parent
+    let subfolders = folders.into_iter().filter(|entry| {
+        entry.indexer.uri().starts_with(folder.uri()) && entry.indexer.uri() != folder.uri()
+    });
+    for subfolder in subfolders {
+        let name = format!("{}{}/", prefix, subfolder.indexer.name());
+        if depth == 0 {
+            log::debug!("Not going deeper into kitsunekko folder: {}", name);
+            continue;
+        }
+
+        match collect_sub_files(&subfolder.indexer, &name, depth - 1, list) {
+            Ok(files) => sub_files.extend(files),
+            Err(e) => log::warn!("Failed to fetch kitsunekko folder {}: {}", name, e),
+        }
+    }
+
+    Ok(sub_files)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::collections::HashMap;
 
     macro_rules! bocchi_the_mock {
         () => {
@@ -99,10 +317,10 @@ mod tests {
 
     #[test]
     fn test_fetch_indexers() {
-        let anime_list = fetch_indexers().unwrap();
-        assert!(anime_list.len() > 0);
-        assert!(anime_list.get(0).unwrap().name().len() > 0);
-        assert!(anime_list.get(0).unwrap().uri().len() > 0);
+        let anime_list = fetch_indexers(SubLanguage::Japanese).unwrap();
+        assert!(!anime_list.is_empty());
+        assert!(!anime_list.first().unwrap().name().is_empty());
+        assert!(!anime_list.first().unwrap().uri().is_empty());
 
         let contains_bocchi = anime_list
             .iter()
@@ -113,38 +331,280 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_collect_sub_files_nested() {
+        const SERIES: &str =
+            "https://kitsunekko.net/dirlist.php?dir=subtitles%2Fjapanese%2FBocchi%2F";
+        const BD: &str =
+            "https://kitsunekko.net/dirlist.php?dir=subtitles%2Fjapanese%2FBocchi%2FBD%2F";
+        const EXTRAS: &str =
+            "https://kitsunekko.net/dirlist.php?dir=subtitles%2Fjapanese%2FBocchi%2FBD%2FExtras%2F";
+        let listings = HashMap::from([
+            (
+                SERIES,
+                r#"
+                <tr><td colspan="2"><a href="/dirlist.php?dir=subtitles%2Fjapanese%2F" class=""><strong>Parent</strong></a></td></tr>
+                <tr><td><a href="/dirlist.php?dir=subtitles%2Fjapanese%2FBocchi%2FBD%2F" class=""><strong>BD</strong></a></td></tr>
+                <tr><td><a href="/subtitles/japanese/Bocchi/Bocchi 01.srt" class=""><strong>Bocchi 01.srt </strong></a></td></tr>
+            "#,
+            ),
+            (
+                BD,
+                r#"
+                <tr><td><a href="/dirlist.php?dir=subtitles%2Fjapanese%2FBocchi%2FBD%2FExtras%2F" class=""><strong>Extras</strong></a></td></tr>
+                <tr><td><a href="/subtitles/japanese/Bocchi/BD/Bocchi 01.ass" class=""><strong>Bocchi 01.ass</strong></a></td></tr>
+            "#,
+            ),
+            (
+                EXTRAS,
+                r#"
+                <tr><td><a href="/subtitles/japanese/Bocchi/BD/Extras/Bocchi OVA.ass" class=""><strong>Bocchi OVA.ass</strong></a></td></tr>
+            "#,
+            ),
+        ]);
+        let list = |folder: &Indexer| -> Listing {
+            HttpScrapper::<ListingEntry>::new(LISTING_REGEX.clone())
+                .scrap_raw_data(listings[folder.uri()])
+        };
+        let series = Indexer::new("Bocchi", SERIES);
+
+        let sub_files = collect_sub_files(&series, "", MAX_FOLDER_DEPTH, &list).unwrap();
+        assert_eq!(
+            sub_files,
+            vec![
+                Indexer::new(
+                    "Bocchi 01.srt",
+                    "https://kitsunekko.net//subtitles/japanese/Bocchi/Bocchi 01.srt"
+                ),
+                Indexer::new(
+                    "BD/Bocchi 01.ass",
+                    "https://kitsunekko.net//subtitles/japanese/Bocchi/BD/Bocchi 01.ass"
+                ),
+                Indexer::new(
+                    "BD/Extras/Bocchi OVA.ass",
+                    "https://kitsunekko.net//subtitles/japanese/Bocchi/BD/Extras/Bocchi OVA.ass"
+                ),
+            ]
+        );
+
+        let sub_files = collect_sub_files(&series, "", 1, &list).unwrap();
+        assert_eq!(sub_files.len(), 2);
+    }
+
     #[test]
     fn test_fuzzy_match_indexers_exact_match_season_1() {
         let mock_anime_list = bocchi_the_mock!();
 
-        let matches = fuzzy_match_indexers("Bocchi the Rock!", mock_anime_list);
+        let matches = fuzzy_match_indexers(
+            "Bocchi the Rock!",
+            None,
+            &TitleAliases::builtin(),
+            mock_anime_list,
+        );
         assert_eq!(matches.len(), 2);
-        assert_eq!(matches[0].name(), "Bocchi the Rock!");
-        assert_eq!(matches[1].name(), "Bocchi the Rock! 2");
+        assert_eq!(matches[0].indexer().name(), "Bocchi the Rock!");
+        assert_eq!(matches[1].indexer().name(), "Bocchi the Rock! 2");
     }
 
     #[test]
     fn test_fetch_best_indexers_for_relife() {
-        let matches = fetch_best_indexers_for("relife").unwrap();
+        let matches = fetch_best_indexers_for(
+            "relife",
+            None,
+            SubLanguage::Japanese,
+            &TitleAliases::builtin(),
+        )
+        .unwrap();
         assert!(matches.len() > 2);
-        assert_eq!(matches[0].name(), "ReLIFE");
-        assert_eq!(matches[1].name(), "ReLife Kanketsu Hen");
+        assert_eq!(matches[0].indexer().name(), "ReLIFE");
+        assert_eq!(matches[1].indexer().name(), "ReLife Kanketsu Hen");
     }
 
     #[test]
     fn test_fuzzy_match_indexers_incomplete_equal_score() {
         let mock_anime_list = bocchi_the_mock!();
-        let matches = fuzzy_match_indexers("Bocchi", mock_anime_list);
+        let matches =
+            fuzzy_match_indexers("Bocchi", None, &TitleAliases::builtin(), mock_anime_list);
         assert_eq!(matches.len(), 2);
-        assert_eq!(matches[0].name(), "Bocchi the Rock!");
-        assert_eq!(matches[1].name(), "Bocchi the Rock! 2");
+        assert_eq!(matches[0].indexer().name(), "Bocchi the Rock!");
+        assert_eq!(matches[1].indexer().name(), "Bocchi the Rock! 2");
     }
 
     #[test]
     fn test_fuzzy_match_indexers_incomplete_better_score_exclusive() {
         let mock_anime_list = bocchi_the_mock!();
-        let matches = fuzzy_match_indexers("Bocchi 2", mock_anime_list);
+        let matches =
+            fuzzy_match_indexers("Bocchi 2", None, &TitleAliases::builtin(), mock_anime_list);
         assert_eq!(matches.len(), 1);
-        assert_eq!(matches[0].name(), "Bocchi the Rock! 2");
+        assert_eq!(matches[0].indexer().name(), "Bocchi the Rock! 2");
+    }
+
+    #[test]
+    fn test_fuzzy_match_indexers_normalized_titles() {
+        let mock_anime_list = vec![
+            Indexer::new(
+                "Re:Zero kara Hajimeru Isekai Seikatsu",
+                "https://kitsunekko.net/rezero",
+            ),
+            Indexer::new("Shingeki no Kyojin", "https://kitsunekko.net/snk"),
+            Indexer::new(
+                "Shingeki no Kyojin The Final Season",
+                "https://kitsunekko.net/snk-final",
+            ),
+        ];
+
+        let matches = fuzzy_match_indexers(
+            "Re Zero kara",
+            None,
+            &TitleAliases::builtin(),
+            mock_anime_list.clone(),
+        );
+        assert_eq!(
+            matches[0].indexer().name(),
+            "Re:Zero kara Hajimeru Isekai Seikatsu"
+        );
+
+        let matches = fuzzy_match_indexers(
+            "しんげきのきょじん",
+            None,
+            &TitleAliases::builtin(),
+            mock_anime_list.clone(),
+        );
+        assert_eq!(matches[0].indexer().name(), "Shingeki no Kyojin");
+
+        let matches = fuzzy_match_indexers(
+            "Attack on Titan",
+            None,
+            &TitleAliases::builtin(),
+            mock_anime_list,
+        );
+        assert_eq!(matches[0].indexer().name(), "Shingeki no Kyojin");
+        assert_eq!(
+            matches[1].indexer().name(),
+            "Shingeki no Kyojin The Final Season"
+        );
+    }
+
+    #[test]
+    fn test_fuzzy_match_indexers_score_breakdown() {
+        let matches = fuzzy_match_indexers(
+            "BOCCHI THE ROCK",
+            None,
+            &TitleAliases::builtin(),
+            bocchi_the_mock!(),
+        );
+
+        assert_eq!(matches[0].normalized_name(), "bocchi the rock");
+        assert_eq!(matches[0].matched_title(), "bocchi the rock");
+        assert_eq!(matches[0].exact_bonus(), &EXACT_MATCH_BONUS);
+        assert_eq!(matches[0].length_penalty(), &0);
+        assert_eq!(matches[0].season_score(), &Some(0));
+
+        let matches =
+            fuzzy_match_indexers("Bocchi", None, &TitleAliases::builtin(), bocchi_the_mock!());
+        assert_eq!(matches[0].exact_bonus(), &0);
+        assert_eq!(matches[0].length_penalty(), &9);
+        assert_eq!(matches[0].score(), matches[0].skim_score() - 9);
+        assert_eq!(matches[1].season_score(), &Some(-1));
+    }
+
+    #[test]
+    fn test_fuzzy_match_indexers_seasons() {
+        let mock_anime_list = vec![
+            Indexer::new(
+                "Kaguya-sama wa Kokurasetai",
+                "https://kitsunekko.net/kaguya",
+            ),
+            Indexer::new(
+                "Kaguya-sama wa Kokurasetai 2nd Season",
+                "https://kitsunekko.net/kaguya-2",
+            ),
+            Indexer::new(
+                "Kaguya-sama wa Kokurasetai OVA",
+                "https://kitsunekko.net/kaguya-ova",
+            ),
+            Indexer::new(
+                "Kaguya-sama wa Kokurasetai Movie",
+                "https://kitsunekko.net/kaguya-movie",
+            ),
+        ];
+
+        let matches = fuzzy_match_indexers(
+            "Kaguya-sama",
+            None,
+            &TitleAliases::builtin(),
+            mock_anime_list.clone(),
+        );
+        assert_eq!(matches.len(), 4);
+        assert_eq!(matches[0].indexer().name(), "Kaguya-sama wa Kokurasetai");
+        assert_eq!(
+            matches[1].indexer().name(),
+            "Kaguya-sama wa Kokurasetai 2nd Season"
+        );
+
+        let matches = fuzzy_match_indexers(
+            "Kaguya-sama S2",
+            None,
+            &TitleAliases::builtin(),
+            mock_anime_list.clone(),
+        );
+        assert_eq!(matches.len(), 1);
+        assert_eq!(
+            matches[0].indexer().name(),
+            "Kaguya-sama wa Kokurasetai 2nd Season"
+        );
+
+        let matches = fuzzy_match_indexers(
+            "Kaguya-sama",
+            Some(2),
+            &TitleAliases::builtin(),
+            mock_anime_list.clone(),
+        );
+        assert_eq!(matches.len(), 1);
+        assert_eq!(
+            matches[0].indexer().name(),
+            "Kaguya-sama wa Kokurasetai 2nd Season"
+        );
+
+        let matches = fuzzy_match_indexers(
+            "Kaguya-sama OVA",
+            None,
+            &TitleAliases::builtin(),
+            mock_anime_list.clone(),
+        );
+        assert_eq!(matches.len(), 1);
+        assert_eq!(
+            matches[0].indexer().name(),
+            "Kaguya-sama wa Kokurasetai OVA"
+        );
+
+        // Nothing of the third season, the closest titles are still worth a try
+        let matches = fuzzy_match_indexers(
+            "Kaguya-sama",
+            Some(3),
+            &TitleAliases::builtin(),
+            mock_anime_list,
+        );
+        assert_eq!(matches.len(), 4);
+    }
+
+    #[test]
+    fn test_fuzzy_match_indexers_number_in_title() {
+        let mock_anime_list = vec![
+            Indexer::new("Kaiju No. 8", "https://kitsunekko.net/kaiju-no-8"),
+            Indexer::new(
+                "Kaiju No. 8 Season 2",
+                "https://kitsunekko.net/kaiju-no-8-2",
+            ),
+        ];
+
+        let matches = fuzzy_match_indexers(
+            "Kaiju No. 8",
+            None,
+            &TitleAliases::builtin(),
+            mock_anime_list,
+        );
+        assert_eq!(matches.len(), 2);
+        assert_eq!(matches[0].indexer().name(), "Kaiju No. 8");
     }
 }
@@ -0,0 +1,154 @@
+use super::audio::FRAME_MS;
+use super::subtitle::Cue;
+
+// How much better a split alignment must score before cues get a different offset than the
+// previous ones, in frames of agreement with the speech (5 seconds)
+const SPLIT_PENALTY: i64 = 500;
+
+// Prefix sums of the speech frames, to count the speech under a cue in constant time
+struct SpeechIndex {
+    prefix: Vec<i64>,
+}
+
+impl SpeechIndex {
+    fn new(speech: &[bool]) -> Self {
+        let mut prefix = Vec::with_capacity(speech.len() + 1);
+        prefix.push(0);
+        for &frame in speech {
+            prefix.push(prefix.last().unwrap() + frame as i64);
+        }
+        SpeechIndex { prefix }
+    }
+
+    // Frames covered by speech count for the cue, silent ones against it
+    fn score(&self, (start, end): (i64, i64), offset: i64) -> i64 {
+        let frames = self.prefix.len() as i64 - 1;
+        let clamp = |frame: i64| frame.clamp(0, frames) as usize;
+
+        let speech = self.prefix[clamp(end + offset)] - self.prefix[clamp(start + offset)];
+        2 * speech - (end - start)
+    }
+}
+
+fn to_frames(cue: &Cue) -> (i64, i64) {
+    (cue.start / FRAME_MS, cue.end / FRAME_MS)
+}
+
+// The offset in ms (within ±max_offset_ms) moving the cues over the most speech
+pub fn best_offset(cues: &[Cue], speech: &[bool], max_offset_ms: i64) -> i64 {
+    let index = SpeechIndex::new(speech);
+    let frames: Vec<(i64, i64)> = cues.iter().map(to_frames).collect();
+    let max_offset = max_offset_ms / FRAME_MS;
+
+    // Ties go to the smallest shift
+    let offset = (-max_offset..=max_offset)
+        .max_by_key(|&offset| {
+            let score: i64 = frames.iter().map(|&cue| index.score(cue, offset)).sum();
+            (score, -offset.abs())
+        })
+        .unwrap_or(0);
+
+    offset * FRAME_MS
+}
+
+// One offset in ms per cue. Consecutive cues share their offset unless a different one fits
+// the speech clearly better, which happens when the raw cuts or adds scenes (e.g. the OP or ads)
+pub fn piecewise_offsets(cues: &[Cue], speech: &[bool], max_offset_ms: i64) -> Vec<i64> {
+    if cues.is_empty() {
+        return Vec::new();
+    }
+
+    let index = SpeechIndex::new(speech);
+    let max_offset = max_offset_ms / FRAME_MS;
+    let offsets: Vec<i64> = (-max_offset..=max_offset).collect();
+
+    // Cues are aligned in the order they are shown, which may not be the order of the file
+    let mut order: Vec<usize> = (0..cues.len()).collect();
+    order.sort_by_key(|&cue| cues[cue].start);
+
+    let mut scores = vec![0; offsets.len()];
+    let mut switched = Vec::with_capacity(order.len());
+    let mut previous_best = Vec::with_capacity(order.len());
+
+    for &cue in &order {
+        let frames = to_frames(&cues[cue]);
+        let best = argmax(&scores);
+        let switch_score = scores[best] - SPLIT_PENALTY;
+
+        let cue_switched: Vec<bool> = scores.iter().map(|&score| switch_score > score).collect();
+        scores = scores
+            .iter()
+            .zip(&offsets)
+            .map(|(&score, &offset)| score.max(switch_score) + index.score(frames, offset))
+            .collect();
+
+        switched.push(cue_switched);
+        previous_best.push(best);
+    }
+
+    let mut result = vec![0; cues.len()];
+    let mut current = argmax(&scores);
+    for step in (0..order.len()).rev() {
+        result[order[step]] = offsets[current] * FRAME_MS;
+        if switched[step][current] {
+            current = previous_best[step];
+        }
+    }
+
+    result
+}
+
+// Among equally good offsets the one closest to no shift wins, which sits in the middle
+fn argmax(scores: &[i64]) -> usize {
+    let best = scores.iter().max().copied().unwrap_or(0);
+    let center = scores.len() / 2;
+    (0..scores.len())
+        .filter(|&index| scores[index] == best)
+        .min_by_key(|&index| index.abs_diff(center))
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn speech_at(frames: usize, ranges: &[std::ops::Range<usize>]) -> Vec<bool> {
+        (0..frames)
+            .map(|frame| ranges.iter().any(|range| range.contains(&frame)))
+            .collect()
+    }
+
+    fn cue(start: i64, end: i64) -> Cue {
+        Cue { start, end }
+    }
+
+    #[test]
+    fn test_best_offset() {
+        // Speech 1.2s later than the cues
+        let speech = speech_at(3_000, &[220..320, 620..700, 1_120..1_300]);
+        let cues = vec![cue(1_000, 2_000), cue(5_000, 5_800), cue(10_000, 11_800)];
+
+        assert_eq!(best_offset(&cues, &speech, 5_000), 1_200);
+    }
+
+    #[test]
+    fn test_piecewise_offsets() {
+        // The raw has 20 seconds more before the last two cues, like a longer ad break
+        let speech = speech_at(
+            6_000,
+            &[100..300, 500..650, 900..1_000, 3_300..3_500, 3_800..4_000],
+        );
+        let cues = vec![
+            cue(1_000, 3_000),
+            cue(5_000, 6_500),
+            cue(9_000, 10_000),
+            cue(13_000, 15_000),
+            cue(18_000, 20_000),
+        ];
+
+        assert_eq!(
+            piecewise_offsets(&cues, &speech, 30_000),
+            vec![0, 0, 0, 20_000, 20_000]
+        );
+    }
+}
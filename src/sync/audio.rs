@@ -0,0 +1,125 @@
+use std::path::Path;
+use std::process::Command;
+
+use error_stack::{IntoReport, Report, Result, ResultExt};
+
+use super::SyncError;
+
+const SAMPLE_RATE: usize = 16_000;
+// Speech is detected in frames of this length, it's also the precision of the alignment
+pub const FRAME_MS: i64 = 10;
+const FRAME_SAMPLES: usize = SAMPLE_RATE * FRAME_MS as usize / 1000;
+
+// Below this loudness range the audio is considered to have no speech at all
+const MIN_DYNAMIC_RANGE_DB: f64 = 6.0;
+// Pauses between words shorter than this stay part of the speech
+const MAX_PAUSE_FRAMES: usize = 20;
+
+// Decodes the first audio track to 16kHz mono samples through ffmpeg
+pub fn extract_audio(video: &Path) -> Result<Vec<i16>, SyncError> {
+    let output = Command::new("ffmpeg")
+        .args(["-nostdin", "-v", "error", "-i"])
+        .arg(video)
+        .args(["-map", "0:a:0", "-ac", "1", "-ar"])
+        .arg(SAMPLE_RATE.to_string())
+        .args(["-f", "s16le", "-"])
+        .output()
+        .into_report()
+        .attach_printable("Failed to run ffmpeg, is it installed?")
+        .change_context(SyncError)?;
+
+    if !output.status.success() {
+        return Err(Report::new(SyncError)
+            .attach_printable(format!(
+                "ffmpeg failed to extract the audio of: {}",
+                video.display()
+            ))
+            .attach_printable(String::from_utf8_lossy(&output.stderr).into_owned()));
+    }
+
+    Ok(output
+        .stdout
+        .chunks_exact(2)
+        .map(|sample| i16::from_le_bytes([sample[0], sample[1]]))
+        .collect())
+}
+
+// A simple energy based voice activity detector, one value per frame. The threshold sits
+// between the quiet and loud parts of the audio, so it adapts to the volume of each raw
+pub fn detect_speech(samples: &[i16]) -> Vec<bool> {
+    let energies: Vec<f64> = samples
+        .chunks(FRAME_SAMPLES)
+        .map(|frame| {
+            let power = frame
+                .iter()
+                .map(|&sample| (sample as f64).powi(2))
+                .sum::<f64>()
+                / frame.len() as f64;
+            10.0 * (power + 1.0).log10()
+        })
+        .collect();
+
+    if energies.is_empty() {
+        return Vec::new();
+    }
+
+    let mut sorted = energies.clone();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let percentile = |p: usize| sorted[(sorted.len() - 1) * p / 100];
+    let (quiet, loud) = (percentile(10), percentile(90));
+
+    if loud - quiet < MIN_DYNAMIC_RANGE_DB {
+        return vec![false; energies.len()];
+    }
+
+    let threshold = quiet + (loud - quiet) * 0.4;
+    let mut speech: Vec<bool> = energies.iter().map(|&energy| energy > threshold).collect();
+
+    // Fill the short pauses between speech frames
+    let mut last_speech: Option<usize> = None;
+    for frame in 0..speech.len() {
+        if !speech[frame] {
+            continue;
+        }
+        if let Some(last) = last_speech {
+            if frame - last <= MAX_PAUSE_FRAMES {
+                speech[last..frame].fill(true);
+            }
+        }
+        last_speech = Some(frame);
+    }
+
+    speech
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Silence with a tone between the given seconds
+    fn samples_with_tone(seconds: usize, tone: std::ops::Range<usize>) -> Vec<i16> {
+        (0..seconds * SAMPLE_RATE)
+            .map(|i| match tone.contains(&(i / SAMPLE_RATE)) {
+                true => ((i as f64 * 0.3).sin() * 8_000.0) as i16,
+                false => (i % 7) as i16 - 3,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_detect_speech() {
+        let speech = detect_speech(&samples_with_tone(5, 2..4));
+
+        assert_eq!(speech.len(), 500);
+        assert!(speech[..200].iter().all(|&frame| !frame));
+        assert!(speech[200..400].iter().all(|&frame| frame));
+        assert!(speech[400..].iter().all(|&frame| !frame));
+    }
+
+    #[test]
+    fn test_detect_speech_in_silence() {
+        assert!(detect_speech(&samples_with_tone(2, 0..0))
+            .iter()
+            .all(|&frame| !frame));
+    }
+}
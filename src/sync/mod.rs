@@ -0,0 +1,108 @@
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use error_stack::{IntoReport, Report, Result, ResultExt};
+
+pub mod align;
+pub mod audio;
+pub mod subtitle;
+
+use subtitle::{SubtitleEncoding, SubtitleTrack};
+
+#[derive(Debug)]
+pub struct SyncError;
+
+impl std::fmt::Display for SyncError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Failed to synchronize subtitles")
+    }
+}
+
+impl Error for SyncError {}
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct SyncOptions {
+    #[arg(
+        long,
+        default_value = "60",
+        help = "How many seconds subtitles may be shifted, in either direction"
+    )]
+    pub max_offset: u64,
+
+    #[arg(
+        long,
+        default_value = "false",
+        help = "Shift every subtitle by the same offset, instead of realigning after cuts"
+    )]
+    pub no_split: bool,
+}
+
+// e.g. "Bocchi - 01.synced.srt" next to "Bocchi - 01.mkv"
+pub fn default_output_path(video: &Path, extension: &str) -> PathBuf {
+    video.with_extension(format!("synced.{}", extension))
+}
+
+// Aligns the subtitle cues with the speech of the video and writes them to `output`,
+// or next to the video when not given. Returns where the subtitles were written
+pub fn sync_subtitle(
+    video: &Path,
+    subtitle: &Path,
+    output: Option<&Path>,
+    options: &SyncOptions,
+) -> Result<PathBuf, SyncError> {
+    let content = fs::read(subtitle)
+        .into_report()
+        .attach_printable_lazy(|| format!("Failed to read subtitles: {}", subtitle.display()))
+        .change_context(SyncError)?;
+    let (content, encoding) = SubtitleEncoding::decode(&content);
+
+    let track = SubtitleTrack::parse(&content);
+    let cues = track.cues();
+    if cues.is_empty() {
+        return Err(Report::new(SyncError)
+            .attach_printable(format!("No timed subtitles in: {}", subtitle.display())));
+    }
+
+    log::info!("Extracting audio of: {}", video.display());
+    let speech = audio::detect_speech(&audio::extract_audio(video)?);
+    if !speech.contains(&true) {
+        return Err(Report::new(SyncError)
+            .attach_printable(format!("No speech detected in: {}", video.display())));
+    }
+
+    let max_offset_ms = options.max_offset as i64 * 1000;
+    let offsets = match options.no_split {
+        true => vec![align::best_offset(&cues, &speech, max_offset_ms); cues.len()],
+        false => align::piecewise_offsets(&cues, &speech, max_offset_ms),
+    };
+    log_offsets(&offsets);
+
+    let output = output.map_or_else(
+        || default_output_path(video, track.format().extension()),
+        Path::to_path_buf,
+    );
+
+    fs::write(&output, encoding.encode(&track.shifted(&offsets)))
+        .into_report()
+        .attach_printable_lazy(|| format!("Failed to write subtitles: {}", output.display()))
+        .change_context(SyncError)?;
+
+    Ok(output)
+}
+
+// One line per run of cues sharing the same offset
+fn log_offsets(offsets: &[i64]) {
+    let mut start = 0;
+    for end in 1..=offsets.len() {
+        if end == offsets.len() || offsets[end] != offsets[start] {
+            log::info!(
+                "Shifted subtitles {}-{} by {:+.2}s",
+                start + 1,
+                end,
+                offsets[start] as f64 / 1000.0
+            );
+            start = end;
+        }
+    }
+}
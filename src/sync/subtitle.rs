@@ -0,0 +1,364 @@
+use encoding_rs::{Encoding, SHIFT_JIS, UTF_16BE, UTF_16LE, UTF_8};
+use lazy_static::lazy_static;
+use regex::Regex;
+
+lazy_static! {
+    // Each timing line is captured as prefix, start, separator, end and suffix, so it can be
+    // written back with other times without touching the rest
+    static ref SRT_TIMING_REGEX: Regex = create_timing_regex(
+        r"^()(\d+:\d{2}:\d{2}[,.]\d{1,3})(\s*-->\s*)(\d+:\d{2}:\d{2}[,.]\d{1,3})(.*)$"
+    );
+    static ref VTT_TIMING_REGEX: Regex = create_timing_regex(
+        r"^()((?:\d+:)?\d{2}:\d{2}\.\d{3})(\s*-->\s*)((?:\d+:)?\d{2}:\d{2}\.\d{3})(.*)$"
+    );
+    static ref ASS_TIMING_REGEX: Regex = create_timing_regex(
+        r"^(Dialogue:\s*[^,]*,)(\d+:\d{2}:\d{2}\.\d{2})(,)(\d+:\d{2}:\d{2}\.\d{2})(,.*)$"
+    );
+}
+
+fn create_timing_regex(regex: &str) -> Regex {
+    match Regex::new(regex) {
+        Ok(regex) => regex,
+        Err(error) => panic!(
+            "Failed to create regex for subtitle timings, error: {}",
+            error
+        ),
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SubtitleFormat {
+    Srt,
+    Vtt,
+    Ass,
+}
+
+impl SubtitleFormat {
+    // Files aren't always named after their format, so the content decides
+    pub fn detect(content: &str) -> Self {
+        let content = content.trim_start_matches('\u{feff}').trim_start();
+
+        if content.starts_with("WEBVTT") {
+            SubtitleFormat::Vtt
+        } else if content.starts_with("[Script Info]") || content.contains("\nDialogue:") {
+            SubtitleFormat::Ass
+        } else {
+            SubtitleFormat::Srt
+        }
+    }
+
+    fn timing_regex(&self) -> &'static Regex {
+        match self {
+            SubtitleFormat::Srt => &SRT_TIMING_REGEX,
+            SubtitleFormat::Vtt => &VTT_TIMING_REGEX,
+            SubtitleFormat::Ass => &ASS_TIMING_REGEX,
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            SubtitleFormat::Srt => "srt",
+            SubtitleFormat::Vtt => "vtt",
+            SubtitleFormat::Ass => "ass",
+        }
+    }
+
+    fn format_timestamp(&self, ms: i64) -> String {
+        let ms = ms.max(0);
+        let (hours, minutes, seconds) = (ms / 3_600_000, ms / 60_000 % 60, ms / 1000 % 60);
+
+        match self {
+            SubtitleFormat::Srt => {
+                format!(
+                    "{:02}:{:02}:{:02},{:03}",
+                    hours,
+                    minutes,
+                    seconds,
+                    ms % 1000
+                )
+            }
+            SubtitleFormat::Vtt => {
+                format!(
+                    "{:02}:{:02}:{:02}.{:03}",
+                    hours,
+                    minutes,
+                    seconds,
+                    ms % 1000
+                )
+            }
+            SubtitleFormat::Ass => format!(
+                "{}:{:02}:{:02}.{:02}",
+                hours,
+                minutes,
+                seconds,
+                ms % 1000 / 10
+            ),
+        }
+    }
+}
+
+// "1:02:03,456", "02:03.456" or "1:02:03.45" in milliseconds
+fn parse_timestamp(timestamp: &str) -> Option<i64> {
+    let (clock, fraction) = timestamp.split_once([',', '.'])?;
+
+    let seconds = clock.split(':').try_fold(0, |total, part| {
+        Some(total * 60 + part.parse::<i64>().ok()?)
+    })?;
+    let fraction_ms = format!("{:0<3}", fraction).get(..3)?.parse::<i64>().ok()?;
+
+    Some(seconds * 1000 + fraction_ms)
+}
+
+// How a subtitle file was encoded, so it can be written back the same way. Files start
+// with a BOM or are UTF-8, except the Shift-JIS ones common on kitsunekko
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct SubtitleEncoding {
+    encoding: &'static Encoding,
+    bom: bool,
+}
+
+impl SubtitleEncoding {
+    pub fn decode(content: &[u8]) -> (String, Self) {
+        let (encoding, bom) = match Encoding::for_bom(content) {
+            Some((encoding, _)) => (encoding, true),
+            None if std::str::from_utf8(content).is_ok() => (UTF_8, false),
+            None => (SHIFT_JIS, false),
+        };
+        let (text, _) = encoding.decode_with_bom_removal(content);
+
+        (text.into_owned(), SubtitleEncoding { encoding, bom })
+    }
+
+    // encoding_rs only encodes to UTF-16 as UTF-8, so its code units are written here
+    pub fn encode(&self, content: &str) -> Vec<u8> {
+        let content = match self.bom {
+            true => format!("\u{feff}{}", content),
+            false => content.to_owned(),
+        };
+
+        if self.encoding == UTF_16LE {
+            content.encode_utf16().flat_map(u16::to_le_bytes).collect()
+        } else if self.encoding == UTF_16BE {
+            content.encode_utf16().flat_map(u16::to_be_bytes).collect()
+        } else {
+            self.encoding.encode(&content).0.into_owned()
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Cue {
+    pub start: i64,
+    pub end: i64,
+}
+
+#[derive(Debug)]
+struct TimingLine {
+    line: usize,
+    prefix: String,
+    separator: String,
+    suffix: String,
+    cue: Cue,
+}
+
+// A subtitle file as its lines, remembering which ones carry the timing of a cue
+#[derive(Debug)]
+pub struct SubtitleTrack {
+    format: SubtitleFormat,
+    lines: Vec<String>,
+    timings: Vec<TimingLine>,
+    line_ending: &'static str,
+}
+
+impl SubtitleTrack {
+    pub fn parse(content: &str) -> Self {
+        let format = SubtitleFormat::detect(content);
+        let lines: Vec<String> = content.lines().map(str::to_owned).collect();
+
+        let timings = lines
+            .iter()
+            .enumerate()
+            .filter_map(|(line, text)| {
+                let capture = format.timing_regex().captures(text)?;
+                Some(TimingLine {
+                    line,
+                    prefix: capture[1].to_owned(),
+                    separator: capture[3].to_owned(),
+                    suffix: capture[5].to_owned(),
+                    cue: Cue {
+                        start: parse_timestamp(&capture[2])?,
+                        end: parse_timestamp(&capture[4])?,
+                    },
+                })
+            })
+            .collect();
+
+        SubtitleTrack {
+            format,
+            lines,
+            timings,
+            line_ending: match content.contains("\r\n") {
+                true => "\r\n",
+                false => "\n",
+            },
+        }
+    }
+
+    pub fn format(&self) -> SubtitleFormat {
+        self.format
+    }
+
+    pub fn cues(&self) -> Vec<Cue> {
+        self.timings.iter().map(|timing| timing.cue).collect()
+    }
+
+    // The text of each cue as written in the file, in the order of `cues`. ASS keeps its
+    // override tags and \N line breaks, the other formats their markup
+    pub fn cue_texts(&self) -> Vec<String> {
+        self.timings
+            .iter()
+            .map(|timing| match self.format {
+                // The text is the last of the fields following the end time
+                SubtitleFormat::Ass => timing.suffix.splitn(8, ',').nth(7).unwrap_or("").to_owned(),
+                SubtitleFormat::Srt | SubtitleFormat::Vtt => self.lines[timing.line + 1..]
+                    .iter()
+                    .take_while(|line| !line.trim().is_empty())
+                    .map(String::as_str)
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            })
+            .collect()
+    }
+
+    // The file with each cue moved by its offset, in the order of `cues`
+    pub fn shifted(&self, offsets: &[i64]) -> String {
+        let mut lines = self.lines.clone();
+
+        for (timing, offset) in self.timings.iter().zip(offsets) {
+            lines[timing.line] = format!(
+                "{}{}{}{}{}",
+                timing.prefix,
+                self.format.format_timestamp(timing.cue.start + offset),
+                timing.separator,
+                self.format.format_timestamp(timing.cue.end + offset),
+                timing.suffix
+            );
+        }
+
+        let mut content = lines.join(self.line_ending);
+        content.push_str(self.line_ending);
+        content
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SRT: &str = "1\r\n00:00:01,500 --> 00:00:03,000\r\nこんにちは\r\n\r\n2\r\n00:01:00,000 --> 00:01:02,250\r\nさようなら\r\n";
+
+    const ASS: &str = "[Script Info]\nTitle: Bocchi\n\n[Events]\nFormat: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\nDialogue: 0,0:00:01.50,0:00:03.00,Default,,0,0,0,,こんにちは\n";
+
+    #[test]
+    fn test_parse_timestamp() {
+        assert_eq!(parse_timestamp("01:02:03,456"), Some(3_723_456));
+        assert_eq!(parse_timestamp("02:03.456"), Some(123_456));
+        assert_eq!(parse_timestamp("0:00:01.50"), Some(1_500));
+        assert_eq!(parse_timestamp("soon"), None);
+    }
+
+    #[test]
+    fn test_shift_srt() {
+        let track = SubtitleTrack::parse(SRT);
+
+        assert_eq!(track.format(), SubtitleFormat::Srt);
+        assert_eq!(
+            track.cues(),
+            vec![
+                Cue {
+                    start: 1_500,
+                    end: 3_000
+                },
+                Cue {
+                    start: 60_000,
+                    end: 62_250
+                },
+            ]
+        );
+        assert_eq!(
+            track.shifted(&[-2_000, 1_000]),
+            "1\r\n00:00:00,000 --> 00:00:01,000\r\nこんにちは\r\n\r\n2\r\n00:01:01,000 --> 00:01:03,250\r\nさようなら\r\n"
+        );
+    }
+
+    #[test]
+    fn test_cue_texts() {
+        assert_eq!(
+            SubtitleTrack::parse(SRT).cue_texts(),
+            vec!["こんにちは", "さようなら"]
+        );
+        assert_eq!(SubtitleTrack::parse(ASS).cue_texts(), vec!["こんにちは"]);
+    }
+
+    // "こんにちは" encoded as Shift-JIS
+    const SHIFT_JIS_TEXT: [u8; 10] = [0x82, 0xB1, 0x82, 0xF1, 0x82, 0xC9, 0x82, 0xBF, 0x82, 0xCD];
+
+    #[test]
+    fn test_parse_shift_jis() {
+        let mut content = b"1\r\n00:00:01,500 --> 00:00:03,000\r\n".to_vec();
+        content.extend_from_slice(&SHIFT_JIS_TEXT);
+        let (content, _) = SubtitleEncoding::decode(&content);
+        let track = SubtitleTrack::parse(&content);
+
+        assert_eq!(track.format(), SubtitleFormat::Srt);
+        assert_eq!(
+            track.cues(),
+            vec![Cue {
+                start: 1_500,
+                end: 3_000
+            }]
+        );
+        assert_eq!(track.cue_texts(), vec!["こんにちは"]);
+    }
+
+    #[test]
+    fn test_shift_keeps_shift_jis() {
+        let mut content = b"1\r\n00:00:01,500 --> 00:00:03,000\r\n".to_vec();
+        content.extend_from_slice(&SHIFT_JIS_TEXT);
+        content.extend_from_slice(b"\r\n");
+        let (decoded, encoding) = SubtitleEncoding::decode(&content);
+
+        let mut expected = b"1\r\n00:00:02,500 --> 00:00:04,000\r\n".to_vec();
+        expected.extend_from_slice(&SHIFT_JIS_TEXT);
+        expected.extend_from_slice(b"\r\n");
+        assert_eq!(
+            encoding.encode(&SubtitleTrack::parse(&decoded).shifted(&[1_000])),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_shift_keeps_utf_16() {
+        let content: Vec<u8> = format!("\u{feff}{}", SRT)
+            .encode_utf16()
+            .flat_map(u16::to_le_bytes)
+            .collect();
+        let (decoded, encoding) = SubtitleEncoding::decode(&content);
+
+        assert_eq!(decoded, SRT);
+        assert_eq!(
+            encoding.encode(&SubtitleTrack::parse(&decoded).shifted(&[0, 0])),
+            content
+        );
+    }
+
+    #[test]
+    fn test_shift_ass() {
+        let track = SubtitleTrack::parse(ASS);
+
+        assert_eq!(track.format(), SubtitleFormat::Ass);
+        assert!(track
+            .shifted(&[1_230])
+            .ends_with("Dialogue: 0,0:00:02.73,0:00:04.23,Default,,0,0,0,,こんにちは\n"));
+    }
+}
@@ -0,0 +1,12 @@
+// A new folder for each call, so tests running in parallel (or several runs at once) don't
+// share their files
+pub fn unique_temp_dir(name: &str) -> std::path::PathBuf {
+    let folder = std::env::temp_dir().join(format!(
+        "anime-jp-test-{}-{}-{}",
+        name,
+        std::process::id(),
+        rand::random::<u64>()
+    ));
+    std::fs::create_dir_all(&folder).unwrap();
+    folder
+}
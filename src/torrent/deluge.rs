@@ -0,0 +1,375 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use base64::Engine;
+use error_stack::{IntoReport, Report, Result, ResultExt};
+use reqwest::blocking::Client;
+use reqwest::header::{COOKIE, SET_COOKIE};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::core::download::downloader::Destination;
+use crate::core::magnet::MagnetLink;
+use crate::qbittorrent::api::torrents::{Torrent, TorrentAddOptions, TorrentList, TorrentState};
+
+use super::{TorrentClient, TorrentClientError};
+
+pub const DEFAULT_URL: &str = "http://127.0.0.1:8112/json";
+pub const DEFAULT_PASSWORD: &str = "deluge";
+
+// Deluge answers with this error code when the session cookie is missing or expired
+const NOT_AUTHENTICATED_CODE: i64 = 1;
+
+const TORRENT_KEYS: [&str; 10] = [
+    "name",
+    "total_size",
+    "state",
+    "total_done",
+    "eta",
+    "progress",
+    "download_payload_rate",
+    "num_seeds",
+    "num_peers",
+    "download_location",
+];
+
+// Talks to deluge-web, which forwards the calls to the daemon it is connected to
+pub struct DelugeClient {
+    http_client: Client,
+    url: String,
+    password: String,
+    session_cookie: Mutex<Option<String>>,
+    request_id: AtomicU64,
+    download_root: Option<PathBuf>,
+}
+
+#[derive(Serialize, Debug)]
+struct RpcRequest<'a> {
+    method: &'a str,
+    params: Value,
+    id: u64,
+}
+
+#[derive(Deserialize, Debug)]
+struct RpcResponse {
+    result: Value,
+    error: Option<RpcError>,
+}
+
+#[derive(Deserialize, Debug)]
+struct RpcError {
+    message: String,
+    code: i64,
+}
+
+#[derive(Deserialize, Debug)]
+struct DelugeTorrent {
+    name: String,
+    total_size: i64,
+    state: String,
+    total_done: i64,
+    eta: f64,
+    // From 0 to 100
+    progress: f64,
+    #[serde(default)]
+    download_payload_rate: f64,
+    #[serde(default)]
+    num_seeds: i64,
+    #[serde(default)]
+    num_peers: i64,
+    #[serde(default)]
+    download_location: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct DelugeFiles {
+    files: Vec<DelugeFile>,
+}
+
+#[derive(Deserialize, Debug)]
+struct DelugeFile {
+    path: String,
+}
+
+impl DelugeTorrent {
+    fn qbittorrent_state(&self) -> TorrentState {
+        let complete = self.progress >= 100.0;
+        match self.state.as_str() {
+            "Seeding" => TorrentState::Uploading,
+            "Paused" if complete => TorrentState::StoppedUp,
+            "Paused" => TorrentState::StoppedDl,
+            "Queued" if complete => TorrentState::QueuedUp,
+            "Queued" => TorrentState::QueuedDl,
+            "Checking" if complete => TorrentState::CheckingUp,
+            "Checking" => TorrentState::CheckingDl,
+            "Allocating" => TorrentState::Allocating,
+            "Downloading" => TorrentState::Downloading,
+            "Moving" => TorrentState::Moving,
+            "Error" => TorrentState::Error,
+            _ => TorrentState::Unknown,
+        }
+    }
+
+    fn to_torrent(&self, hash: &str) -> Torrent {
+        Torrent::new(
+            hash,
+            &self.name,
+            self.total_size,
+            self.qbittorrent_state(),
+            self.total_done,
+            self.eta as i64,
+        )
+        .with_transfer(
+            self.progress / 100.0,
+            self.download_payload_rate as i64,
+            self.num_seeds,
+            self.num_peers,
+        )
+        .with_save_path(&self.download_location)
+    }
+}
+
+impl DelugeClient {
+    pub fn new(url: &str, password: &str, download_root: Option<PathBuf>) -> Self {
+        DelugeClient {
+            http_client: Client::new(),
+            url: url.to_owned(),
+            password: password.to_owned(),
+            session_cookie: Mutex::new(None),
+            request_id: AtomicU64::new(0),
+            download_root,
+        }
+    }
+
+    fn send(&self, method: &str, params: &Value) -> Result<RpcResponse, TorrentClientError> {
+        let request = RpcRequest {
+            method,
+            params: params.clone(),
+            id: self.request_id.fetch_add(1, Ordering::Relaxed),
+        };
+
+        let mut req = self.http_client.post(&self.url).json(&request);
+        if let Some(cookie) = self.session_cookie.lock().unwrap().as_ref() {
+            req = req.header(COOKIE, cookie);
+        }
+
+        let res = req
+            .send()
+            .into_report()
+            .attach_printable_lazy(|| format!("Failed to send {} to: {}", method, self.url))
+            .change_context(TorrentClientError)?;
+
+        if !res.status().is_success() {
+            return Err(Report::new(TorrentClientError)
+                .attach_printable(format!("Response failed with status: {}", res.status())));
+        }
+
+        // Only the name=value part of the cookie is sent back
+        if let Some(cookie) = res
+            .headers()
+            .get(SET_COOKIE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.split(';').next())
+        {
+            *self.session_cookie.lock().unwrap() = Some(cookie.to_owned());
+        }
+
+        res.json()
+            .into_report()
+            .attach_printable_lazy(|| format!("Failed to read response of: {}", method))
+            .change_context(TorrentClientError)
+    }
+
+    // Logs in and makes sure deluge-web is connected to a daemon, picking the first known host
+    fn login(&self) -> Result<(), TorrentClientError> {
+        let logged_in = self.send("auth.login", &json!([self.password]))?;
+        if logged_in.result != Value::Bool(true) {
+            return Err(
+                Report::new(TorrentClientError).attach_printable("Deluge refused the password")
+            );
+        }
+
+        let connected = self.send("web.connected", &json!([]))?;
+        if connected.result == Value::Bool(true) {
+            return Ok(());
+        }
+
+        let hosts = self.send("web.get_hosts", &json!([]))?;
+        let host_id = hosts
+            .result
+            .get(0)
+            .and_then(|host| host.get(0))
+            .cloned()
+            .ok_or_else(|| {
+                Report::new(TorrentClientError)
+                    .attach_printable("Deluge has no daemon to connect to")
+            })?;
+
+        self.send("web.connect", &json!([host_id]))?;
+        Ok(())
+    }
+
+    fn call<R: DeserializeOwned>(
+        &self,
+        method: &str,
+        params: Value,
+    ) -> Result<R, TorrentClientError> {
+        if self.session_cookie.lock().unwrap().is_none() {
+            self.login()?;
+        }
+
+        let mut response = self.send(method, &params)?;
+        if matches!(&response.error, Some(error) if error.code == NOT_AUTHENTICATED_CODE) {
+            log::debug!("Deluge session expired, logging in again");
+            self.login()?;
+            response = self.send(method, &params)?;
+        }
+
+        if let Some(error) = response.error {
+            return Err(Report::new(TorrentClientError)
+                .attach_printable(format!("{} failed: {}", method, error.message)));
+        }
+
+        serde_json::from_value(response.result)
+            .into_report()
+            .attach_printable_lazy(|| format!("Unexpected result of: {}", method))
+            .change_context(TorrentClientError)
+    }
+}
+
+impl TorrentClient for DelugeClient {
+    fn add(
+        &self,
+        magnet: &MagnetLink,
+        torrent_url: Option<&str>,
+        dest: &Destination,
+        options: &TorrentAddOptions,
+    ) -> Result<String, TorrentClientError> {
+        // Deluge can't rename a torrent when adding it, and labels need a plugin,
+        // so only the folder and the paused state are kept
+        let options =
+            super::apply_destination(dest, options.clone(), self.download_root.as_deref());
+        let torrent_file = super::fetch_torrent_file_or_warn(torrent_url);
+
+        let mut add_options = json!({
+            "add_paused": super::add_paused(&options, torrent_file.is_some()),
+        });
+        if let Some(savepath) = &options.savepath {
+            add_options["download_location"] = json!(savepath);
+        }
+
+        let _: Value = match torrent_file {
+            Some(torrent_file) => self.call(
+                "core.add_torrent_file",
+                json!([
+                    format!("{}.torrent", magnet.info_hash()),
+                    base64::engine::general_purpose::STANDARD.encode(torrent_file),
+                    add_options,
+                ]),
+            ),
+            None => self.call(
+                "core.add_torrent_magnet",
+                json!([magnet.to_string(), add_options]),
+            ),
+        }
+        .attach_printable_lazy(|| format!("Failed to add torrent: {}", magnet))?;
+
+        Ok(magnet.info_hash().to_owned())
+    }
+
+    fn list(&self, hashes: &[String]) -> Result<TorrentList, TorrentClientError> {
+        let torrents: HashMap<String, DelugeTorrent> = self.call(
+            "core.get_torrents_status",
+            json!([{ "id": hashes }, TORRENT_KEYS]),
+        )?;
+
+        Ok(TorrentList(
+            torrents
+                .iter()
+                .map(|(hash, torrent)| torrent.to_torrent(hash))
+                .collect(),
+        ))
+    }
+
+    fn remove(&self, hashes: &[String], delete_files: bool) -> Result<(), TorrentClientError> {
+        for hash in hashes {
+            let _: Value = self.call("core.remove_torrent", json!([hash, delete_files]))?;
+        }
+
+        Ok(())
+    }
+
+    fn select_files(
+        &self,
+        hash: &str,
+        wanted: &dyn Fn(&str) -> bool,
+        resume: bool,
+    ) -> Result<usize, TorrentClientError> {
+        let files = super::wait_for_files(hash, || {
+            let status: DelugeFiles =
+                self.call("core.get_torrent_status", json!([hash, ["files"]]))?;
+            Ok(status.files.into_iter().map(|file| file.path).collect())
+        })?;
+
+        let (selected, _) = super::partition_files(&files, wanted);
+
+        if selected.is_empty() {
+            self.remove(&[hash.to_owned()], true)?;
+            return Err(Report::new(TorrentClientError)
+                .attach_printable(format!("No wanted file in torrent: {}", hash)));
+        }
+
+        // Deluge takes the priority of every file at once, 0 means skip
+        let priorities: Vec<u8> = (0..files.len())
+            .map(|file_id| u8::from(selected.contains(&file_id)))
+            .collect();
+        let _: Value = self.call(
+            "core.set_torrent_options",
+            json!([[hash], { "file_priorities": priorities }]),
+        )?;
+
+        if resume {
+            let _: Value = self.call("core.resume_torrent", json!([[hash]]))?;
+        }
+
+        Ok(selected.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_torrent_state() {
+        let torrents: HashMap<String, DelugeTorrent> = serde_json::from_str(
+            r#"{
+                "568807a73ecd33fff3ac19f47805f0940cbdb9ac": {"name": "One Piece", "total_size": 10, "state": "Paused", "total_done": 10, "eta": 0, "progress": 100.0},
+                "4936206e05d1bb04084f50032d6b3704f271eff7": {"name": "Bougyoryoku", "total_size": 10, "state": "Downloading", "total_done": 2, "eta": 60, "progress": 20.0}
+            }"#,
+        )
+        .unwrap();
+
+        let list = TorrentList(
+            torrents
+                .iter()
+                .map(|(hash, torrent)| torrent.to_torrent(hash))
+                .collect(),
+        );
+        assert!(list.all_finished(&["568807a73ecd33fff3ac19f47805f0940cbdb9ac".to_string()]));
+        assert!(!list.all_finished(&["4936206e05d1bb04084f50032d6b3704f271eff7".to_string()]));
+    }
+
+    #[test]
+    fn test_rpc_error_response() {
+        let response: RpcResponse = serde_json::from_str(
+            r#"{"result": null, "error": {"message": "Not authenticated", "code": 1}, "id": 3}"#,
+        )
+        .unwrap();
+
+        assert_eq!(response.error.unwrap().code, NOT_AUTHENTICATED_CODE);
+    }
+}
@@ -0,0 +1,343 @@
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use clap::ValueEnum;
+use error_stack::{IntoReport, Report, Result, ResultExt};
+
+use crate::core::download::downloader::Destination;
+use crate::core::magnet::MagnetLink;
+use crate::qbittorrent::api::torrents::{TorrentAddOptions, TorrentList};
+use crate::qbittorrent::client::QBitTorrentClient;
+
+pub mod deluge;
+pub mod transmission;
+pub mod wait;
+pub mod watch_folder;
+
+pub use wait::{WaitOptions, WaitSummary};
+
+// Magnets have no file list until the client fetched their metadata from peers
+pub const METADATA_POLL_INTERVAL: Duration = Duration::from_secs(1);
+pub const METADATA_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+// A slow tracker site shouldn't hold the download back, the magnet works without it
+pub const TORRENT_FILE_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug)]
+pub struct TorrentClientError;
+
+impl std::fmt::Display for TorrentClientError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Problem in torrent client operation")
+    }
+}
+
+impl Error for TorrentClientError {}
+
+// What the raw downloads need from a torrent client, torrents are always tracked by info-hash
+pub trait TorrentClient {
+    // Uploads the .torrent file when it can be fetched, otherwise adds the magnet.
+    // Returns the info-hash of the added torrent
+    fn add(
+        &self,
+        magnet: &MagnetLink,
+        torrent_url: Option<&str>,
+        dest: &Destination,
+        options: &TorrentAddOptions,
+    ) -> Result<String, TorrentClientError>;
+
+    // Torrents unknown to the client are left out of the list
+    fn list(&self, hashes: &[String]) -> Result<TorrentList, TorrentClientError>;
+
+    fn remove(&self, hashes: &[String], delete_files: bool) -> Result<(), TorrentClientError>;
+
+    // Only the files whose name is accepted by `wanted` are downloaded, a torrent without
+    // any wanted file is removed. Returns how many files were selected
+    fn select_files(
+        &self,
+        hash: &str,
+        wanted: &dyn Fn(&str) -> bool,
+        resume: bool,
+    ) -> Result<usize, TorrentClientError>;
+
+    // Waits until every torrent finished, stalled or the overall timeout is over
+    fn wait_for_completion(
+        &self,
+        hashes: &[String],
+        options: &WaitOptions,
+        on_update: &dyn Fn(&TorrentList),
+    ) -> Result<WaitSummary, TorrentClientError> {
+        wait::poll_until_finished(
+            hashes,
+            options,
+            || self.list(hashes),
+            on_update,
+            |stalled| self.remove(stalled, true),
+        )
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum TorrentClientBackend {
+    #[value(name = "qbittorrent")]
+    QBitTorrent,
+    Transmission,
+    Deluge,
+    WatchFolder,
+}
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct TorrentClientConfig {
+    #[arg(
+        long = "torrent-client",
+        global = true,
+        default_value = "qbittorrent",
+        help = "The torrent client raws are sent to"
+    )]
+    pub backend: TorrentClientBackend,
+
+    #[arg(
+        long = "torrent-client-url",
+        global = true,
+        help = "RPC url of Transmission or Deluge, defaults to their local address"
+    )]
+    pub url: Option<String>,
+
+    #[arg(
+        long = "torrent-client-username",
+        global = true,
+        help = "Username for Transmission's RPC"
+    )]
+    pub username: Option<String>,
+
+    #[arg(
+        long = "torrent-client-password",
+        global = true,
+        help = "Password for Transmission's RPC or Deluge's WebUI"
+    )]
+    pub password: Option<String>,
+
+    #[arg(
+        long,
+        global = true,
+        help = "Folder watched by the torrent client, required by the watch-folder backend"
+    )]
+    pub watch_folder: Option<PathBuf>,
+}
+
+pub fn create_torrent_client(
+    config: &TorrentClientConfig,
+    download_root: Option<PathBuf>,
+) -> Result<Box<dyn TorrentClient>, TorrentClientError> {
+    let client: Box<dyn TorrentClient> = match config.backend {
+        TorrentClientBackend::QBitTorrent => Box::new(QBitTorrentClient::new(download_root)),
+        TorrentClientBackend::Transmission => Box::new(transmission::TransmissionClient::new(
+            config.url.as_deref().unwrap_or(transmission::DEFAULT_URL),
+            config.username.clone(),
+            config.password.clone(),
+            download_root,
+        )),
+        TorrentClientBackend::Deluge => Box::new(deluge::DelugeClient::new(
+            config.url.as_deref().unwrap_or(deluge::DEFAULT_URL),
+            config
+                .password
+                .as_deref()
+                .unwrap_or(deluge::DEFAULT_PASSWORD),
+            download_root,
+        )),
+        TorrentClientBackend::WatchFolder => {
+            let folder = config.watch_folder.clone().ok_or_else(|| {
+                Report::new(TorrentClientError)
+                    .attach_printable("The watch-folder backend needs --watch-folder")
+            })?;
+            Box::new(watch_folder::WatchFolderClient::new(folder))
+        }
+    };
+
+    Ok(client)
+}
+
+// Default means the client's own default save path, and a given basename becomes the torrent name
+pub fn apply_destination(
+    dest: &Destination,
+    options: TorrentAddOptions,
+    download_root: Option<&Path>,
+) -> TorrentAddOptions {
+    let (folder, file_basename) = match dest {
+        Destination::GivenFolderGivenFileBasename(folder, file_basename) => {
+            (Some(folder), Some(file_basename))
+        }
+        Destination::GivenFolderGuessFileBasename(folder) => (Some(folder), None),
+        Destination::DefaultFolderGivenFileBasename(file_basename) => (None, Some(file_basename)),
+        Destination::Default => (None, None),
+    };
+
+    TorrentAddOptions {
+        savepath: folder
+            .map(|folder| resolve_save_path(folder, download_root))
+            .or(options.savepath),
+        rename: file_basename.cloned().or(options.rename),
+        ..options
+    }
+}
+
+// Relative folders are resolved against download_root, which should be the folder
+// as seen by the torrent client (it may run on another machine or container)
+pub fn resolve_save_path(folder: &str, download_root: Option<&Path>) -> String {
+    match download_root {
+        Some(download_root) if Path::new(folder).is_relative() => {
+            download_root.join(folder).to_string_lossy().into_owned()
+        }
+        _ => folder.to_owned(),
+    }
+}
+
+// Backends without stop conditions can only hold a torrent for file selection when its
+// metadata is already known. A magnet keeps running and its files are deselected once known
+pub fn add_paused(options: &TorrentAddOptions, has_metadata: bool) -> bool {
+    options.paused.unwrap_or(false) || (options.stop_condition.is_some() && has_metadata)
+}
+
+pub fn fetch_torrent_file(url: &str) -> Result<Vec<u8>, TorrentClientError> {
+    fetch_torrent_file_within(url, TORRENT_FILE_TIMEOUT)
+}
+
+fn fetch_torrent_file_within(url: &str, timeout: Duration) -> Result<Vec<u8>, TorrentClientError> {
+    let res = reqwest::blocking::Client::builder()
+        .timeout(timeout)
+        .build()
+        .and_then(|client| client.get(url).send())
+        .and_then(|res| res.error_for_status())
+        .into_report()
+        .attach_printable_lazy(|| format!("Failed to download torrent file: {}", url))
+        .change_context(TorrentClientError)?;
+
+    let torrent_file = res
+        .bytes()
+        .into_report()
+        .attach_printable_lazy(|| format!("Failed to read torrent file: {}", url))
+        .change_context(TorrentClientError)?;
+
+    // Error pages may still come with a success status, torrent files are bencoded dictionaries
+    if !torrent_file.starts_with(b"d") {
+        return Err(Report::new(TorrentClientError)
+            .attach_printable(format!("Not a torrent file: {}", url)));
+    }
+
+    Ok(torrent_file.to_vec())
+}
+
+// A torrent file that can't be fetched only means falling back to the magnet
+pub fn fetch_torrent_file_or_warn(torrent_url: Option<&str>) -> Option<Vec<u8>> {
+    let torrent_url = torrent_url?;
+
+    match fetch_torrent_file(torrent_url) {
+        Ok(torrent_file) => Some(torrent_file),
+        Err(e) => {
+            log::warn!("Falling back to magnet\n{e:?}");
+            None
+        }
+    }
+}
+
+// Polls the file names of a torrent until its metadata is known
+pub fn wait_for_files(
+    hash: &str,
+    fetch_files: impl Fn() -> Result<Vec<String>, TorrentClientError>,
+) -> Result<Vec<String>, TorrentClientError> {
+    let start = Instant::now();
+
+    loop {
+        // The torrent may not be registered yet right after being added
+        match fetch_files() {
+            Ok(files) if !files.is_empty() => return Ok(files),
+            Ok(_) => log::debug!("Waiting for metadata of torrent: {}", hash),
+            Err(e) => log::debug!("Failed to get files of torrent {}: {:?}", hash, e),
+        }
+
+        if start.elapsed() > METADATA_TIMEOUT {
+            return Err(Report::new(TorrentClientError).attach_printable(format!(
+                "Timed out waiting for metadata of torrent: {}",
+                hash
+            )));
+        }
+
+        std::thread::sleep(METADATA_POLL_INTERVAL);
+    }
+}
+
+// Splits file ids into the wanted and the unwanted ones, ids being positions in the file list
+pub fn partition_files(
+    files: &[String],
+    wanted: &dyn Fn(&str) -> bool,
+) -> (Vec<usize>, Vec<usize>) {
+    (0..files.len()).partition(|&file_id| wanted(&files[file_id]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_add_paused() {
+        let options = TorrentAddOptions::default();
+        assert!(!add_paused(&options, true));
+
+        let options = TorrentAddOptions {
+            paused: Some(true),
+            ..TorrentAddOptions::default()
+        };
+        assert!(add_paused(&options, false));
+
+        let options = TorrentAddOptions {
+            stop_condition: Some(
+                crate::qbittorrent::api::torrents::StopCondition::MetadataReceived,
+            ),
+            ..TorrentAddOptions::default()
+        };
+        assert!(add_paused(&options, true));
+        assert!(!add_paused(&options, false));
+    }
+
+    #[test]
+    fn test_partition_files() {
+        let files = vec![
+            "Batch/Bocchi - 01.mkv".to_string(),
+            "Batch/readme.txt".to_string(),
+            "Batch/Bocchi - 02.mkv".to_string(),
+        ];
+
+        let (wanted, unwanted) = partition_files(&files, &|name| name.ends_with(".mkv"));
+        assert_eq!(wanted, vec![0, 2]);
+        assert_eq!(unwanted, vec![1]);
+    }
+
+    #[test]
+    fn test_fetch_torrent_file_times_out() {
+        let mut server = mockito::Server::new();
+        let _mock = server
+            .mock("GET", "/slow.torrent")
+            .with_body_from_fn(|body| {
+                std::thread::sleep(Duration::from_secs(2));
+                body.write_all(b"d4:infod4:name4:testee")
+            })
+            .create();
+        let url = format!("{}/slow.torrent", server.url());
+
+        let result = fetch_torrent_file_within(&url, Duration::from_millis(200));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_fetch_torrent_file_or_warn_falls_back_to_magnet() {
+        let mut server = mockito::Server::new();
+        let _mock = server
+            .mock("GET", "/error.torrent")
+            .with_body("<html>Not found</html>")
+            .create();
+        let url = format!("{}/error.torrent", server.url());
+
+        assert_eq!(fetch_torrent_file_or_warn(Some(&url)), None);
+        assert_eq!(fetch_torrent_file_or_warn(None), None);
+    }
+}
@@ -0,0 +1,382 @@
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use base64::Engine;
+use error_stack::{IntoReport, Report, Result, ResultExt};
+use reqwest::blocking::Client;
+use reqwest::StatusCode;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::core::download::downloader::Destination;
+use crate::core::magnet::MagnetLink;
+use crate::qbittorrent::api::torrents::{Torrent, TorrentAddOptions, TorrentList, TorrentState};
+
+use super::{TorrentClient, TorrentClientError};
+
+pub const DEFAULT_URL: &str = "http://127.0.0.1:9091/transmission/rpc";
+
+// Transmission answers 409 with a fresh session id, which has to be sent on every request
+const SESSION_ID_HEADER: &str = "X-Transmission-Session-Id";
+
+// Numbers used by the "status" field of torrent-get
+const STATUS_STOPPED: i64 = 0;
+const STATUS_CHECK_WAIT: i64 = 1;
+const STATUS_CHECK: i64 = 2;
+const STATUS_DOWNLOAD_WAIT: i64 = 3;
+const STATUS_DOWNLOAD: i64 = 4;
+const STATUS_SEED_WAIT: i64 = 5;
+const STATUS_SEED: i64 = 6;
+
+const TORRENT_FIELDS: [&str; 12] = [
+    "hashString",
+    "name",
+    "totalSize",
+    "status",
+    "haveValid",
+    "eta",
+    "percentDone",
+    "rateDownload",
+    "peersSendingToUs",
+    "peersGettingFromUs",
+    "downloadDir",
+    "files",
+];
+
+pub struct TransmissionClient {
+    http_client: Client,
+    url: String,
+    username: Option<String>,
+    password: Option<String>,
+    session_id: Mutex<Option<String>>,
+    download_root: Option<PathBuf>,
+}
+
+#[derive(Serialize, Debug)]
+struct RpcRequest<'a, T: Serialize> {
+    method: &'a str,
+    arguments: T,
+}
+
+#[derive(Deserialize, Debug)]
+struct RpcResponse<T> {
+    result: String,
+    arguments: Option<T>,
+}
+
+#[derive(Serialize, Debug, Default)]
+struct TorrentAddArguments {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    filename: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    metainfo: Option<String>,
+    #[serde(rename = "download-dir", skip_serializing_if = "Option::is_none")]
+    download_dir: Option<String>,
+    paused: bool,
+    // Labels exist since Transmission 3, older versions ignore them
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    labels: Vec<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct TorrentGetArguments {
+    torrents: Vec<TransmissionTorrent>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct TransmissionTorrent {
+    hash_string: String,
+    name: String,
+    total_size: i64,
+    status: i64,
+    have_valid: i64,
+    eta: i64,
+    percent_done: f64,
+    #[serde(default)]
+    rate_download: i64,
+    #[serde(default)]
+    peers_sending_to_us: i64,
+    #[serde(default)]
+    peers_getting_from_us: i64,
+    #[serde(default)]
+    download_dir: String,
+    #[serde(default)]
+    files: Vec<TransmissionFile>,
+}
+
+#[derive(Deserialize, Debug)]
+struct TransmissionFile {
+    name: String,
+}
+
+impl TransmissionTorrent {
+    fn state(&self) -> TorrentState {
+        let complete = self.percent_done >= 1.0;
+        match self.status {
+            STATUS_STOPPED if complete => TorrentState::StoppedUp,
+            STATUS_STOPPED => TorrentState::StoppedDl,
+            STATUS_CHECK_WAIT | STATUS_CHECK if complete => TorrentState::CheckingUp,
+            STATUS_CHECK_WAIT | STATUS_CHECK => TorrentState::CheckingDl,
+            STATUS_DOWNLOAD_WAIT => TorrentState::QueuedDl,
+            STATUS_DOWNLOAD => TorrentState::Downloading,
+            STATUS_SEED_WAIT => TorrentState::QueuedUp,
+            STATUS_SEED => TorrentState::Uploading,
+            _ => TorrentState::Unknown,
+        }
+    }
+
+    fn to_torrent(&self) -> Torrent {
+        Torrent::new(
+            &self.hash_string,
+            &self.name,
+            self.total_size,
+            self.state(),
+            self.have_valid,
+            self.eta,
+        )
+        .with_transfer(
+            self.percent_done,
+            self.rate_download,
+            self.peers_sending_to_us,
+            self.peers_getting_from_us,
+        )
+        .with_save_path(&self.download_dir)
+    }
+}
+
+impl TransmissionClient {
+    pub fn new(
+        url: &str,
+        username: Option<String>,
+        password: Option<String>,
+        download_root: Option<PathBuf>,
+    ) -> Self {
+        TransmissionClient {
+            http_client: Client::new(),
+            url: url.to_owned(),
+            username,
+            password,
+            session_id: Mutex::new(None),
+            download_root,
+        }
+    }
+
+    fn call<A, R>(&self, method: &str, arguments: A) -> Result<R, TorrentClientError>
+    where
+        A: Serialize,
+        R: DeserializeOwned,
+    {
+        let request = RpcRequest { method, arguments };
+
+        // The first request of a session is always refused with the id to use
+        for _ in 0..2 {
+            let mut req = self.http_client.post(&self.url).json(&request);
+            if let Some(session_id) = self.session_id.lock().unwrap().as_ref() {
+                req = req.header(SESSION_ID_HEADER, session_id);
+            }
+            if let Some(username) = &self.username {
+                req = req.basic_auth(username, self.password.as_ref());
+            }
+
+            let res = req
+                .send()
+                .into_report()
+                .attach_printable_lazy(|| format!("Failed to send {} to: {}", method, self.url))
+                .change_context(TorrentClientError)?;
+
+            if res.status() == StatusCode::CONFLICT {
+                let session_id = res
+                    .headers()
+                    .get(SESSION_ID_HEADER)
+                    .and_then(|value| value.to_str().ok())
+                    .map(str::to_owned);
+                *self.session_id.lock().unwrap() = session_id;
+                continue;
+            }
+
+            if !res.status().is_success() {
+                return Err(Report::new(TorrentClientError)
+                    .attach_printable(format!("Response failed with status: {}", res.status())));
+            }
+
+            let response: RpcResponse<R> = res
+                .json()
+                .into_report()
+                .attach_printable_lazy(|| format!("Failed to read response of: {}", method))
+                .change_context(TorrentClientError)?;
+
+            if response.result != "success" {
+                return Err(Report::new(TorrentClientError)
+                    .attach_printable(format!("{} failed: {}", method, response.result)));
+            }
+
+            return response.arguments.ok_or_else(|| {
+                Report::new(TorrentClientError)
+                    .attach_printable(format!("{} answered without arguments", method))
+            });
+        }
+
+        Err(Report::new(TorrentClientError).attach_printable(format!(
+            "Transmission refused the session id for: {}",
+            method
+        )))
+    }
+
+    fn get_torrents(
+        &self,
+        hashes: &[String],
+    ) -> Result<Vec<TransmissionTorrent>, TorrentClientError> {
+        let arguments: TorrentGetArguments = self.call(
+            "torrent-get",
+            serde_json::json!({ "ids": hashes, "fields": TORRENT_FIELDS }),
+        )?;
+
+        Ok(arguments.torrents)
+    }
+}
+
+impl TorrentClient for TransmissionClient {
+    fn add(
+        &self,
+        magnet: &MagnetLink,
+        torrent_url: Option<&str>,
+        dest: &Destination,
+        options: &TorrentAddOptions,
+    ) -> Result<String, TorrentClientError> {
+        // Transmission can't rename a torrent when adding it, so a given basename is ignored
+        let options =
+            super::apply_destination(dest, options.clone(), self.download_root.as_deref());
+
+        let metainfo = super::fetch_torrent_file_or_warn(torrent_url)
+            .map(|torrent_file| base64::engine::general_purpose::STANDARD.encode(torrent_file));
+
+        let arguments = TorrentAddArguments {
+            filename: metainfo.is_none().then(|| magnet.to_string()),
+            paused: super::add_paused(&options, metainfo.is_some()),
+            metainfo,
+            download_dir: options.savepath.clone(),
+            labels: options
+                .category
+                .iter()
+                .chain(&options.tags)
+                .cloned()
+                .collect(),
+        };
+
+        // Duplicates are answered with "torrent-duplicate" instead of failing
+        let _: serde_json::Value = self
+            .call("torrent-add", arguments)
+            .attach_printable_lazy(|| format!("Failed to add torrent: {}", magnet))?;
+
+        Ok(magnet.info_hash().to_owned())
+    }
+
+    fn list(&self, hashes: &[String]) -> Result<TorrentList, TorrentClientError> {
+        let torrents = self.get_torrents(hashes)?;
+        Ok(TorrentList(
+            torrents
+                .iter()
+                .map(TransmissionTorrent::to_torrent)
+                .collect(),
+        ))
+    }
+
+    fn remove(&self, hashes: &[String], delete_files: bool) -> Result<(), TorrentClientError> {
+        let _: serde_json::Value = self.call(
+            "torrent-remove",
+            serde_json::json!({ "ids": hashes, "delete-local-data": delete_files }),
+        )?;
+
+        Ok(())
+    }
+
+    fn select_files(
+        &self,
+        hash: &str,
+        wanted: &dyn Fn(&str) -> bool,
+        resume: bool,
+    ) -> Result<usize, TorrentClientError> {
+        let hashes = [hash.to_owned()];
+
+        let files = super::wait_for_files(hash, || {
+            Ok(self
+                .get_torrents(&hashes)?
+                .into_iter()
+                .flat_map(|torrent| torrent.files)
+                .map(|file| file.name)
+                .collect())
+        })?;
+
+        let (selected, skipped) = super::partition_files(&files, wanted);
+
+        if selected.is_empty() {
+            self.remove(&hashes, true)?;
+            return Err(Report::new(TorrentClientError)
+                .attach_printable(format!("No wanted file in torrent: {}", hash)));
+        }
+
+        if !skipped.is_empty() {
+            let _: serde_json::Value = self.call(
+                "torrent-set",
+                serde_json::json!({ "ids": hashes, "files-unwanted": skipped }),
+            )?;
+        }
+
+        if resume {
+            let _: serde_json::Value =
+                self.call("torrent-start", serde_json::json!({ "ids": hashes }))?;
+        }
+
+        Ok(selected.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_torrent_state() {
+        let torrents: TorrentGetArguments = serde_json::from_str(
+            r#"{"torrents": [
+                {"hashString": "568807a73ecd33fff3ac19f47805f0940cbdb9ac", "name": "One Piece", "totalSize": 10, "status": 6, "haveValid": 10, "eta": -1, "percentDone": 1.0},
+                {"hashString": "4936206e05d1bb04084f50032d6b3704f271eff7", "name": "Bougyoryoku", "totalSize": 10, "status": 0, "haveValid": 2, "eta": -1, "percentDone": 0.2, "files": [{"name": "Bougyoryoku - 06.mp4", "length": 10, "bytesCompleted": 2}]}
+            ]}"#,
+        )
+        .unwrap();
+
+        let list = TorrentList(
+            torrents
+                .torrents
+                .iter()
+                .map(TransmissionTorrent::to_torrent)
+                .collect(),
+        );
+        assert!(list.all_finished(&["568807a73ecd33fff3ac19f47805f0940cbdb9ac".to_string()]));
+        assert!(!list.all_finished(&["4936206e05d1bb04084f50032d6b3704f271eff7".to_string()]));
+        assert_eq!(torrents.torrents[1].files[0].name, "Bougyoryoku - 06.mp4");
+    }
+
+    #[test]
+    fn test_add_arguments_serialization() {
+        let arguments = TorrentAddArguments {
+            filename: Some(
+                "magnet:?xt=urn:btih:568807a73ecd33fff3ac19f47805f0940cbdb9ac".to_string(),
+            ),
+            download_dir: Some("/downloads/raws".to_string()),
+            labels: vec!["One Piece".to_string()],
+            ..TorrentAddArguments::default()
+        };
+
+        assert_eq!(
+            serde_json::to_value(arguments).unwrap(),
+            serde_json::json!({
+                "filename": "magnet:?xt=urn:btih:568807a73ecd33fff3ac19f47805f0940cbdb9ac",
+                "download-dir": "/downloads/raws",
+                "paused": false,
+                "labels": ["One Piece"],
+            })
+        );
+    }
+}
@@ -0,0 +1,380 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use clap::ValueEnum;
+use error_stack::Result;
+
+use crate::qbittorrent::api::torrents::TorrentList;
+
+use super::TorrentClientError;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+// What to do with a torrent that stopped making progress
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum StallPolicy {
+    // Stop waiting for it, but leave it in the client
+    Skip,
+    // Stop waiting for it and remove it along with its files
+    Remove,
+    // Keep waiting, only the overall timeout can end the wait
+    Wait,
+}
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct WaitOptions {
+    #[arg(
+        long = "timeout",
+        help = "Minutes to wait for all raws before giving up, no limit by default"
+    )]
+    pub timeout_minutes: Option<u64>,
+
+    #[arg(
+        long = "stall-timeout",
+        default_value = "10",
+        help = "Minutes without progress before a raw is stalled"
+    )]
+    pub stall_timeout_minutes: u64,
+
+    #[arg(
+        long = "metadata-timeout",
+        default_value = "5",
+        help = "Minutes spent fetching the metadata of a magnet before a raw is stalled"
+    )]
+    pub metadata_timeout_minutes: u64,
+
+    #[arg(
+        long = "on-stall",
+        default_value = "skip",
+        help = "What to do with stalled raws"
+    )]
+    pub stall_policy: StallPolicy,
+}
+
+impl Default for WaitOptions {
+    fn default() -> Self {
+        WaitOptions {
+            timeout_minutes: None,
+            stall_timeout_minutes: 10,
+            metadata_timeout_minutes: 5,
+            stall_policy: StallPolicy::Skip,
+        }
+    }
+}
+
+impl WaitOptions {
+    fn timeout(&self) -> Option<Duration> {
+        self.timeout_minutes
+            .map(|minutes| Duration::from_secs(minutes * 60))
+    }
+
+    fn stall_timeout(&self) -> Duration {
+        Duration::from_secs(self.stall_timeout_minutes * 60)
+    }
+
+    fn metadata_timeout(&self) -> Duration {
+        Duration::from_secs(self.metadata_timeout_minutes * 60)
+    }
+}
+
+// How each waited torrent ended up, by info-hash
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct WaitSummary {
+    pub finished: Vec<String>,
+    pub stalled: Vec<String>,
+    pub timed_out: Vec<String>,
+}
+
+// Remembers when each torrent last downloaded something, or started fetching its metadata
+#[derive(Debug, Default)]
+struct StallTracker {
+    last_progress: HashMap<String, (i64, Instant)>,
+    metadata_since: HashMap<String, Instant>,
+}
+
+impl StallTracker {
+    // Nothing is downloaded before the metadata is known, so the stall timeout starts
+    // over once it is
+    fn observe_metadata(&mut self, hash: &str, fetching: bool, now: Instant) {
+        match fetching {
+            true => {
+                self.metadata_since.entry(hash.to_owned()).or_insert(now);
+            }
+            false => {
+                if self.metadata_since.remove(hash).is_some() {
+                    self.last_progress.remove(hash);
+                }
+            }
+        }
+    }
+
+    fn is_stuck_on_metadata(&self, hash: &str, metadata_timeout: Duration, now: Instant) -> bool {
+        self.metadata_since
+            .get(hash)
+            .is_some_and(|since| now.duration_since(*since) >= metadata_timeout)
+    }
+
+    // A torrent not listed yet counts as not downloading anything
+    fn observe(&mut self, hash: &str, downloaded: Option<i64>, now: Instant) {
+        let downloaded = downloaded.unwrap_or(0);
+
+        match self.last_progress.get_mut(hash) {
+            Some((last_downloaded, last_time)) if downloaded > *last_downloaded => {
+                *last_downloaded = downloaded;
+                *last_time = now;
+            }
+            Some(_) => {}
+            None => {
+                self.last_progress
+                    .insert(hash.to_owned(), (downloaded, now));
+            }
+        }
+    }
+
+    fn is_stalled(&self, hash: &str, stall_timeout: Duration, now: Instant) -> bool {
+        self.last_progress
+            .get(hash)
+            .is_some_and(|(_, last_time)| now.duration_since(*last_time) >= stall_timeout)
+    }
+}
+
+// Polls `fetch` until every torrent finished, stalled or the timeout is over.
+// Stalled torrents are handed to `remove` when the policy asks for it
+pub fn poll_until_finished(
+    hashes: &[String],
+    options: &WaitOptions,
+    mut fetch: impl FnMut() -> Result<TorrentList, TorrentClientError>,
+    on_update: &dyn Fn(&TorrentList),
+    remove: impl Fn(&[String]) -> Result<(), TorrentClientError>,
+) -> Result<WaitSummary, TorrentClientError> {
+    let start = Instant::now();
+    let mut summary = WaitSummary::default();
+    let mut pending = hashes.to_vec();
+    let mut tracker = StallTracker::default();
+
+    while !pending.is_empty() {
+        log::debug!("Waiting for {} torrents to finish", pending.len());
+
+        let torrents = fetch()?;
+        log::trace!("Torrent info: {:?}", torrents);
+
+        on_update(&torrents);
+
+        if torrents.all_finished(&pending) {
+            summary.finished.append(&mut pending);
+            break;
+        }
+
+        let now = Instant::now();
+        let mut stalled = Vec::new();
+
+        pending.retain(|hash| {
+            let torrent = torrents.find(hash);
+            if torrent.is_some_and(|torrent| torrent.finished()) {
+                summary.finished.push(hash.clone());
+                return false;
+            }
+
+            let fetching_metadata =
+                torrent.is_some_and(|torrent| torrent.state().is_fetching_metadata());
+            tracker.observe_metadata(hash, fetching_metadata, now);
+            tracker.observe(hash, torrent.map(|torrent| *torrent.downloaded()), now);
+            if options.stall_policy == StallPolicy::Wait {
+                return true;
+            }
+
+            if fetching_metadata {
+                if !tracker.is_stuck_on_metadata(hash, options.metadata_timeout(), now) {
+                    return true;
+                }
+
+                log::warn!(
+                    "Torrent still fetching metadata after {} minutes, no peer may have it: {}",
+                    options.metadata_timeout_minutes,
+                    hash
+                );
+                stalled.push(hash.clone());
+                return false;
+            }
+
+            if !tracker.is_stalled(hash, options.stall_timeout(), now) {
+                return true;
+            }
+
+            log::warn!(
+                "Torrent stalled in state {:?}: {}",
+                torrent.map(|torrent| *torrent.state()),
+                hash
+            );
+            stalled.push(hash.clone());
+            false
+        });
+
+        if !stalled.is_empty() && options.stall_policy == StallPolicy::Remove {
+            remove(&stalled)?;
+        }
+        summary.stalled.extend(stalled);
+
+        if options
+            .timeout()
+            .is_some_and(|timeout| start.elapsed() >= timeout)
+        {
+            log::warn!("Timed out waiting for {} torrents", pending.len());
+            summary.timed_out.append(&mut pending);
+            break;
+        }
+
+        if !pending.is_empty() {
+            std::thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::qbittorrent::api::torrents::{Torrent, TorrentState};
+    use std::cell::{Cell, RefCell};
+
+    const HASH: &str = "568807a73ecd33fff3ac19f47805f0940cbdb9ac";
+
+    fn torrent_list(state: TorrentState, downloaded: i64) -> TorrentList {
+        TorrentList(vec![Torrent::new(
+            HASH,
+            "One Piece",
+            10,
+            state,
+            downloaded,
+            0,
+        )])
+    }
+
+    #[test]
+    fn test_stall_tracker() {
+        let mut tracker = StallTracker::default();
+        let start = Instant::now();
+        let stall_timeout = Duration::from_secs(60);
+
+        tracker.observe(HASH, None, start);
+        assert!(!tracker.is_stalled(HASH, stall_timeout, start + Duration::from_secs(30)));
+
+        tracker.observe(HASH, Some(5), start + Duration::from_secs(50));
+        assert!(!tracker.is_stalled(HASH, stall_timeout, start + Duration::from_secs(100)));
+
+        tracker.observe(HASH, Some(5), start + Duration::from_secs(110));
+        assert!(tracker.is_stalled(HASH, stall_timeout, start + Duration::from_secs(110)));
+    }
+
+    #[test]
+    fn test_poll_until_finished() {
+        let polls = Cell::new(0);
+        let fetch = || {
+            polls.set(polls.get() + 1);
+            Ok(match polls.get() {
+                1 => torrent_list(TorrentState::Downloading, 2),
+                _ => torrent_list(TorrentState::StalledUp, 10),
+            })
+        };
+
+        let summary = poll_until_finished(
+            &[HASH.to_string()],
+            &WaitOptions::default(),
+            fetch,
+            &|_| {},
+            |_| panic!("Nothing should be removed"),
+        )
+        .unwrap();
+
+        assert_eq!(summary.finished, vec![HASH.to_string()]);
+        assert!(summary.stalled.is_empty());
+    }
+
+    #[test]
+    fn test_poll_until_finished_removes_stalled() {
+        let options = WaitOptions {
+            stall_timeout_minutes: 0,
+            metadata_timeout_minutes: 0,
+            stall_policy: StallPolicy::Remove,
+            ..WaitOptions::default()
+        };
+        let removed = RefCell::new(Vec::new());
+
+        let summary = poll_until_finished(
+            &[HASH.to_string()],
+            &options,
+            || Ok(torrent_list(TorrentState::ForcedMetaDl, 0)),
+            &|_| {},
+            |hashes| {
+                removed.borrow_mut().extend_from_slice(hashes);
+                Ok(())
+            },
+        )
+        .unwrap();
+
+        assert_eq!(summary.stalled, vec![HASH.to_string()]);
+        assert_eq!(*removed.borrow(), vec![HASH.to_string()]);
+    }
+
+    #[test]
+    fn test_stall_tracker_metadata() {
+        let mut tracker = StallTracker::default();
+        let start = Instant::now();
+        let timeout = Duration::from_secs(60);
+
+        tracker.observe_metadata(HASH, true, start);
+        tracker.observe(HASH, Some(0), start);
+        tracker.observe_metadata(HASH, true, start + Duration::from_secs(30));
+        assert!(!tracker.is_stuck_on_metadata(HASH, timeout, start + Duration::from_secs(30)));
+        assert!(tracker.is_stuck_on_metadata(HASH, timeout, start + Duration::from_secs(60)));
+
+        // Once the metadata is known, the stall timeout starts from there
+        let known = start + Duration::from_secs(90);
+        tracker.observe_metadata(HASH, false, known);
+        tracker.observe(HASH, Some(0), known);
+        assert!(!tracker.is_stuck_on_metadata(HASH, timeout, known));
+        assert!(!tracker.is_stalled(HASH, timeout, known + Duration::from_secs(30)));
+    }
+
+    #[test]
+    fn test_poll_until_finished_waits_for_metadata() {
+        let options = WaitOptions {
+            timeout_minutes: Some(0),
+            stall_timeout_minutes: 0,
+            ..WaitOptions::default()
+        };
+
+        let summary = poll_until_finished(
+            &[HASH.to_string()],
+            &options,
+            || Ok(torrent_list(TorrentState::MetaDl, 0)),
+            &|_| {},
+            |_| panic!("Nothing should be removed"),
+        )
+        .unwrap();
+
+        assert!(summary.stalled.is_empty());
+        assert_eq!(summary.timed_out, vec![HASH.to_string()]);
+    }
+
+    #[test]
+    fn test_poll_until_finished_times_out() {
+        let options = WaitOptions {
+            timeout_minutes: Some(0),
+            stall_policy: StallPolicy::Wait,
+            ..WaitOptions::default()
+        };
+
+        let summary = poll_until_finished(
+            &[HASH.to_string()],
+            &options,
+            || Ok(TorrentList(Vec::new())),
+            &|_| {},
+            |_| Ok(()),
+        )
+        .unwrap();
+
+        assert_eq!(summary.timed_out, vec![HASH.to_string()]);
+    }
+}
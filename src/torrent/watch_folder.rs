@@ -0,0 +1,150 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use error_stack::{IntoReport, Report, Result, ResultExt};
+
+use crate::core::download::downloader::Destination;
+use crate::core::magnet::MagnetLink;
+use crate::qbittorrent::api::torrents::{TorrentAddOptions, TorrentList};
+
+use super::{TorrentClient, TorrentClientError, WaitOptions, WaitSummary};
+
+const TORRENT_EXTENSION: &str = "torrent";
+const MAGNET_EXTENSION: &str = "magnet";
+
+// Drops .torrent/.magnet files into a folder watched by any torrent client. The client is never
+// talked to, so destinations, options and progress are all up to it
+pub struct WatchFolderClient {
+    folder: PathBuf,
+}
+
+impl WatchFolderClient {
+    pub fn new(folder: PathBuf) -> Self {
+        WatchFolderClient { folder }
+    }
+
+    fn file_path(&self, hash: &str, extension: &str) -> PathBuf {
+        self.folder.join(hash).with_extension(extension)
+    }
+}
+
+// Clients may pick up a file while it's being written, so it only appears once complete
+fn write_atomically(path: &Path, contents: &[u8]) -> Result<(), TorrentClientError> {
+    let partial_path = path.with_extension("part");
+
+    fs::write(&partial_path, contents)
+        .and_then(|_| fs::rename(&partial_path, path))
+        .into_report()
+        .attach_printable_lazy(|| format!("Failed to write: {}", path.display()))
+        .change_context(TorrentClientError)
+}
+
+impl TorrentClient for WatchFolderClient {
+    fn add(
+        &self,
+        magnet: &MagnetLink,
+        torrent_url: Option<&str>,
+        dest: &Destination,
+        _options: &TorrentAddOptions,
+    ) -> Result<String, TorrentClientError> {
+        log::debug!(
+            "Ignoring destination {:?}, the watching client decides it",
+            dest
+        );
+
+        fs::create_dir_all(&self.folder)
+            .into_report()
+            .attach_printable_lazy(|| format!("Failed to create: {}", self.folder.display()))
+            .change_context(TorrentClientError)?;
+
+        let hash = magnet.info_hash();
+        match super::fetch_torrent_file_or_warn(torrent_url) {
+            Some(torrent_file) => {
+                write_atomically(&self.file_path(hash, TORRENT_EXTENSION), &torrent_file)?
+            }
+            None => write_atomically(
+                &self.file_path(hash, MAGNET_EXTENSION),
+                magnet.to_string().as_bytes(),
+            )?,
+        }
+
+        Ok(hash.to_owned())
+    }
+
+    // Nothing is known about torrents once the client picked them up
+    fn list(&self, _hashes: &[String]) -> Result<TorrentList, TorrentClientError> {
+        Ok(TorrentList(Vec::new()))
+    }
+
+    // Only files the client didn't pick up yet can be taken back
+    fn remove(&self, hashes: &[String], _delete_files: bool) -> Result<(), TorrentClientError> {
+        for hash in hashes {
+            for extension in [TORRENT_EXTENSION, MAGNET_EXTENSION] {
+                let path = self.file_path(hash, extension);
+                if path.exists() {
+                    fs::remove_file(&path)
+                        .into_report()
+                        .attach_printable_lazy(|| format!("Failed to remove: {}", path.display()))
+                        .change_context(TorrentClientError)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn select_files(
+        &self,
+        hash: &str,
+        _wanted: &dyn Fn(&str) -> bool,
+        _resume: bool,
+    ) -> Result<usize, TorrentClientError> {
+        Err(Report::new(TorrentClientError).attach_printable(format!(
+            "Can't select files through a watch folder, torrent: {}",
+            hash
+        )))
+    }
+
+    fn wait_for_completion(
+        &self,
+        _hashes: &[String],
+        _options: &WaitOptions,
+        _on_update: &dyn Fn(&TorrentList),
+    ) -> Result<WaitSummary, TorrentClientError> {
+        log::info!("Progress of torrents in a watch folder isn't known, not waiting for them");
+        Ok(WaitSummary::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::unique_temp_dir;
+
+    #[test]
+    fn test_add_and_remove_magnet() {
+        let folder = unique_temp_dir("watch-folder");
+        let client = WatchFolderClient::new(folder.clone());
+        let magnet =
+            MagnetLink::parse("magnet:?xt=urn:btih:568807a73ecd33fff3ac19f47805f0940cbdb9ac")
+                .unwrap();
+
+        let hash = client
+            .add(
+                &magnet,
+                None,
+                &Destination::Default,
+                &TorrentAddOptions::default(),
+            )
+            .unwrap();
+
+        let path = folder.join("568807a73ecd33fff3ac19f47805f0940cbdb9ac.magnet");
+        assert_eq!(hash, "568807a73ecd33fff3ac19f47805f0940cbdb9ac");
+        assert_eq!(fs::read_to_string(&path).unwrap(), magnet.to_string());
+
+        client.remove(&[hash], false).unwrap();
+        assert!(!path.exists());
+
+        fs::remove_dir_all(&folder).unwrap();
+    }
+}
@@ -0,0 +1,655 @@
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::core::aliases::TitleAliases;
+use crate::core::download::downloader::{Destination, FileDownloader};
+use crate::core::episode::parse_episode_number;
+use crate::core::indexer::Indexer;
+use crate::hooks::{HookEvent, Hooks};
+use crate::library;
+use crate::metadata::anilist::AniListClient;
+use crate::metadata::AnimeMetadata;
+use crate::mux::MuxOptions;
+use crate::qbittorrent::api::torrents::TorrentAddOptions;
+use crate::raws;
+use crate::raws::search::AnimeRawData;
+use crate::subs;
+use crate::subs::download::AnimeSubsDownloader;
+use crate::subs::language::SubLanguage;
+use crate::subs::search::IndexerMatch;
+use crate::torrent::TorrentClient;
+
+use super::state::WatchState;
+use super::watchlist::{Watchlist, WatchlistEntry};
+
+use error_stack::{Report, Result, ResultExt};
+
+#[derive(Debug)]
+pub struct WatchError;
+
+impl std::fmt::Display for WatchError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Problem while polling the watchlist")
+    }
+}
+
+impl Error for WatchError {}
+
+// Searches nyaa for the raws of a query
+type RawsSearch = Box<dyn Fn(&str) -> std::result::Result<Vec<AnimeRawData>, Box<dyn Error>>>;
+// Finds the kitsunekko folder of an entry along with its subtitle files
+type SubsSearch = Box<dyn Fn(&WatchlistEntry, SubLanguage, &TitleAliases, i64) -> SubsSearchResult>;
+type SubsSearchResult = Result<(Indexer, Vec<Indexer>), WatchError>;
+
+pub struct WatchDaemon {
+    watchlist: Watchlist,
+    state: WatchState,
+    state_path: PathBuf,
+    torrent_client: Box<dyn TorrentClient>,
+    aliases: TitleAliases,
+    hooks: Hooks,
+    metadata_client: Option<AniListClient>,
+    // Looked up once per entry, None when the anime isn't on AniList
+    metadata: HashMap<String, Option<AnimeMetadata>>,
+    mux_options: Option<MuxOptions>,
+    // The folder the subtitles of each entry are saved to, known once they were polled
+    subs_folders: HashMap<String, PathBuf>,
+    min_score: i64,
+    languages: Vec<SubLanguage>,
+    // The sites are only reached through these, so tests can replace them
+    search_raws: RawsSearch,
+    search_subs: SubsSearch,
+    subs_root: PathBuf,
+}
+
+impl WatchDaemon {
+    pub fn new(
+        watchlist: Watchlist,
+        state: WatchState,
+        state_path: PathBuf,
+        torrent_client: Box<dyn TorrentClient>,
+        aliases: TitleAliases,
+        hooks: Hooks,
+    ) -> Self {
+        WatchDaemon {
+            watchlist,
+            state,
+            state_path,
+            torrent_client,
+            aliases,
+            hooks,
+            metadata_client: None,
+            metadata: HashMap::new(),
+            mux_options: None,
+            subs_folders: HashMap::new(),
+            min_score: subs::search::DEFAULT_MIN_SCORE,
+            languages: vec![SubLanguage::Japanese],
+            search_raws: Box::new(raws::search::search_anime_raws),
+            search_subs: Box::new(search_subs),
+            subs_root: PathBuf::from(subs::download::DEFAULT_FOLDER),
+        }
+    }
+
+    // With metadata the entries get the other titles of their series when searching subs,
+    // and finished series stop getting raws once all their episodes were fetched
+    pub fn with_metadata(mut self, metadata_client: AniListClient) -> Self {
+        self.metadata_client = Some(metadata_client);
+        self
+    }
+
+    // With mux options, the subtitles are added to the raws once both were downloaded
+    pub fn with_mux(mut self, mux_options: MuxOptions) -> Self {
+        self.mux_options = Some(mux_options);
+        self
+    }
+
+    // Entries without a pinned kitsunekko series only get subtitles from folders scoring this
+    pub fn with_min_score(mut self, min_score: i64) -> Self {
+        self.min_score = min_score;
+        self
+    }
+
+    // The kitsunekko languages to download, the subtitles are tagged with their language
+    pub fn with_languages(mut self, languages: Vec<SubLanguage>) -> Self {
+        self.languages = languages;
+        self
+    }
+
+    pub fn run(&mut self, interval: Duration) -> Result<(), WatchError> {
+        loop {
+            self.poll_once()?;
+
+            log::info!("Next poll in {} minutes", interval.as_secs() / 60);
+            std::thread::sleep(interval);
+        }
+    }
+
+    // A failing entry is only logged, so one broken series doesn't stop the others from updating
+    pub fn poll_once(&mut self) -> Result<(), WatchError> {
+        // Before enqueuing new raws, which the client may not list yet
+        match self.poll_completed() {
+            Ok(count) => log::info!("{} raws finished downloading", count),
+            Err(e) => log::error!("\n{e:?}"),
+        }
+
+        let entries = self.watchlist.0.clone();
+        log::info!("Polling {} watchlist entries", entries.len());
+
+        for entry in &entries {
+            // Subtitles and muxing of a complete series may still be pending, only raws are done
+            let complete = self.entry_metadata(entry).is_some_and(|metadata| {
+                metadata.is_complete(&self.state.fetched_episodes(entry.name()))
+            });
+            if complete {
+                log::info!(
+                    "All episodes of {} were fetched, not searching raws",
+                    entry.name()
+                );
+            } else {
+                match self.poll_raws(entry) {
+                    Ok(count) => log::info!("Enqueued {} new raws for {}", count, entry.name()),
+                    Err(e) => log::error!("\n{e:?}"),
+                }
+            }
+
+            for language in self.languages.clone() {
+                match self.poll_subs(entry, language) {
+                    Ok(count) => log::info!(
+                        "Downloaded {} new {} subs for {}",
+                        count,
+                        language.code(),
+                        entry.name()
+                    ),
+                    Err(e) => log::error!("\n{e:?}"),
+                }
+            }
+
+            if let Some(mux_options) = self.mux_options.clone() {
+                match self.poll_mux(entry, &mux_options) {
+                    Ok(count) => log::info!("Muxed {} new raws for {}", count, entry.name()),
+                    Err(e) => log::error!("\n{e:?}"),
+                }
+            }
+
+            // Save after every entry so an interrupted poll keeps its progress
+            self.state
+                .save(&self.state_path)
+                .attach_printable("Failed to save watch state")
+                .change_context(WatchError)?;
+        }
+
+        self.hooks.fire(HookEvent::RunFinished {
+            name: "watchlist".to_string(),
+        });
+
+        Ok(())
+    }
+
+    fn poll_raws(&mut self, entry: &WatchlistEntry) -> Result<usize, WatchError> {
+        log::debug!("Polling raws for: {}", entry.name());
+        // Raws are named after the romaji title more often than after the one in the watchlist
+        let metadata = self.entry_metadata(entry);
+        let search_entry = match metadata.as_ref().and_then(AnimeMetadata::title) {
+            Some(title) => entry.with_name(title),
+            None => entry.clone(),
+        };
+
+        let raws = (self.search_raws)(&search_entry.raw_query()).map_err(|e| {
+            Report::new(WatchError)
+                .attach_printable(format!("Failed to search raws for: {}", entry.name()))
+                .attach_printable(e.to_string())
+        })?;
+
+        let dest = Destination::GivenFolderGuessFileBasename(format!(
+            "{}/{}",
+            raws::download::DEFAULT_FOLDER,
+            entry.name()
+        ));
+
+        let mut count = 0;
+        let raws = raws.iter().filter(|raw_data| {
+            let episode = parse_episode_number(&raw_data.anime_name);
+            search_entry.matches_raw(raw_data)
+                && metadata
+                    .as_ref()
+                    .zip(episode)
+                    .is_none_or(|(metadata, episode)| metadata.has_episode(episode))
+        });
+        for raw_data in raws {
+            if self
+                .state
+                .is_raw_fetched(raw_data.anime_raw_magnet.info_hash())
+            {
+                continue;
+            }
+
+            let result = self.torrent_client.add(
+                &raw_data.anime_raw_magnet,
+                Some(&raw_data.anime_raw_torrent_url),
+                &dest,
+                &TorrentAddOptions::for_series(entry.name()),
+            );
+
+            match result {
+                Ok(hash) => {
+                    log::info!("Enqueued new raw: {}", raw_data.anime_name);
+                    self.hooks.fire(HookEvent::TorrentAdded {
+                        name: raw_data.anime_name.clone(),
+                        hash: hash.clone(),
+                    });
+                    self.state
+                        .mark_raw_fetched(raw_data.anime_raw_magnet.info_hash());
+                    self.state.mark_raw_downloading(&hash);
+                    if self.mux_options.is_some() {
+                        self.state.mark_raw_unmuxed(&hash, entry.name());
+                    }
+                    if let Some(episode) = parse_episode_number(&raw_data.anime_name) {
+                        self.state.mark_episode_fetched(entry.name(), episode);
+                    }
+                    count += 1;
+                }
+                Err(e) => log::error!("\n{e:?}"),
+            }
+        }
+
+        Ok(count)
+    }
+
+    fn poll_subs(
+        &mut self,
+        entry: &WatchlistEntry,
+        language: SubLanguage,
+    ) -> Result<usize, WatchError> {
+        log::debug!("Polling {} subs for: {}", language.code(), entry.name());
+        let mut aliases = self.aliases.clone();
+        if let Some(metadata) = self.entry_metadata(entry) {
+            aliases.add(metadata.titles());
+        }
+        let (anime_indexer, subs_indexers) =
+            (self.search_subs)(entry, language, &aliases, self.min_score)?;
+
+        let subs_folder = self.subs_root.join(anime_indexer.name());
+        // Only Japanese subtitles are muxed
+        if language == SubLanguage::Japanese {
+            self.subs_folders
+                .insert(entry.name().clone(), subs_folder.clone());
+        }
+        let dest =
+            Destination::GivenFolderGuessFileBasename(subs_folder.to_string_lossy().into_owned());
+
+        let subs_downloader = AnimeSubsDownloader::new().with_language(language);
+        let mut count = 0;
+        for subs_indexer in &subs_indexers {
+            if self.state.is_sub_fetched(subs_indexer.uri()) {
+                continue;
+            }
+            if subs::download::is_archive(subs_indexer.name()) {
+                log::debug!("Skipping archive: {}", subs_indexer.name());
+                continue;
+            }
+
+            match subs_downloader.download_indexer_to_file(subs_indexer, &dest) {
+                Ok(_) => {
+                    log::info!("Downloaded new subs: {}", subs_indexer.name());
+                    let path = subs_downloader.indexer_file_path(subs_indexer, &dest);
+                    self.hooks.fire(HookEvent::SubtitleSaved {
+                        name: subs_indexer.name().to_owned(),
+                        path: path.display().to_string(),
+                    });
+                    self.state.mark_sub_fetched(subs_indexer.uri());
+                    count += 1;
+                }
+                Err(e) => log::error!("\n{e:?}"),
+            }
+        }
+
+        Ok(count)
+    }
+
+    // Subtitles often come out days after the raw, so a finished raw stays pending until
+    // every one of its videos got a Japanese track
+    fn poll_mux(
+        &mut self,
+        entry: &WatchlistEntry,
+        mux_options: &MuxOptions,
+    ) -> Result<usize, WatchError> {
+        let hashes = self.state.unmuxed_raws(entry.name());
+        let Some(subs_folder) = self.subs_folders.get(entry.name()) else {
+            return Ok(0);
+        };
+        if hashes.is_empty() {
+            return Ok(0);
+        }
+
+        let subtitles = find_subtitles(subs_folder)?;
+        let torrents = self.torrent_client.list(&hashes).map_err(|e| {
+            Report::new(WatchError)
+                .attach_printable(format!("Failed to list the raws of: {}", entry.name()))
+                .attach_printable(e.to_string())
+        })?;
+
+        let mut count = 0;
+        for hash in &hashes {
+            let Some(torrent) = torrents.find(hash) else {
+                log::warn!(
+                    "Raw not in the torrent client anymore, not muxing it: {}",
+                    hash
+                );
+                self.state.mark_raw_muxed(hash);
+                continue;
+            };
+            if !torrent.finished() {
+                continue;
+            }
+
+            let videos = library::muxing::torrent_videos(torrent).map_err(|e| {
+                Report::new(WatchError)
+                    .attach_printable(format!("Failed to list the videos of: {}", torrent.name()))
+                    .attach_printable(e.to_string())
+            })?;
+            let (muxed, complete) = library::muxing::mux_videos(&videos, &subtitles, mux_options);
+            count += muxed;
+            if complete {
+                self.state.mark_raw_muxed(hash);
+            }
+        }
+
+        Ok(count)
+    }
+
+    // Fires TorrentCompleted once for each enqueued raw, the first time it's seen finished
+    fn poll_completed(&mut self) -> Result<usize, WatchError> {
+        let hashes = self.state.downloading_raws();
+        if hashes.is_empty() {
+            return Ok(0);
+        }
+
+        let torrents = self.torrent_client.list(&hashes).map_err(|e| {
+            Report::new(WatchError)
+                .attach_printable("Failed to list the downloading raws")
+                .attach_printable(e.to_string())
+        })?;
+
+        let mut count = 0;
+        for hash in &hashes {
+            let Some(torrent) = torrents.find(hash) else {
+                log::warn!("Raw not in the torrent client anymore: {}", hash);
+                self.state.mark_raw_completed(hash);
+                continue;
+            };
+            if !torrent.finished() {
+                continue;
+            }
+
+            self.hooks.fire(HookEvent::TorrentCompleted {
+                name: torrent.name().clone(),
+                hash: hash.clone(),
+                save_path: torrent.save_path().clone(),
+            });
+            self.state.mark_raw_completed(hash);
+            count += 1;
+        }
+
+        self.state
+            .save(&self.state_path)
+            .attach_printable("Failed to save watch state")
+            .change_context(WatchError)?;
+
+        Ok(count)
+    }
+
+    // Failed lookups aren't remembered, they are tried again on the next poll
+    fn entry_metadata(&mut self, entry: &WatchlistEntry) -> Option<AnimeMetadata> {
+        let metadata_client = self.metadata_client.as_ref()?;
+
+        if !self.metadata.contains_key(entry.name()) {
+            match metadata_client.search(entry.name()) {
+                Ok(metadata) => {
+                    self.metadata.insert(entry.name().clone(), metadata);
+                }
+                Err(e) => {
+                    log::warn!("\n{e:?}");
+                    return None;
+                }
+            }
+        }
+
+        self.metadata.get(entry.name()).cloned().flatten()
+    }
+}
+
+fn find_subtitles(subs_folder: &Path) -> Result<BTreeMap<u32, PathBuf>, WatchError> {
+    library::muxing::find_japanese_subtitles(subs_folder).map_err(|e| {
+        Report::new(WatchError)
+            .attach_printable(format!(
+                "Failed to list subtitles in: {}",
+                subs_folder.display()
+            ))
+            .attach_printable(e.to_string())
+    })
+}
+
+fn search_subs(
+    entry: &WatchlistEntry,
+    language: SubLanguage,
+    aliases: &TitleAliases,
+    min_score: i64,
+) -> SubsSearchResult {
+    let anime_indexer = find_subs_indexer(entry, language, aliases, min_score)?;
+
+    let subs_indexers = subs::search::fetch_sub_files(&anime_indexer).map_err(|e| {
+        Report::new(WatchError)
+            .attach_printable(format!("Failed to fetch sub files for: {}", entry.name()))
+            .attach_printable(e.to_string())
+    })?;
+
+    Ok((anime_indexer, subs_indexers))
+}
+
+// Uses the pinned kitsunekko series when there is one, otherwise the best fuzzy match
+fn find_subs_indexer(
+    entry: &WatchlistEntry,
+    language: SubLanguage,
+    aliases: &TitleAliases,
+    min_score: i64,
+) -> Result<Indexer, WatchError> {
+    let indexers = match entry.subs_id() {
+        Some(subs_id) => subs::search::fetch_indexers(language).map(|indexers| {
+            indexers
+                .into_iter()
+                .filter(|indexer| indexer.name() == subs_id)
+                .collect::<Vec<_>>()
+        }),
+        None => {
+            subs::search::fetch_best_indexers_for(entry.name(), *entry.season(), language, aliases)
+                .map(|matches| {
+                    matches
+                        .into_iter()
+                        .take(1)
+                        .filter(|best| best.score() >= min_score)
+                        .map(IndexerMatch::into_indexer)
+                        .collect::<Vec<_>>()
+                })
+        }
+    };
+
+    let indexers = indexers.map_err(|e| {
+        Report::new(WatchError)
+            .attach_printable(format!("Failed to fetch indexers for: {}", entry.name()))
+            .attach_printable(e.to_string())
+    })?;
+
+    indexers.into_iter().next().ok_or_else(|| {
+        Report::new(WatchError).attach_printable(format!("Subs not found for: {}", entry.name()))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::magnet::MagnetLink;
+    use crate::qbittorrent::api::torrents::{Torrent, TorrentList, TorrentState};
+    use crate::test_util::unique_temp_dir;
+    use crate::torrent::TorrentClientError;
+
+    const FINISHED_HASH: &str = "568807a73ecd33fff3ac19f47805f0940cbdb9ac";
+    const DOWNLOADING_HASH: &str = "4936206e05d1bb04084f50032d6b3704f271eff7";
+
+    // Lists the same torrents on every call and never adds any
+    struct FakeClient(fn() -> Vec<Torrent>);
+
+    impl TorrentClient for FakeClient {
+        fn add(
+            &self,
+            _magnet: &MagnetLink,
+            _torrent_url: Option<&str>,
+            _dest: &Destination,
+            _options: &TorrentAddOptions,
+        ) -> Result<String, TorrentClientError> {
+            Err(Report::new(TorrentClientError))
+        }
+
+        fn list(&self, _hashes: &[String]) -> Result<TorrentList, TorrentClientError> {
+            Ok(TorrentList((self.0)()))
+        }
+
+        fn remove(
+            &self,
+            _hashes: &[String],
+            _delete_files: bool,
+        ) -> Result<(), TorrentClientError> {
+            Ok(())
+        }
+
+        fn select_files(
+            &self,
+            _hash: &str,
+            _wanted: &dyn Fn(&str) -> bool,
+            _resume: bool,
+        ) -> Result<usize, TorrentClientError> {
+            Ok(0)
+        }
+    }
+
+    // Appends the name of each completed torrent to the output file
+    fn completed_hooks(output: &Path) -> Hooks {
+        serde_json::from_value(serde_json::json!([{
+            "on": ["torrent_completed"],
+            "type": "command",
+            "program": "sh",
+            "args": ["-c", format!("echo {{name}} >> {}", output.display())],
+        }]))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_poll_completed_fires_once_per_raw() {
+        let folder = unique_temp_dir("watch-completed");
+        let output = folder.join("output");
+
+        let mut state = WatchState::default();
+        state.mark_raw_downloading(FINISHED_HASH);
+        state.mark_raw_downloading(DOWNLOADING_HASH);
+        let client = FakeClient(|| {
+            vec![
+                Torrent::new(FINISHED_HASH, "Bocchi", 10, TorrentState::Uploading, 10, 0),
+                Torrent::new(
+                    DOWNLOADING_HASH,
+                    "Bougyoryoku",
+                    10,
+                    TorrentState::Downloading,
+                    2,
+                    60,
+                ),
+            ]
+        });
+        let mut daemon = WatchDaemon::new(
+            Watchlist::default(),
+            state,
+            folder.join("state.json"),
+            Box::new(client),
+            TitleAliases::default(),
+            completed_hooks(&output),
+        );
+
+        assert_eq!(daemon.poll_completed().unwrap(), 1);
+        assert_eq!(daemon.poll_completed().unwrap(), 0);
+
+        assert_eq!(std::fs::read_to_string(&output).unwrap(), "Bocchi\n");
+        assert_eq!(
+            WatchState::load(&folder.join("state.json"))
+                .unwrap()
+                .downloading_raws(),
+            vec![DOWNLOADING_HASH.to_string()]
+        );
+
+        std::fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[test]
+    fn test_complete_series_still_gets_new_subs() {
+        let folder = unique_temp_dir("watch-complete");
+        let mut server = mockito::Server::new();
+        let _anilist_mock = server
+            .mock("POST", "/")
+            .with_body(
+                r#"{"data": {"Media": {
+                    "id": 130003,
+                    "title": {"romaji": "Bocchi the Rock!", "english": null, "native": null},
+                    "synonyms": [],
+                    "season": null,
+                    "seasonYear": null,
+                    "episodes": 1,
+                    "status": "FINISHED"
+                }}}"#,
+            )
+            .create();
+        let subs_mock = server
+            .mock("GET", "/bocchi/ep01.ass")
+            .with_body("[Script Info]\nTitle: Bocchi\n")
+            .create();
+
+        let mut state = WatchState::default();
+        state.mark_episode_fetched("Bocchi the Rock!", 1);
+        let mut daemon = WatchDaemon::new(
+            Watchlist(vec![WatchlistEntry::new(
+                "Bocchi the Rock!",
+                None,
+                None,
+                None,
+            )]),
+            state,
+            folder.join("state.json"),
+            Box::new(FakeClient(Vec::new)),
+            TitleAliases::default(),
+            Hooks::default(),
+        )
+        .with_metadata(AniListClient::new(&server.url()));
+        daemon.search_raws =
+            Box::new(|query| panic!("Searched raws of a complete series: {query}"));
+        let subs_uri = format!("{}/bocchi/ep01.ass", server.url());
+        let found_uri = subs_uri.clone();
+        daemon.search_subs = Box::new(move |_, _, _, _| {
+            Ok((
+                Indexer::new("Bocchi the Rock!", "https://kitsunekko.net/bocchi"),
+                vec![Indexer::new("Bocchi the Rock! 01.ass", &found_uri)],
+            ))
+        });
+        daemon.subs_root = folder.join("subs");
+
+        daemon.poll_once().unwrap();
+
+        subs_mock.assert();
+        assert!(daemon.state.is_sub_fetched(&subs_uri));
+        assert_eq!(
+            std::fs::read_dir(folder.join("subs").join("Bocchi the Rock!"))
+                .unwrap()
+                .count(),
+            1
+        );
+
+        std::fs::remove_dir_all(&folder).unwrap();
+    }
+}
@@ -0,0 +1,3 @@
+pub mod daemon;
+pub mod state;
+pub mod watchlist;
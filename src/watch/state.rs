@@ -0,0 +1,188 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use error_stack::Result;
+
+use crate::core::storage::{self, JsonFileError};
+
+pub const DEFAULT_STATE_PATH: &str = "watch_state.json";
+
+// Remembers what was already fetched so polling never enqueues the same thing twice.
+// Raws are keyed by info-hash and subs by their kitsunekko uri
+#[derive(Deserialize, Serialize, Debug, Default)]
+pub struct WatchState {
+    fetched_raws: HashSet<String>,
+    fetched_subs: HashSet<String>,
+    // The episode numbers of the fetched raws of each watchlist entry
+    #[serde(default)]
+    fetched_episodes: HashMap<String, BTreeSet<u32>>,
+    // The watchlist entry of each raw waiting for its subtitles to be muxed, by info-hash
+    #[serde(default)]
+    unmuxed_raws: HashMap<String, String>,
+    // The info-hashes of the enqueued raws that didn't finish downloading yet
+    #[serde(default)]
+    downloading_raws: HashSet<String>,
+}
+
+impl WatchState {
+    pub fn load(path: &Path) -> Result<Self, JsonFileError> {
+        storage::load_json(path)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), JsonFileError> {
+        storage::save_json(self, path)
+    }
+
+    pub fn is_raw_fetched(&self, info_hash: &str) -> bool {
+        self.fetched_raws.contains(info_hash)
+    }
+
+    pub fn mark_raw_fetched(&mut self, info_hash: &str) {
+        self.fetched_raws.insert(info_hash.to_owned());
+    }
+
+    pub fn mark_episode_fetched(&mut self, name: &str, episode: u32) {
+        self.fetched_episodes
+            .entry(name.to_owned())
+            .or_default()
+            .insert(episode);
+    }
+
+    pub fn fetched_episodes(&self, name: &str) -> BTreeSet<u32> {
+        self.fetched_episodes.get(name).cloned().unwrap_or_default()
+    }
+
+    pub fn mark_raw_unmuxed(&mut self, info_hash: &str, name: &str) {
+        self.unmuxed_raws
+            .insert(info_hash.to_owned(), name.to_owned());
+    }
+
+    pub fn mark_raw_muxed(&mut self, info_hash: &str) {
+        self.unmuxed_raws.remove(info_hash);
+    }
+
+    pub fn unmuxed_raws(&self, name: &str) -> Vec<String> {
+        let mut hashes: Vec<String> = self
+            .unmuxed_raws
+            .iter()
+            .filter(|(_, entry_name)| *entry_name == name)
+            .map(|(info_hash, _)| info_hash.clone())
+            .collect();
+        hashes.sort();
+        hashes
+    }
+
+    pub fn mark_raw_downloading(&mut self, info_hash: &str) {
+        self.downloading_raws.insert(info_hash.to_owned());
+    }
+
+    pub fn mark_raw_completed(&mut self, info_hash: &str) {
+        self.downloading_raws.remove(info_hash);
+    }
+
+    pub fn downloading_raws(&self) -> Vec<String> {
+        let mut hashes: Vec<String> = self.downloading_raws.iter().cloned().collect();
+        hashes.sort();
+        hashes
+    }
+
+    pub fn is_sub_fetched(&self, uri: &str) -> bool {
+        self.fetched_subs.contains(uri)
+    }
+
+    pub fn mark_sub_fetched(&mut self, uri: &str) {
+        self.fetched_subs.insert(uri.to_owned());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::unique_temp_dir;
+
+    #[test]
+    fn test_mark_fetched() {
+        let mut state = WatchState::default();
+        assert!(!state.is_raw_fetched("568807a73ecd33fff3ac19f47805f0940cbdb9ac"));
+
+        state.mark_raw_fetched("568807a73ecd33fff3ac19f47805f0940cbdb9ac");
+        state.mark_sub_fetched("https://kitsunekko.net/subtitles/japanese/ep01.srt");
+
+        assert!(state.is_raw_fetched("568807a73ecd33fff3ac19f47805f0940cbdb9ac"));
+        assert!(state.is_sub_fetched("https://kitsunekko.net/subtitles/japanese/ep01.srt"));
+        assert!(!state.is_sub_fetched("568807a73ecd33fff3ac19f47805f0940cbdb9ac"));
+    }
+
+    #[test]
+    fn test_mark_episode_fetched() {
+        let mut state = WatchState::default();
+        state.mark_episode_fetched("Bocchi the Rock!", 2);
+        state.mark_episode_fetched("Bocchi the Rock!", 1);
+
+        assert_eq!(
+            state.fetched_episodes("Bocchi the Rock!"),
+            BTreeSet::from([1, 2])
+        );
+        assert!(state.fetched_episodes("One Piece").is_empty());
+    }
+
+    #[test]
+    fn test_mark_raw_muxed() {
+        let mut state = WatchState::default();
+        state.mark_raw_unmuxed(
+            "568807a73ecd33fff3ac19f47805f0940cbdb9ac",
+            "Bocchi the Rock!",
+        );
+        state.mark_raw_unmuxed(
+            "4936206e05d1bb04084f50032d6b3704f271eff7",
+            "Bocchi the Rock!",
+        );
+        state.mark_raw_unmuxed("0000000000000000000000000000000000000000", "One Piece");
+        state.mark_raw_muxed("568807a73ecd33fff3ac19f47805f0940cbdb9ac");
+
+        assert_eq!(
+            state.unmuxed_raws("Bocchi the Rock!"),
+            vec!["4936206e05d1bb04084f50032d6b3704f271eff7".to_string()]
+        );
+        assert!(state.unmuxed_raws("Bougyoryoku").is_empty());
+    }
+
+    #[test]
+    fn test_mark_raw_completed() {
+        let mut state = WatchState::default();
+        state.mark_raw_downloading("568807a73ecd33fff3ac19f47805f0940cbdb9ac");
+        state.mark_raw_downloading("4936206e05d1bb04084f50032d6b3704f271eff7");
+        state.mark_raw_completed("568807a73ecd33fff3ac19f47805f0940cbdb9ac");
+
+        assert_eq!(
+            state.downloading_raws(),
+            vec!["4936206e05d1bb04084f50032d6b3704f271eff7".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let folder = unique_temp_dir("watch-state");
+        let path = folder.join("state.json");
+
+        let mut state = WatchState::default();
+        state.mark_raw_fetched("568807a73ecd33fff3ac19f47805f0940cbdb9ac");
+        state.save(&path).unwrap();
+
+        let loaded = WatchState::load(&path).unwrap();
+        assert!(loaded.is_raw_fetched("568807a73ecd33fff3ac19f47805f0940cbdb9ac"));
+
+        std::fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[test]
+    fn test_load_missing_file_is_empty() {
+        let folder = unique_temp_dir("missing-watch-state");
+        let state = WatchState::load(&folder.join("state.json")).unwrap();
+        assert!(!state.is_raw_fetched("568807a73ecd33fff3ac19f47805f0940cbdb9ac"));
+
+        std::fs::remove_dir_all(&folder).unwrap();
+    }
+}
@@ -0,0 +1,164 @@
+use std::path::Path;
+
+use derive_getters::Getters;
+use serde::{Deserialize, Serialize};
+
+use error_stack::Result;
+
+use crate::raws::search::AnimeRawData;
+
+use crate::core::storage::{self, JsonFileError};
+
+pub const DEFAULT_WATCHLIST_PATH: &str = "watchlist.json";
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, Getters)]
+pub struct WatchlistEntry {
+    name: String,
+    raw_group: Option<String>,
+    resolution: Option<String>,
+    subs_id: Option<String>,
+    // Overrides the season in the name when matching kitsunekko folders
+    season: Option<u32>,
+}
+
+impl WatchlistEntry {
+    pub fn new(
+        name: &str,
+        raw_group: Option<&str>,
+        resolution: Option<&str>,
+        subs_id: Option<&str>,
+    ) -> Self {
+        WatchlistEntry {
+            name: name.to_owned(),
+            raw_group: raw_group.map(str::to_owned),
+            resolution: resolution.map(str::to_owned),
+            subs_id: subs_id.map(str::to_owned),
+            season: None,
+        }
+    }
+
+    pub fn with_season(mut self, season: Option<u32>) -> Self {
+        self.season = season;
+        self
+    }
+
+    // The same entry searched under another title, e.g. the canonical one of its series
+    pub fn with_name(&self, name: &str) -> Self {
+        WatchlistEntry {
+            name: name.to_owned(),
+            ..self.clone()
+        }
+    }
+
+    // Nyaa only matches titles containing every term, so the group narrows the search server-side
+    pub fn raw_query(&self) -> String {
+        match &self.raw_group {
+            Some(raw_group) => format!("{} {}", self.name, raw_group),
+            None => self.name.clone(),
+        }
+    }
+
+    pub fn matches_raw(&self, raw_data: &AnimeRawData) -> bool {
+        let raw_name = raw_data.anime_name.to_lowercase();
+
+        let contains = |term: &Option<String>| match term {
+            Some(term) => raw_name.contains(&term.to_lowercase()),
+            None => true,
+        };
+
+        raw_name.contains(&self.name.to_lowercase())
+            && contains(&self.raw_group)
+            && contains(&self.resolution)
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Default)]
+pub struct Watchlist(pub Vec<WatchlistEntry>);
+
+impl Watchlist {
+    pub fn load(path: &Path) -> Result<Self, JsonFileError> {
+        storage::load_json(path)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), JsonFileError> {
+        storage::save_json(self, path)
+    }
+
+    // Replaces the entry with the same name, if any
+    pub fn add(&mut self, entry: WatchlistEntry) {
+        self.remove(entry.name());
+        self.0.push(entry);
+    }
+
+    pub fn remove(&mut self, name: &str) -> bool {
+        let len = self.0.len();
+        self.0
+            .retain(|entry| entry.name.to_lowercase() != name.to_lowercase());
+        self.0.len() != len
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::magnet::MagnetLink;
+
+    fn raw(name: &str) -> AnimeRawData {
+        AnimeRawData {
+            anime_name: name.to_string(),
+            anime_raw_magnet: MagnetLink::parse(
+                "magnet:?xt=urn:btih:568807a73ecd33fff3ac19f47805f0940cbdb9ac",
+            )
+            .unwrap(),
+            anime_raw_torrent_url: "https://nyaa.si/download/1636619.torrent".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_raw_query_with_group() {
+        let entry = WatchlistEntry::new("One Piece", Some("Fumi-Raws"), None, None);
+        assert_eq!(entry.raw_query(), "One Piece Fumi-Raws");
+        assert_eq!(
+            entry.with_name("ONE PIECE").raw_query(),
+            "ONE PIECE Fumi-Raws"
+        );
+    }
+
+    #[test]
+    fn test_matches_raw_filters_group_and_resolution() {
+        let entry = WatchlistEntry::new("One Piece", Some("Fumi-Raws"), Some("1920x1080"), None);
+
+        assert!(entry.matches_raw(&raw(
+            "[Fumi-Raws] (One Piece (1051) - (フジテレビ 1920x1080).mkv"
+        )));
+        assert!(!entry.matches_raw(&raw(
+            "[Fumi-Raws] (One Piece (1051) - (フジテレビ 1280x720).mkv"
+        )));
+        assert!(!entry.matches_raw(&raw("[Ohys-Raws] One Piece - 1051 (CX 1920x1080).mp4")));
+    }
+
+    #[test]
+    fn test_add_replaces_entry_with_same_name() {
+        let mut watchlist = Watchlist::default();
+        watchlist.add(WatchlistEntry::new("Bocchi the Rock!", None, None, None));
+        watchlist.add(WatchlistEntry::new(
+            "bocchi the rock!",
+            Some("Ohys-Raws"),
+            None,
+            None,
+        ));
+
+        assert_eq!(watchlist.0.len(), 1);
+        assert_eq!(watchlist.0[0].raw_group(), &Some("Ohys-Raws".to_string()));
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut watchlist = Watchlist::default();
+        watchlist.add(WatchlistEntry::new("Bocchi the Rock!", None, None, None));
+
+        assert!(!watchlist.remove("ReLIFE"));
+        assert!(watchlist.remove("Bocchi the Rock!"));
+        assert!(watchlist.0.is_empty());
+    }
+}